@@ -0,0 +1,590 @@
+//! Frame animations from sprite sheets
+//!
+//! A [`SpriteSheet`] is a list of frames (texture regions) and named [`Clip`]s. Sheets are either
+//! sliced into a grid or loaded from Aseprite / TexturePacker JSON. An [`Animator`] plays a clip,
+//! advancing with the `dt` from the game loop:
+//!
+//! ```ignore
+//! let sheet = SpriteSheet::load_json("assets/player.json")?;
+//! let mut anim = Animator::new(sheet.clip("run").unwrap().clone().event(2, "step"))?;
+//!
+//! // every frame
+//! anim.update(dt);
+//! for ev in anim.events() { /* play the footstep sound */ }
+//! let uvs = anim.uv_corners(&sheet);
+//! ```
+
+mod json;
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::*;
+use vek::{Rect, Vec2};
+
+/// Frame duration of grid sheets and JSON without durations (TexturePacker)
+pub const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// Maximum playback rate of an [`Animator`]
+pub const MAX_SPEED: f32 = 100.0;
+
+/// Texture types with a pixel size, for slicing sheets into UV rects
+pub trait TextureSize {
+    fn texture_size(&self) -> [u32; 2];
+}
+
+impl TextureSize for [u32; 2] {
+    fn texture_size(&self) -> [u32; 2] {
+        *self
+    }
+}
+
+/// Region of the sheet texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetFrame {
+    pub name: String,
+    /// Pixel rect (top-left origin)
+    pub rect: Rect<u32, u32>,
+    pub duration: Duration,
+    /// Packed rotated by 90 degrees clockwise (TexturePacker). `rect` is the region in the
+    /// texture, so the width and height are swapped from the sprite's
+    pub rotated: bool,
+}
+
+/// Frames in a texture and named clips
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpriteSheet {
+    /// Texture size in pixels
+    pub size: [u32; 2],
+    pub frames: Vec<SheetFrame>,
+    pub clips: HashMap<String, Clip>,
+}
+
+impl SpriteSheet {
+    /// Slices the texture into cells in row-major order. Remaining pixels on the right and the
+    /// bottom are ignored
+    pub fn grid(texture: &impl TextureSize, cell: [u32; 2]) -> Self {
+        let size = texture.texture_size();
+        assert!(cell[0] > 0 && cell[1] > 0, "grid cell size {:?}", cell);
+
+        let (cols, rows) = (size[0] / cell[0], size[1] / cell[1]);
+        let frames = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .enumerate()
+            .map(|(i, (row, col))| SheetFrame {
+                name: i.to_string(),
+                rect: Rect::new(col * cell[0], row * cell[1], cell[0], cell[1]),
+                duration: DEFAULT_FRAME_DURATION,
+                rotated: false,
+            })
+            .collect();
+
+        Self {
+            size,
+            frames,
+            clips: HashMap::new(),
+        }
+    }
+
+    /// Loads Aseprite or TexturePacker JSON (either hash or array)
+    ///
+    /// Aseprite tags become clips. The texture path is not loaded (see `meta.image`).
+    pub fn load_json(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .with_context(|| format!("loading sprite sheet: {}", path.display()))?;
+        Self::from_json(&src).with_context(|| format!("loading sprite sheet: {}", path.display()))
+    }
+
+    /// See [`load_json`](Self::load_json)
+    pub fn from_json(src: &str) -> Result<Self> {
+        json::parse(src)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+
+    pub fn add_clip(&mut self, name: impl Into<String>, clip: Clip) -> &mut Self {
+        self.clips.insert(name.into(), clip);
+        self
+    }
+
+    /// Clip over sheet frames with their own durations
+    pub fn make_clip(&self, frames: impl IntoIterator<Item = usize>, mode: PlayMode) -> Clip {
+        let frames = frames
+            .into_iter()
+            .map(|frame| ClipFrame {
+                frame,
+                duration: self.frames[frame].duration,
+            })
+            .collect();
+        Clip::new(frames, mode)
+    }
+
+    pub fn frame_index(&self, name: &str) -> Option<usize> {
+        self.frames.iter().position(|f| f.name == name)
+    }
+
+    /// Normalized texture region of a frame (top-left origin)
+    ///
+    /// The region of a [`rotated`](SheetFrame::rotated) frame is rotated, too. Prefer
+    /// [`uv_corners`](Self::uv_corners) unless the packer is known to not rotate frames.
+    pub fn uv_rect(&self, frame: usize) -> Rect<f32, f32> {
+        let r = self.frames[frame].rect;
+        let (w, h) = (self.size[0].max(1) as f32, self.size[1].max(1) as f32);
+        Rect::new(
+            r.x as f32 / w,
+            r.y as f32 / h,
+            r.w as f32 / w,
+            r.h as f32 / h,
+        )
+    }
+
+    /// UVs of the sprite's top-left, top-right, bottom-right and bottom-left corners
+    ///
+    /// Rotated frames are turned back to the sprite's orientation.
+    pub fn uv_corners(&self, frame: usize) -> [Vec2<f32>; 4] {
+        let r = self.uv_rect(frame);
+        let (x0, y0, x1, y1) = (r.x, r.y, r.x + r.w, r.y + r.h);
+        let corners = [
+            Vec2::new(x0, y0),
+            Vec2::new(x1, y0),
+            Vec2::new(x1, y1),
+            Vec2::new(x0, y1),
+        ];
+
+        if self.frames[frame].rotated {
+            // packed clockwise: the sprite's top-left is the region's top-right
+            [corners[1], corners[2], corners[3], corners[0]]
+        } else {
+            corners
+        }
+    }
+}
+
+/// What happens after the last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Loop,
+    /// Back and forth, without repeating the end frames
+    PingPong,
+    /// Stops at the last frame
+    Once,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipFrame {
+    /// Index of [`SpriteSheet::frames`]
+    pub frame: usize,
+    pub duration: Duration,
+}
+
+/// Named event fired when a clip enters a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameEvent {
+    /// Index of [`Clip::frames`]
+    pub frame: usize,
+    pub name: String,
+}
+
+/// Sequence of sheet frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub frames: Vec<ClipFrame>,
+    pub mode: PlayMode,
+    pub events: Vec<FrameEvent>,
+}
+
+impl Clip {
+    pub fn new(frames: Vec<ClipFrame>, mode: PlayMode) -> Self {
+        Self {
+            frames,
+            mode,
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an event on entering the `frame`-th frame of the clip
+    pub fn event(mut self, frame: usize, name: impl Into<String>) -> Self {
+        self.events.push(FrameEvent {
+            frame,
+            name: name.into(),
+        });
+        self
+    }
+
+    pub fn mode(mut self, mode: PlayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Duration of one pass
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+}
+
+/// Plays a [`Clip`]
+#[derive(Debug, Clone)]
+pub struct Animator {
+    clip: Clip,
+    /// Index of `clip.frames`
+    pos: usize,
+    /// Time spent on the current frame
+    elapsed: Duration,
+    /// Going backwards (ping-pong)
+    reverse: bool,
+    started: bool,
+    finished: bool,
+    /// Indices of `clip.events` fired on the last update
+    fired: Vec<usize>,
+    /// Playback rate in `0.0..=MAX_SPEED`
+    speed: f32,
+    pub paused: bool,
+}
+
+impl Animator {
+    /// Fails if the clip has no frame
+    pub fn new(clip: Clip) -> Result<Self> {
+        ensure!(!clip.frames.is_empty(), "can't play a clip without frames");
+        Ok(Self::with_clip(clip))
+    }
+
+    fn with_clip(clip: Clip) -> Self {
+        Self {
+            clip,
+            pos: 0,
+            elapsed: Duration::ZERO,
+            reverse: false,
+            started: false,
+            finished: false,
+            fired: Vec::new(),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Switches to another clip from the start. Fails if the clip has no frame
+    pub fn play(&mut self, clip: Clip) -> Result<()> {
+        ensure!(!clip.frames.is_empty(), "can't play a clip without frames");
+        self.reset(clip);
+        Ok(())
+    }
+
+    pub fn restart(&mut self) {
+        let clip = std::mem::replace(&mut self.clip, Clip::new(Vec::new(), PlayMode::Once));
+        self.reset(clip);
+    }
+
+    /// Starts the clip over, keeping the speed and the pause state
+    fn reset(&mut self, clip: Clip) {
+        let (speed, paused) = (self.speed, self.paused);
+        *self = Self::with_clip(clip);
+        self.speed = speed;
+        self.paused = paused;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the playback rate, clamped to `0.0..=MAX_SPEED` (NaN is `0.0`)
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = if speed.is_nan() {
+            0.0
+        } else {
+            speed.clamp(0.0, MAX_SPEED)
+        };
+    }
+
+    pub fn clip(&self) -> &Clip {
+        &self.clip
+    }
+
+    /// Index of [`Clip::frames`]
+    pub fn clip_pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Index of [`SpriteSheet::frames`]
+    pub fn frame(&self) -> usize {
+        self.clip.frames[self.pos].frame
+    }
+
+    /// See [`SpriteSheet::uv_rect`]
+    pub fn uv_rect(&self, sheet: &SpriteSheet) -> Rect<f32, f32> {
+        sheet.uv_rect(self.frame())
+    }
+
+    /// See [`SpriteSheet::uv_corners`]
+    pub fn uv_corners(&self, sheet: &SpriteSheet) -> [Vec2<f32>; 4] {
+        sheet.uv_corners(self.frame())
+    }
+
+    /// A [`PlayMode::Once`] clip reached the end of its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Names of the events fired on the last [`update`](Self::update)
+    pub fn events(&self) -> impl Iterator<Item = &str> + '_ {
+        self.fired
+            .iter()
+            .map(move |&i| self.clip.events[i].name.as_str())
+    }
+
+    /// Advances the animation. Multiple frames can be passed in one update
+    pub fn update(&mut self, dt: Duration) {
+        self.fired.clear();
+
+        if !self.started {
+            self.started = true;
+            self.fire();
+        }
+
+        if self.paused || self.finished || self.clip.duration().is_zero() {
+            return;
+        }
+
+        self.elapsed += dt.mul_f32(self.speed);
+        loop {
+            let duration = self.clip.frames[self.pos].duration;
+            if self.elapsed < duration {
+                break;
+            }
+
+            self.elapsed -= duration;
+            if !self.advance() {
+                self.finished = true;
+                self.elapsed = Duration::ZERO;
+                break;
+            }
+            self.fire();
+        }
+    }
+
+    /// Goes to the next frame. Returns false at the end of a [`PlayMode::Once`] clip
+    fn advance(&mut self) -> bool {
+        let len = self.clip.frames.len();
+        match self.clip.mode {
+            PlayMode::Loop => {
+                self.pos = (self.pos + 1) % len;
+            }
+            PlayMode::Once => {
+                if self.pos + 1 >= len {
+                    return false;
+                }
+                self.pos += 1;
+            }
+            PlayMode::PingPong => {
+                if len == 1 {
+                    return true;
+                }
+                if self.reverse && self.pos == 0 || !self.reverse && self.pos + 1 == len {
+                    self.reverse = !self.reverse;
+                }
+                if self.reverse {
+                    self.pos -= 1;
+                } else {
+                    self.pos += 1;
+                }
+            }
+        }
+        true
+    }
+
+    fn fire(&mut self) {
+        let pos = self.pos;
+        self.fired.extend(
+            self.clip
+                .events
+                .iter()
+                .enumerate()
+                .filter(|(_, ev)| ev.frame == pos)
+                .map(|(i, _)| i),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(n: usize, mode: PlayMode) -> Clip {
+        let frames = (0..n)
+            .map(|frame| ClipFrame {
+                frame,
+                duration: DEFAULT_FRAME_DURATION,
+            })
+            .collect();
+        Clip::new(frames, mode)
+    }
+
+    #[test]
+    fn empty_clips_are_rejected() {
+        assert!(Animator::new(clip(0, PlayMode::Loop)).is_err());
+
+        let mut anim = Animator::new(clip(2, PlayMode::Loop)).unwrap();
+        assert!(anim.play(clip(0, PlayMode::Once)).is_err());
+        // the old clip keeps playing
+        anim.update(DEFAULT_FRAME_DURATION);
+        assert_eq!(anim.frame(), 1);
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut anim = Animator::new(clip(3, PlayMode::Loop)).unwrap();
+
+        for (speed, expected) in [
+            (f32::INFINITY, MAX_SPEED),
+            (f32::NEG_INFINITY, 0.0),
+            (-2.0, 0.0),
+            (f32::NAN, 0.0),
+            (2.0, 2.0),
+        ] {
+            anim.set_speed(speed);
+            assert_eq!(anim.speed(), expected, "{}", speed);
+            anim.update(Duration::from_secs(1));
+        }
+
+        anim.restart();
+        assert_eq!(anim.speed(), 2.0);
+        anim.update(DEFAULT_FRAME_DURATION);
+        assert_eq!(anim.frame(), 2);
+    }
+
+    /// Frames shown by updates of one frame duration each, after the first frame
+    fn sequence(anim: &mut Animator, n: usize) -> Vec<usize> {
+        anim.update(Duration::ZERO);
+        (0..n)
+            .map(|_| {
+                anim.update(DEFAULT_FRAME_DURATION);
+                anim.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn play_modes() {
+        let mut anim = Animator::new(clip(3, PlayMode::Loop)).unwrap();
+        assert_eq!(sequence(&mut anim, 5), [1, 2, 0, 1, 2]);
+        assert!(!anim.is_finished());
+
+        let mut anim = Animator::new(clip(3, PlayMode::PingPong)).unwrap();
+        assert_eq!(sequence(&mut anim, 7), [1, 2, 1, 0, 1, 2, 1]);
+
+        let mut anim = Animator::new(clip(3, PlayMode::Once)).unwrap();
+        assert_eq!(sequence(&mut anim, 2), [1, 2]);
+        assert!(!anim.is_finished());
+        assert_eq!(sequence(&mut anim, 2), [2, 2]);
+        assert!(anim.is_finished());
+    }
+
+    #[test]
+    fn events() {
+        let clip = clip(4, PlayMode::Loop).event(0, "start").event(2, "step");
+        let mut anim = Animator::new(clip.event(3, "end")).unwrap();
+
+        // the first frame fires on the first update
+        anim.update(Duration::from_millis(50));
+        assert_eq!(anim.events().collect::<Vec<_>>(), ["start"]);
+        anim.update(Duration::ZERO);
+        assert_eq!(anim.events().count(), 0);
+
+        // every frame passed in one update fires in order
+        anim.update(Duration::from_millis(300));
+        assert_eq!(anim.frame(), 3);
+        assert_eq!(anim.events().collect::<Vec<_>>(), ["step", "end"]);
+
+        // the remainder is kept
+        anim.update(Duration::from_millis(50));
+        assert_eq!(anim.frame(), 0);
+        assert_eq!(anim.events().collect::<Vec<_>>(), ["start"]);
+    }
+
+    const HASH: &str = r#"{
+        "frames": {
+            "a": { "frame": { "x": 0, "y": 0, "w": 16, "h": 8 }, "duration": 50 },
+            "b": { "frame": { "x": 16, "y": 0, "w": 16, "h": 8 }, "rotated": true }
+        },
+        "meta": { "size": { "w": 32, "h": 32 } }
+    }"#;
+
+    const ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "a", "frame": { "x": 0, "y": 0, "w": 16, "h": 8 }, "duration": 50 },
+            { "filename": "b", "frame": { "x": 16, "y": 0, "w": 16, "h": 8 }, "rotated": true }
+        ],
+        "meta": { "size": { "w": 32, "h": 32 } }
+    }"#;
+
+    #[test]
+    fn json_hash_and_array() {
+        let sheet = SpriteSheet::from_json(HASH).unwrap();
+        assert_eq!(sheet, SpriteSheet::from_json(ARRAY).unwrap());
+
+        assert_eq!(sheet.frame_index("b"), Some(1));
+        assert_eq!(sheet.frames[0].duration, Duration::from_millis(50));
+        assert_eq!(sheet.frames[1].duration, DEFAULT_FRAME_DURATION);
+        // the rotated region is 8x16 in the texture
+        assert_eq!(sheet.frames[1].rect, Rect::new(16, 0, 8, 16));
+    }
+
+    #[test]
+    fn rotated_uvs() {
+        let sheet = SpriteSheet::from_json(HASH).unwrap();
+
+        let uv = |x, y| Vec2::new(x, y);
+        assert_eq!(
+            sheet.uv_corners(0),
+            [uv(0.0, 0.0), uv(0.5, 0.0), uv(0.5, 0.25), uv(0.0, 0.25)]
+        );
+        assert_eq!(
+            sheet.uv_corners(1),
+            [uv(0.75, 0.0), uv(0.75, 0.5), uv(0.5, 0.5), uv(0.5, 0.0)]
+        );
+    }
+
+    #[test]
+    fn frame_tags() {
+        let frames = (0..4)
+            .map(|i| {
+                format!(
+                    r#"{{ "filename": "{}", "frame": {{ "x": {}, "y": 0, "w": 8, "h": 8 }} }}"#,
+                    i,
+                    i * 8
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let sheet = |tags: &str| {
+            SpriteSheet::from_json(&format!(
+                r#"{{ "frames": [{}], "meta": {{ "size": {{ "w": 32, "h": 8 }}, "frameTags": [{}] }} }}"#,
+                frames, tags
+            ))
+        };
+
+        let s = sheet(
+            r#"{ "name": "fwd", "from": 0, "to": 2 },
+               { "name": "rev", "from": 1, "to": 3, "direction": "reverse" },
+               { "name": "pp", "from": 0, "to": 1, "direction": "pingpong" },
+               { "name": "ppr", "from": 2, "to": 3, "direction": "pingpong_reverse" },
+               { "name": "once", "from": 3, "to": 3, "direction": "forward", "repeat": "1" }"#,
+        )
+        .unwrap();
+
+        let clip = |name: &str| {
+            let clip = s.clip(name).unwrap();
+            let frames = clip.frames.iter().map(|f| f.frame).collect::<Vec<_>>();
+            (frames, clip.mode)
+        };
+        assert_eq!(clip("fwd"), (vec![0, 1, 2], PlayMode::Loop));
+        assert_eq!(clip("rev"), (vec![3, 2, 1], PlayMode::Loop));
+        assert_eq!(clip("pp"), (vec![0, 1], PlayMode::PingPong));
+        assert_eq!(clip("ppr"), (vec![3, 2], PlayMode::PingPong));
+        assert_eq!(clip("once"), (vec![3], PlayMode::Once));
+
+        assert!(sheet(r#"{ "name": "x", "from": 2, "to": 4 }"#).is_err());
+        assert!(sheet(r#"{ "name": "x", "from": 0, "to": 1, "direction": "up" }"#).is_err());
+    }
+}
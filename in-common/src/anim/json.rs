@@ -0,0 +1,176 @@
+//! Aseprite / TexturePacker JSON
+//!
+//! Both tools export the same schema (`frames` as a hash or an array, and `meta.size`). Aseprite
+//! adds frame durations and `meta.frameTags`.
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use anyhow::*;
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use vek::Rect;
+
+use crate::anim::{Clip, ClipFrame, PlayMode, SheetFrame, SpriteSheet, DEFAULT_FRAME_DURATION};
+
+#[derive(Debug, Deserialize)]
+struct Sheet {
+    frames: Frames,
+    meta: Meta,
+}
+
+/// Frames in file order. The hash format is read as a list, too
+#[derive(Debug)]
+struct Frames(Vec<(String, Frame)>);
+
+#[derive(Debug, Deserialize)]
+struct NamedFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+#[derive(Debug, Deserialize)]
+struct Frame {
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    /// Milliseconds (Aseprite)
+    duration: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    size: JsonSize,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonSize {
+    w: u32,
+    h: u32,
+}
+
+/// Aseprite tag
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    /// Number of passes (Aseprite 1.3)
+    repeat: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> std::result::Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "frames as an array or a map")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(f) = seq.next_element::<NamedFrame>()? {
+                    frames.push((f.filename, f.frame));
+                }
+                std::result::Result::Ok(Frames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(entry) = map.next_entry::<String, Frame>()? {
+                    frames.push(entry);
+                }
+                std::result::Result::Ok(Frames(frames))
+            }
+        }
+
+        de.deserialize_any(FramesVisitor)
+    }
+}
+
+pub fn parse(src: &str) -> Result<SpriteSheet> {
+    let sheet: Sheet = serde_json::from_str(src)?;
+
+    let frames = sheet
+        .frames
+        .0
+        .into_iter()
+        .map(|(name, f)| {
+            let r = f.frame;
+            // the packed region is rotated
+            let (w, h) = if f.rotated { (r.h, r.w) } else { (r.w, r.h) };
+            SheetFrame {
+                name,
+                rect: Rect::new(r.x, r.y, w, h),
+                duration: f
+                    .duration
+                    .map_or(DEFAULT_FRAME_DURATION, Duration::from_millis),
+                rotated: f.rotated,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut clips = HashMap::new();
+    for tag in sheet.meta.frame_tags {
+        ensure!(
+            tag.from <= tag.to && tag.to < frames.len(),
+            "tag `{}`: frames {}..={} out of {}",
+            tag.name,
+            tag.from,
+            tag.to,
+            frames.len()
+        );
+
+        let (reverse, mut mode) = match tag.direction.as_str() {
+            "" | "forward" => (false, PlayMode::Loop),
+            "reverse" => (true, PlayMode::Loop),
+            "pingpong" => (false, PlayMode::PingPong),
+            "pingpong_reverse" => (true, PlayMode::PingPong),
+            x => bail!("tag `{}`: unknown direction `{}`", tag.name, x),
+        };
+        if tag.repeat.as_deref() == Some("1") {
+            mode = PlayMode::Once;
+        }
+
+        let mut clip_frames = (tag.from..=tag.to)
+            .map(|frame| ClipFrame {
+                frame,
+                duration: frames[frame].duration,
+            })
+            .collect::<Vec<_>>();
+        if reverse {
+            clip_frames.reverse();
+        }
+
+        clips.insert(tag.name, Clip::new(clip_frames, mode));
+    }
+
+    Ok(SpriteSheet {
+        size: [sheet.meta.size.w, sheet.meta.size.h],
+        frames,
+        clips,
+    })
+}
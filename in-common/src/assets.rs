@@ -0,0 +1,348 @@
+//! Asset registry core: typed, reference-counted handles deduplicated by path and hot reload
+//!
+//! Backends wrap [`Cache`]s of their GPU resources (`Assets` in `in-wgpu` and `in-rokol`). A
+//! [`Handle`] is shared by every load of the same file and reloaded assets are swapped in place,
+//! so existing handles see the new contents.
+
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use anyhow::*;
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+/// Font asset
+pub type Font = fontdue::Font;
+
+struct Slot<T> {
+    /// Canonical path. `None` for assets not loaded from a file
+    path: Option<PathBuf>,
+    item: RefCell<T>,
+    /// Incremented on every reload
+    generation: Cell<u32>,
+}
+
+/// Reference-counted handle to an asset
+pub struct Handle<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: Rc::clone(&self.slot),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("path", &self.slot.path)
+            .field("generation", &self.slot.generation.get())
+            .finish()
+    }
+}
+
+impl<T> Handle<T> {
+    /// Creates a handle that is not backed by a file
+    pub fn new(item: T) -> Self {
+        Self::with_path(None, item)
+    }
+
+    fn with_path(path: Option<PathBuf>, item: T) -> Self {
+        Self {
+            slot: Rc::new(Slot {
+                path,
+                item: RefCell::new(item),
+                generation: Cell::new(0),
+            }),
+        }
+    }
+
+    /// Borrows the asset. Don't hold it over a reload
+    pub fn get(&self) -> Ref<'_, T> {
+        self.slot.item.borrow()
+    }
+
+    /// Swaps the asset in place, returning the old one
+    pub fn replace(&self, item: T) -> T {
+        self.slot
+            .generation
+            .set(self.slot.generation.get().wrapping_add(1));
+        self.slot.item.replace(item)
+    }
+
+    /// Canonical path of the asset file
+    pub fn path(&self) -> Option<&Path> {
+        self.slot.path.as_deref()
+    }
+
+    /// Changes on every reload. Use it to refresh resources derived from the asset
+    pub fn generation(&self) -> u32 {
+        self.slot.generation.get()
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+/// Canonicalizes an asset path, failing if the file does not exist
+pub fn resolve(path: &Path) -> Result<PathBuf> {
+    path.canonicalize()
+        .with_context(|| format!("asset not found: {}", path.display()))
+}
+
+/// Assets of one type deduplicated by path
+///
+/// The cache does not keep assets alive; an asset is dropped with its last [`Handle`].
+pub struct Cache<T> {
+    slots: HashMap<PathBuf, Weak<Slot<T>>>,
+}
+
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Self {
+            slots: HashMap::new(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Cache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.slots.keys()).finish()
+    }
+}
+
+impl<T> Cache<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the living handle for the path or loads the asset
+    pub fn load_with(
+        &mut self,
+        path: &Path,
+        load: impl FnOnce(&Path) -> Result<T>,
+    ) -> Result<Handle<T>> {
+        let path = self::resolve(path)?;
+
+        if let Some(slot) = self.slots.get(&path).and_then(Weak::upgrade) {
+            return Ok(Handle { slot });
+        }
+
+        let item = load(&path).with_context(|| format!("loading asset: {}", path.display()))?;
+        let handle = Handle::with_path(Some(path.clone()), item);
+        self.slots.insert(path, Rc::downgrade(&handle.slot));
+
+        Ok(handle)
+    }
+
+    /// Living handle for the path
+    pub fn get(&self, path: &Path) -> Option<Handle<T>> {
+        let path = path.canonicalize().ok()?;
+        self.slots
+            .get(&path)
+            .and_then(Weak::upgrade)
+            .map(|slot| Handle { slot })
+    }
+
+    /// Reloads the asset in place if it's alive. Returns `Ok(false)` if it's not loaded
+    ///
+    /// The old asset is kept on failure.
+    pub fn reload_with(
+        &mut self,
+        path: &Path,
+        load: impl FnOnce(&Path) -> Result<T>,
+    ) -> Result<bool> {
+        let handle = match self.get(path) {
+            Some(h) => h,
+            None => return Ok(false),
+        };
+
+        let path = handle.path().unwrap();
+        let item = load(path).with_context(|| format!("reloading asset: {}", path.display()))?;
+        handle.replace(item);
+
+        Ok(true)
+    }
+
+    /// Forgets dropped assets
+    pub fn gc(&mut self) {
+        self.slots.retain(|_, slot| slot.strong_count() > 0);
+    }
+
+    /// Paths of living assets
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.slots
+            .iter()
+            .filter(|(_, slot)| slot.strong_count() > 0)
+            .map(|(path, _)| path.as_path())
+    }
+}
+
+/// Watches asset files and reports modified assets
+///
+/// Parent directories are watched so that files replaced by editors (write to a temporary file
+/// and rename) are still tracked.
+pub struct Watcher {
+    watcher: RecommendedWatcher,
+    rx: Receiver<DebouncedEvent>,
+    dirs: HashSet<PathBuf>,
+    /// Watched file -> asset paths depending on it
+    files: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("dirs", &self.dirs)
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+impl Watcher {
+    /// * `delay`: events are debounced for this duration
+    pub fn new(delay: Duration) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::watcher(tx, delay)?;
+
+        Ok(Self {
+            watcher,
+            rx,
+            dirs: HashSet::new(),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Reports `asset` when `file` is modified. `asset` is usually `file` itself, but multi-file
+    /// assets (e.g. vertex + fragment shader) register each file under one asset path
+    pub fn watch(&mut self, file: &Path, asset: &Path) -> Result<()> {
+        let file = self::resolve(file)?;
+        let asset = self::resolve(asset)?;
+
+        let dir = file.parent().unwrap().to_path_buf();
+        if !self.dirs.contains(&dir) {
+            self.watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("watching directory: {}", dir.display()))?;
+            self.dirs.insert(dir);
+        }
+
+        self.files.entry(file).or_default().insert(asset);
+        Ok(())
+    }
+
+    /// Drains file events and returns paths of modified assets
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut assets = Vec::new();
+
+        while let std::result::Result::Ok(ev) = self.rx.try_recv() {
+            let file = match ev {
+                DebouncedEvent::Write(p) | DebouncedEvent::Create(p) => p,
+                DebouncedEvent::Rename(_from, to) => to,
+                DebouncedEvent::Error(err, path) => {
+                    log::warn!("file watcher error ({:?}): {}", path, err);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if let Some(deps) = self.files.get(&file) {
+                for asset in deps {
+                    if !assets.contains(asset) {
+                        assets.push(asset.clone());
+                    }
+                }
+            }
+        }
+
+        assets
+    }
+}
+
+/// Loads a TrueType or OpenType font
+pub fn load_font(path: &Path) -> Result<Font> {
+    let bytes = std::fs::read(path)?;
+    Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(Error::msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+    }
+
+    fn len(path: &Path) -> Result<usize> {
+        Ok(std::fs::read(path)?.len())
+    }
+
+    #[test]
+    fn dedupe() {
+        let mut cache = Cache::new();
+        let a = cache.load_with(&file("Cargo.toml"), len).unwrap();
+        // another path to the same file
+        let b = cache
+            .load_with(&file("src/../Cargo.toml"), |_| panic!("loaded twice"))
+            .unwrap();
+        assert!(a.ptr_eq(&b));
+        assert_eq!(
+            a.path(),
+            Some(file("Cargo.toml").canonicalize().unwrap().as_path())
+        );
+
+        let c = cache.load_with(&file("src/lib.rs"), len).unwrap();
+        assert!(!a.ptr_eq(&c));
+        assert_eq!(cache.paths().count(), 2);
+
+        // dropped with the last handle
+        drop((a, b));
+        assert!(cache.get(&file("Cargo.toml")).is_none());
+        cache.gc();
+        assert_eq!(cache.paths().collect::<Vec<_>>(), [c.path().unwrap()]);
+    }
+
+    #[test]
+    fn not_found() {
+        let mut cache = Cache::<usize>::new();
+        let err = cache.load_with(&file("missing.txt"), len).unwrap_err();
+        assert!(err.to_string().contains("asset not found"), "{}", err);
+
+        let err = cache
+            .load_with(&file("Cargo.toml"), |_| Err(anyhow!("bad asset")))
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("bad asset"), "{:#}", err);
+    }
+
+    #[test]
+    fn reload() {
+        let mut cache = Cache::new();
+        let path = file("Cargo.toml");
+        assert!(!cache.reload_with(&path, len).unwrap());
+
+        let handle = cache.load_with(&path, |_| Ok(0)).unwrap();
+        assert_eq!(handle.generation(), 0);
+        assert!(cache.reload_with(&path, len).unwrap());
+        assert_eq!(
+            (*handle.get(), handle.generation()),
+            (len(&path).unwrap(), 1)
+        );
+
+        // the old asset is kept on failure
+        assert!(cache.reload_with(&path, |_| Err(anyhow!("bad"))).is_err());
+        assert_eq!(
+            (*handle.get(), handle.generation()),
+            (len(&path).unwrap(), 1)
+        );
+    }
+}
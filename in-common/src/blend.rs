@@ -0,0 +1,153 @@
+//! Blend mode presets and premultiplied alpha
+//!
+//! Filtering straight-alpha textures mixes the color of transparent texels into the edges of
+//! sprites (dark fringes). Premultiply the textures on load and draw them with
+//! [`BlendMode::Premultiplied`] to avoid it.
+//!
+//! Premultiply sRGB textures with [`premultiply_alpha_srgb`] so that the GPU decodes the
+//! premultiplied color as `linear(color) * alpha`.
+
+/// Blend factor of a [`BlendComponent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstColor,
+    OneMinusDstColor,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+/// `src * src_factor + dst * dst_factor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendComponent {
+    pub src: BlendFactor,
+    pub dst: BlendFactor,
+}
+
+impl BlendComponent {
+    pub const fn new(src: BlendFactor, dst: BlendFactor) -> Self {
+        Self { src, dst }
+    }
+}
+
+/// Blend equations for color and alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendEquation {
+    pub color: BlendComponent,
+    pub alpha: BlendComponent,
+}
+
+/// Named blend presets
+///
+/// The alpha channel is composited with "over" in every mode but [`Opaque`](Self::Opaque).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Overwrites the destination
+    Opaque,
+    /// Straight (non-premultiplied) alpha
+    #[default]
+    Alpha,
+    /// Premultiplied alpha. Vertex colors and tints have to be premultiplied, too
+    Premultiplied,
+    /// Adds premultiplied color
+    Additive,
+    /// `src * dst` for premultiplied color. Transparent texels keep the destination
+    Multiply,
+    /// `1 - (1 - src) * (1 - dst)` for premultiplied color
+    Screen,
+}
+
+impl BlendMode {
+    /// `None` for no blending
+    pub fn equation(self) -> Option<BlendEquation> {
+        use BlendFactor::*;
+
+        let color = match self {
+            Self::Opaque => return None,
+            Self::Alpha => BlendComponent::new(SrcAlpha, OneMinusSrcAlpha),
+            Self::Premultiplied => BlendComponent::new(One, OneMinusSrcAlpha),
+            Self::Additive => BlendComponent::new(One, One),
+            Self::Multiply => BlendComponent::new(DstColor, OneMinusSrcAlpha),
+            Self::Screen => BlendComponent::new(One, OneMinusSrcColor),
+        };
+
+        Some(BlendEquation {
+            color,
+            alpha: BlendComponent::new(One, OneMinusSrcAlpha),
+        })
+    }
+}
+
+/// Multiplies RGB with alpha in RGBA8 pixels, for textures sampled without sRGB decoding
+pub fn premultiply_alpha(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        for c in &mut px[..3] {
+            // rounded `c * a / 255`
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Multiplies RGB with alpha in linear space in sRGB-encoded RGBA8 pixels
+pub fn premultiply_alpha_srgb(rgba: &mut [u8]) {
+    let mut linear = [0.0f32; 256];
+    for (i, x) in linear.iter_mut().enumerate() {
+        *x = self::srgb_to_linear(i as f32 / 255.0);
+    }
+
+    for px in rgba.chunks_exact_mut(4) {
+        let a = px[3] as f32 / 255.0;
+        for c in &mut px[..3] {
+            let x = self::linear_to_srgb(linear[*c as usize] * a);
+            *c = (x * 255.0).round() as u8;
+        }
+    }
+}
+
+fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply() {
+        let mut px = [200, 100, 1, 255, 200, 255, 1, 128, 200, 100, 255, 0];
+        premultiply_alpha(&mut px);
+        assert_eq!(px, [200, 100, 1, 255, 100, 128, 1, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn premultiply_srgb() {
+        // half-transparent white is brighter than 128 in sRGB
+        let mut px = [255, 128, 10, 128, 200, 100, 1, 255, 255, 255, 255, 0];
+        premultiply_alpha_srgb(&mut px);
+        assert_eq!(px, [188, 93, 5, 128, 200, 100, 1, 255, 0, 0, 0, 0]);
+
+        // round trip of every value
+        for i in 0..=255u8 {
+            let x = linear_to_srgb(srgb_to_linear(i as f32 / 255.0));
+            assert_eq!((x * 255.0).round() as u8, i);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! 2D camera in Y-down world coordinates (pixels)
+
+use vek::{Extent2, Mat4, Rect, Vec2};
+
+/// Orthographic 2D camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2d {
+    /// World position at the center of the screen
+    pub pos: Vec2<f32>,
+    /// Screen pixels per world unit
+    pub zoom: f32,
+    /// Screen size in pixels
+    pub viewport: Extent2<f32>,
+}
+
+impl Camera2d {
+    pub fn new(viewport: impl Into<Extent2<f32>>) -> Self {
+        let viewport = viewport.into();
+        Self {
+            pos: Vec2::new(viewport.w / 2.0, viewport.h / 2.0),
+            zoom: 1.0,
+            viewport,
+        }
+    }
+
+    /// Visible area in world coordinates
+    pub fn view_rect(&self) -> Rect<f32, f32> {
+        let size = self.viewport / self.zoom;
+        Rect::new(
+            self.pos.x - size.w / 2.0,
+            self.pos.y - size.h / 2.0,
+            size.w,
+            size.h,
+        )
+    }
+
+    /// World to clip space (Y up, `z` kept at zero)
+    pub fn matrix(&self) -> Mat4<f32> {
+        let r = self.view_rect();
+        let (sx, sy) = (2.0 / r.w, -2.0 / r.h);
+        #[rustfmt::skip]
+        let m = Mat4::new(
+            sx, 0.0, 0.0, -1.0 - r.x * sx,
+            0.0, sy, 0.0, 1.0 - r.y * sy,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        m
+    }
+
+    pub fn screen_to_world(&self, screen: Vec2<f32>) -> Vec2<f32> {
+        let r = self.view_rect();
+        Vec2::new(r.x, r.y) + screen / self.zoom
+    }
+
+    pub fn world_to_screen(&self, world: Vec2<f32>) -> Vec2<f32> {
+        let r = self.view_rect();
+        (world - Vec2::new(r.x, r.y)) * self.zoom
+    }
+}
@@ -0,0 +1,312 @@
+//! Clip-rect stack for scissor tests
+//!
+//! Rects are in logical (window) coordinates or frame buffer pixels, both with the top-left
+//! origin. Nested rects are intersected, and the current clip is a pixel rect for
+//! `set_scissor_rect` (`in-wgpu`) or `rg::apply_scissor_rect` (`in-rokol`).
+//!
+//! Draws under different clips need separate draw calls. [`ClippedMesh`] collects meshes into
+//! one buffer and splits it into [`ClipBatch`]es whenever the clip changes:
+//!
+//! ```ignore
+//! clip.push_logical(Rect::new(16.0, 16.0, 200.0, 120.0));
+//! batch.push(&clip, &panel);
+//! clip.pop();
+//! batch.push(&clip, &cursor);
+//! ```
+
+use vek::{Extent2, Rect, Rgba, Vec2};
+
+use crate::mesh::MeshData;
+
+/// Nested clip rects in frame buffer pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipStack {
+    fb_size: Extent2<u32>,
+    /// Window size to frame buffer size
+    dpi_scale: Vec2<f32>,
+    /// Intersected rects (pixels)
+    stack: Vec<Rect<f32, f32>>,
+}
+
+impl ClipStack {
+    /// * `dpi_scale`: `WindowWrapper::dpi_scale` in `in-wgpu`
+    pub fn new(fb_size: impl Into<Extent2<u32>>, dpi_scale: impl Into<Vec2<f32>>) -> Self {
+        Self {
+            fb_size: fb_size.into(),
+            dpi_scale: dpi_scale.into(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Update it on window resize. Pushed rects are kept in pixels
+    pub fn set_viewport(
+        &mut self,
+        fb_size: impl Into<Extent2<u32>>,
+        dpi_scale: impl Into<Vec2<f32>>,
+    ) {
+        self.fb_size = fb_size.into();
+        self.dpi_scale = dpi_scale.into();
+    }
+
+    pub fn fb_size(&self) -> Extent2<u32> {
+        self.fb_size
+    }
+
+    pub fn dpi_scale(&self) -> Vec2<f32> {
+        self.dpi_scale
+    }
+
+    pub fn to_pixels(&self, logical: Rect<f32, f32>) -> Rect<f32, f32> {
+        Rect::new(
+            logical.x * self.dpi_scale.x,
+            logical.y * self.dpi_scale.y,
+            logical.w * self.dpi_scale.x,
+            logical.h * self.dpi_scale.y,
+        )
+    }
+
+    pub fn to_logical(&self, pixels: Rect<f32, f32>) -> Rect<f32, f32> {
+        Rect::new(
+            pixels.x / self.dpi_scale.x,
+            pixels.y / self.dpi_scale.y,
+            pixels.w / self.dpi_scale.x,
+            pixels.h / self.dpi_scale.y,
+        )
+    }
+
+    /// Pushes a rect in window coordinates
+    pub fn push_logical(&mut self, rect: Rect<f32, f32>) {
+        self.push_pixels(self.to_pixels(rect));
+    }
+
+    /// Pushes a rect in frame buffer pixels
+    pub fn push_pixels(&mut self, rect: Rect<f32, f32>) {
+        let rect = match self.stack.last() {
+            Some(top) => self::intersect(*top, rect),
+            None => rect,
+        };
+        self.stack.push(rect);
+    }
+
+    /// Pops the innermost rect. Returns `None` if the stack is empty
+    pub fn pop(&mut self) -> Option<Rect<f32, f32>> {
+        self.stack.pop()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Current clip in window coordinates. `None` if nothing is pushed
+    pub fn current_logical(&self) -> Option<Rect<f32, f32>> {
+        self.stack.last().map(|r| self.to_logical(*r))
+    }
+
+    /// Scissor rect in pixels, covering partially clipped pixels and clamped to the frame
+    /// buffer. The whole frame buffer if nothing is pushed
+    pub fn scissor(&self) -> Rect<u32, u32> {
+        let full = Rect::new(0, 0, self.fb_size.w, self.fb_size.h);
+        let r = match self.stack.last() {
+            Some(r) => *r,
+            None => return full,
+        };
+
+        let clamp = |x: f32, max: u32| (x.max(0.0) as u32).min(max);
+        let x0 = clamp(r.x.floor(), full.w);
+        let y0 = clamp(r.y.floor(), full.h);
+        let x1 = clamp((r.x + r.w).ceil(), full.w).max(x0);
+        let y1 = clamp((r.y + r.h).ceil(), full.h).max(y0);
+        Rect::new(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    /// If everything is clipped out
+    pub fn is_empty(&self) -> bool {
+        let r = self.scissor();
+        r.w == 0 || r.h == 0
+    }
+}
+
+/// Intersection with zero size if they don't overlap
+fn intersect(a: Rect<f32, f32>, b: Rect<f32, f32>) -> Rect<f32, f32> {
+    let x0 = a.x.max(b.x);
+    let y0 = a.y.max(b.y);
+    let x1 = (a.x + a.w).min(b.x + b.w).max(x0);
+    let y1 = (a.y + a.h).min(b.y + b.h).max(y0);
+    Rect::new(x0, y0, x1 - x0, y1 - y0)
+}
+
+/// Index range drawn with one scissor rect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipBatch {
+    /// Pixels (top-left origin)
+    pub scissor: Rect<u32, u32>,
+    /// First index in the index buffer
+    pub start: u32,
+    /// Number of indices
+    pub count: u32,
+}
+
+impl ClipBatch {
+    /// Range in the index buffer
+    pub fn indices(&self) -> std::ops::Range<u32> {
+        self.start..self.start + self.count
+    }
+
+    /// If the scissor rect has no pixel (the draw can be skipped)
+    pub fn is_empty(&self) -> bool {
+        self.scissor.w == 0 || self.scissor.h == 0 || self.count == 0
+    }
+}
+
+/// 2D meshes merged into one buffer, split into batches by clip rect
+#[derive(Debug, Clone, Default)]
+pub struct ClippedMesh {
+    /// Positions, UVs and colors
+    pub mesh: MeshData,
+    pub batches: Vec<ClipBatch>,
+}
+
+impl ClippedMesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.mesh = MeshData::default();
+        self.batches.clear();
+    }
+
+    /// Appends a mesh under the current clip. Starts a new batch if the clip has changed
+    pub fn push(&mut self, clip: &ClipStack, mesh: &MeshData) {
+        let scissor = clip.scissor();
+        let start = self.mesh.indices.len() as u32;
+        let count = mesh.indices.len() as u32;
+
+        match self.batches.last_mut() {
+            Some(last) if last.scissor == scissor => last.count += count,
+            _ => self.batches.push(ClipBatch {
+                scissor,
+                start,
+                count,
+            }),
+        }
+
+        // fill missing attributes so that every attribute array stays aligned
+        let base = self.mesh.positions.len() as u32;
+        let n = mesh.n_verts();
+        self.mesh.positions.extend_from_slice(&mesh.positions);
+        self.mesh
+            .uvs
+            .extend((0..n).map(|i| mesh.uvs.get(i).copied().unwrap_or_default()));
+        self.mesh
+            .colors
+            .extend((0..n).map(|i| mesh.colors.get(i).copied().unwrap_or_else(Rgba::white)));
+        self.mesh
+            .indices
+            .extend(mesh.indices.iter().map(|i| base + i));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::Vec3;
+
+    use super::*;
+
+    fn quad(x: f32) -> MeshData {
+        MeshData {
+            positions: vec![
+                Vec3::new(x, 0.0, 0.0),
+                Vec3::new(x + 1.0, 0.0, 0.0),
+                Vec3::new(x + 1.0, 1.0, 0.0),
+                Vec3::new(x, 1.0, 0.0),
+            ],
+            indices: vec![0, 1, 2, 2, 3, 0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nested_intersection() {
+        let mut clip = ClipStack::new([100, 100], [2.0, 2.0]);
+        clip.push_logical(Rect::new(10.0, 10.0, 20.0, 20.0));
+        clip.push_pixels(Rect::new(0.0, 30.0, 100.0, 100.0));
+        assert_eq!(clip.depth(), 2);
+        assert_eq!(
+            clip.current_logical(),
+            Some(Rect::new(10.0, 15.0, 20.0, 15.0))
+        );
+
+        // disjoint rects have zero size
+        clip.push_pixels(Rect::new(80.0, 80.0, 10.0, 10.0));
+        assert!(clip.is_empty());
+
+        clip.pop();
+        clip.pop();
+        assert_eq!(clip.scissor(), Rect::new(20, 20, 40, 40));
+        clip.pop();
+        assert_eq!(clip.pop(), None);
+        assert_eq!(clip.scissor(), Rect::new(0, 0, 100, 100));
+    }
+
+    #[test]
+    fn scissor_covers_partial_pixels() {
+        let mut clip = ClipStack::new([100, 50], [1.0, 1.0]);
+        clip.push_pixels(Rect::new(1.5, 2.25, 3.0, 4.5));
+        assert_eq!(clip.scissor(), Rect::new(1, 2, 4, 5));
+
+        // clamped to the frame buffer
+        clip.clear();
+        clip.push_pixels(Rect::new(-10.5, 40.0, 200.0, 20.0));
+        assert_eq!(clip.scissor(), Rect::new(0, 40, 100, 10));
+
+        clip.clear();
+        clip.push_pixels(Rect::new(120.0, 60.0, 10.0, 10.0));
+        assert_eq!(clip.scissor(), Rect::new(100, 50, 0, 0));
+        assert!(clip.is_empty());
+    }
+
+    #[test]
+    fn batches() {
+        let mut clip = ClipStack::new([100, 100], [1.0, 1.0]);
+        let mut batch = ClippedMesh::new();
+
+        batch.push(&clip, &quad(0.0));
+        batch.push(&clip, &quad(1.0));
+        clip.push_pixels(Rect::new(0.0, 0.0, 10.0, 10.0));
+        batch.push(&clip, &quad(2.0));
+        clip.pop();
+        batch.push(&clip, &quad(3.0));
+
+        let full = Rect::new(0, 0, 100, 100);
+        let batches = [
+            (full, 0, 12),
+            (Rect::new(0, 0, 10, 10), 12, 6),
+            (full, 18, 6),
+        ];
+        let batches = batches
+            .iter()
+            .map(|&(scissor, start, count)| ClipBatch {
+                scissor,
+                start,
+                count,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(batch.batches, batches);
+        assert_eq!(batches[1].indices(), 12..18);
+
+        // indices are rebased, missing attributes are filled
+        assert_eq!(batch.mesh.indices[12..18], [8, 9, 10, 10, 11, 8]);
+        assert_eq!(batch.mesh.n_verts(), 16);
+        assert_eq!(batch.mesh.uvs.len(), 16);
+        assert_eq!(batch.mesh.colors[15], Rgba::white());
+
+        batch.clear();
+        assert!(batch.batches.is_empty());
+        assert_eq!(batch.mesh.n_verts(), 0);
+    }
+}
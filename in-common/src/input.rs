@@ -0,0 +1,480 @@
+//! Keyboard, mouse, game controller and text input state fed from SDL events
+//!
+//! Call [`Input::event`] for each polled event and [`Input::end_frame`] after each update:
+//!
+//! ```ignore
+//! for ev in pump.poll_iter() {
+//!     input.event(&ev);
+//! }
+//! update(&input);
+//! input.end_frame();
+//! ```
+
+mod actions;
+mod pad;
+
+pub use self::{
+    actions::{ActionMap, Binding},
+    pad::{AxisDir, Controllers, DeadZone, PadState, Pads},
+};
+
+use std::{collections::HashSet, hash::Hash};
+
+use sdl2::{
+    event::Event,
+    keyboard::Scancode,
+    mouse::{MouseButton, MouseWheelDirection},
+};
+use vek::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Query {
+    Down,
+    Pressed,
+    Released,
+}
+
+/// Down/pressed/released state of a set of buttons
+#[derive(Debug, Clone)]
+pub struct ButtonSet<T> {
+    down: HashSet<T>,
+    /// Pressed since the last frame
+    pressed: HashSet<T>,
+    /// Released since the last frame
+    released: HashSet<T>,
+}
+
+impl<T> Default for ButtonSet<T> {
+    fn default() -> Self {
+        Self {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> ButtonSet<T> {
+    pub fn on_down(&mut self, x: T) {
+        if self.down.insert(x) {
+            self.pressed.insert(x);
+        }
+    }
+
+    pub fn on_up(&mut self, x: T) {
+        if self.down.remove(&x) {
+            self.released.insert(x);
+        }
+    }
+
+    /// Clears the pressed/released state
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    /// Releases every button (e.g. on focus lost)
+    pub fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+    }
+
+    pub fn is_down(&self, x: T) -> bool {
+        self.down.contains(&x)
+    }
+
+    /// Pressed since the last frame
+    pub fn is_pressed(&self, x: T) -> bool {
+        self.pressed.contains(&x)
+    }
+
+    /// Released since the last frame
+    pub fn is_released(&self, x: T) -> bool {
+        self.released.contains(&x)
+    }
+
+    pub fn iter_down(&self) -> impl Iterator<Item = &T> {
+        self.down.iter()
+    }
+
+    fn query(&self, x: T, query: Query) -> bool {
+        match query {
+            Query::Down => self.is_down(x),
+            Query::Pressed => self.is_pressed(x),
+            Query::Released => self.is_released(x),
+        }
+    }
+}
+
+/// Input state
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub keys: ButtonSet<Scancode>,
+    pub mouse: ButtonSet<MouseButton>,
+    pub pads: Pads,
+    /// Mouse position in frame buffer pixels
+    mouse_pos: Vec2<f32>,
+    /// Mouse movement since the last frame in frame buffer pixels
+    mouse_delta: Vec2<f32>,
+    /// Wheel movement since the last frame (positive: right/away from the user)
+    wheel: Vec2<f32>,
+    /// Text typed since the last frame (requires `VideoSubsystem::text_input().start()`)
+    text: String,
+    /// Window size to frame buffer size
+    dpi_scale: Vec2<f32>,
+    pub actions: ActionMap,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            keys: Default::default(),
+            mouse: Default::default(),
+            pads: Default::default(),
+            mouse_pos: Vec2::zero(),
+            mouse_delta: Vec2::zero(),
+            wheel: Vec2::zero(),
+            text: String::new(),
+            dpi_scale: Vec2::one(),
+            actions: Default::default(),
+        }
+    }
+}
+
+impl Input {
+    pub fn new(actions: ActionMap) -> Self {
+        Self {
+            actions,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the scaling factor from window coordinates to frame buffer pixels
+    ///
+    /// Update it on window resize (`WindowWrapper::dpi_scale` in `in-wgpu`).
+    pub fn set_dpi_scale(&mut self, scale: impl Into<Vec2<f32>>) {
+        self.dpi_scale = scale.into();
+    }
+
+    pub fn event(&mut self, ev: &Event) {
+        self.pads.event(ev);
+
+        match ev {
+            Event::KeyDown {
+                scancode: Some(sc),
+                repeat: false,
+                ..
+            } => {
+                self.keys.on_down(*sc);
+            }
+            Event::KeyUp {
+                scancode: Some(sc), ..
+            } => {
+                self.keys.on_up(*sc);
+            }
+            Event::MouseButtonDown {
+                mouse_btn, x, y, ..
+            } => {
+                self.mouse_pos = self.to_fb(*x, *y);
+                self.mouse.on_down(*mouse_btn);
+            }
+            Event::MouseButtonUp {
+                mouse_btn, x, y, ..
+            } => {
+                self.mouse_pos = self.to_fb(*x, *y);
+                self.mouse.on_up(*mouse_btn);
+            }
+            Event::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => {
+                self.mouse_pos = self.to_fb(*x, *y);
+                self.mouse_delta += self.to_fb(*xrel, *yrel);
+            }
+            Event::MouseWheel {
+                x, y, direction, ..
+            } => {
+                let sign = match direction {
+                    MouseWheelDirection::Flipped => -1.0,
+                    _ => 1.0,
+                };
+                self.wheel += Vec2::new(*x as f32, *y as f32) * sign;
+            }
+            Event::TextInput { text, .. } => {
+                self.text.push_str(text);
+            }
+            Event::Window {
+                win_event: sdl2::event::WindowEvent::FocusLost,
+                ..
+            } => {
+                // we won't receive the key up events
+                self.keys.release_all();
+                self.mouse.release_all();
+            }
+            _ => {}
+        }
+    }
+
+    /// Clears per-frame state. Call it after each update
+    pub fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.mouse.end_frame();
+        self.pads.end_frame();
+        self.mouse_delta = Vec2::zero();
+        self.wheel = Vec2::zero();
+        self.text.clear();
+    }
+
+    fn to_fb(&self, x: i32, y: i32) -> Vec2<f32> {
+        Vec2::new(x as f32, y as f32) * self.dpi_scale
+    }
+
+    pub fn mouse_pos(&self) -> Vec2<f32> {
+        self.mouse_pos
+    }
+
+    pub fn mouse_delta(&self) -> Vec2<f32> {
+        self.mouse_delta
+    }
+
+    pub fn wheel(&self) -> Vec2<f32> {
+        self.wheel
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn binding_state(&self, b: &Binding, query: Query) -> bool {
+        match b {
+            Binding::Key(sc) => self.keys.query(*sc, query),
+            Binding::Mouse(btn) => self.mouse.query(*btn, query),
+            Binding::Pad(btn) => self.pads.any_button(|b| b.query(*btn, query)),
+            Binding::Axis(dir) => self.pads.any_axis_button(|b| b.query(*dir, query)),
+        }
+    }
+
+    fn binding_value(&self, b: &Binding) -> f32 {
+        match b {
+            Binding::Axis(dir) => self.pads.axis_dir_value(*dir),
+            _ if self.binding_state(b, Query::Down) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// If any binding of the action is down
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|b| self.binding_state(b, Query::Down))
+    }
+
+    /// If any binding of the action is pressed since the last frame
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|b| self.binding_state(b, Query::Pressed))
+    }
+
+    /// If any binding of the action is released since the last frame
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|b| self.binding_state(b, Query::Released))
+    }
+
+    /// Analog value of the action in `0.0..=1.0` (`1.0` for digital bindings that are down)
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.actions
+            .bindings(action)
+            .iter()
+            .map(|b| self.binding_value(b))
+            .fold(0.0, f32::max)
+    }
+
+    /// Value in `-1.0..=1.0` from two actions
+    pub fn action_axis(&self, negative: &str, positive: &str) -> f32 {
+        self.action_value(positive) - self.action_value(negative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::{
+        controller::{Axis, Button},
+        event::WindowEvent,
+        keyboard::Mod,
+    };
+
+    use super::*;
+
+    fn key(down: bool, sc: Scancode, repeat: bool) -> Event {
+        let (timestamp, window_id, keycode, keymod) = (0, 1, None, Mod::NOMOD);
+        let scancode = Some(sc);
+        if down {
+            Event::KeyDown {
+                timestamp,
+                window_id,
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp,
+                window_id,
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+            }
+        }
+    }
+
+    fn pad_button(down: bool, which: u32, button: Button) -> Event {
+        if down {
+            Event::ControllerButtonDown {
+                timestamp: 0,
+                which,
+                button,
+            }
+        } else {
+            Event::ControllerButtonUp {
+                timestamp: 0,
+                which,
+                button,
+            }
+        }
+    }
+
+    fn pad_axis(which: u32, axis: Axis, value: i16) -> Event {
+        Event::ControllerAxisMotion {
+            timestamp: 0,
+            which,
+            axis,
+            value,
+        }
+    }
+
+    fn input() -> Input {
+        let mut actions = ActionMap::new();
+        actions.bind("jump", Binding::Key(Scancode::Space));
+        actions.bind("jump", Binding::Pad(Button::A));
+        actions.bind("left", Binding::Key(Scancode::A));
+        actions.bind("left", Binding::Axis(AxisDir::new(Axis::LeftX, false)));
+        actions.bind("right", Binding::Axis(AxisDir::new(Axis::LeftX, true)));
+        Input::new(actions)
+    }
+
+    /// (down, pressed, released)
+    fn action(input: &Input, name: &str) -> (bool, bool, bool) {
+        (
+            input.action_down(name),
+            input.action_pressed(name),
+            input.action_released(name),
+        )
+    }
+
+    #[test]
+    fn key_press_hold_release() {
+        let mut input = self::input();
+
+        input.event(&key(true, Scancode::Space, false));
+        assert!(input.keys.is_pressed(Scancode::Space));
+        assert_eq!(action(&input, "jump"), (true, true, false));
+        input.end_frame();
+
+        // held; repeats are ignored
+        input.event(&key(true, Scancode::Space, true));
+        assert_eq!(action(&input, "jump"), (true, false, false));
+        input.end_frame();
+
+        input.event(&key(false, Scancode::Space, false));
+        assert!(!input.keys.is_down(Scancode::Space));
+        assert_eq!(action(&input, "jump"), (false, false, true));
+        input.end_frame();
+
+        assert_eq!(action(&input, "jump"), (false, false, false));
+    }
+
+    #[test]
+    fn press_and_release_in_one_frame() {
+        let mut input = self::input();
+        input.event(&key(true, Scancode::A, false));
+        input.event(&key(false, Scancode::A, false));
+        assert_eq!(action(&input, "left"), (false, true, true));
+    }
+
+    #[test]
+    fn controller_buttons() {
+        let mut input = self::input();
+
+        // no device is needed: pads are registered on their first event
+        input.event(&pad_button(true, 3, Button::A));
+        assert!(input.pads.get(3).unwrap().buttons.is_pressed(Button::A));
+        assert_eq!(action(&input, "jump"), (true, true, false));
+        assert_eq!(input.action_value("jump"), 1.0);
+        input.end_frame();
+
+        assert_eq!(action(&input, "jump"), (true, false, false));
+
+        // another binding of the same action doesn't release it
+        input.event(&key(true, Scancode::Space, false));
+        input.event(&key(false, Scancode::Space, false));
+        assert!(input.action_down("jump"));
+        input.end_frame();
+
+        input.event(&pad_button(false, 3, Button::A));
+        assert_eq!(action(&input, "jump"), (false, false, true));
+    }
+
+    #[test]
+    fn controller_axes() {
+        let mut input = self::input();
+
+        // inside the dead zone
+        input.event(&pad_axis(0, Axis::LeftX, -3000));
+        assert_eq!(action(&input, "left"), (false, false, false));
+        assert_eq!(input.action_value("left"), 0.0);
+
+        input.event(&pad_axis(0, Axis::LeftX, i16::MIN));
+        assert_eq!(action(&input, "left"), (true, true, false));
+        assert_eq!(input.action_value("left"), 1.0);
+        assert_eq!(input.action_axis("left", "right"), -1.0);
+        input.end_frame();
+
+        input.event(&pad_axis(0, Axis::LeftX, i16::MAX));
+        assert_eq!(action(&input, "left"), (false, false, true));
+        assert_eq!(action(&input, "right"), (true, true, false));
+        assert_eq!(input.action_axis("left", "right"), 1.0);
+    }
+
+    #[test]
+    fn release_on_focus_lost_and_removal() {
+        let mut input = self::input();
+        input.event(&key(true, Scancode::A, false));
+        input.event(&pad_button(true, 0, Button::A));
+        input.end_frame();
+
+        input.event(&Event::Window {
+            timestamp: 0,
+            window_id: 1,
+            win_event: WindowEvent::FocusLost,
+        });
+        assert_eq!(action(&input, "left"), (false, false, true));
+        assert_eq!(action(&input, "jump"), (false, false, true));
+        input.end_frame();
+
+        input.event(&pad_button(true, 0, Button::A));
+        input.end_frame();
+        input.event(&Event::ControllerDeviceRemoved {
+            timestamp: 0,
+            which: 0,
+        });
+        assert!(!input.action_down("jump"));
+        input.end_frame();
+        assert!(input.pads.get(0).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Named actions mapped to input bindings
+//!
+//! Config file format:
+//!
+//! ```text
+//! # comment
+//! jump = key:Space, mouse:Left, pad:a
+//! left = key:A, key:Left, pad:dpleft, axis:leftx-
+//! ```
+//!
+//! Game controller buttons and axes use the names of SDL controller mappings (`a`, `dpup`,
+//! `leftshoulder`, `leftx`, `triggerright`, ..). Axes are suffixed with `+` or `-`.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::*;
+use sdl2::{
+    controller::{Axis, Button},
+    keyboard::Scancode,
+    mouse::MouseButton,
+};
+
+use crate::input::AxisDir;
+
+/// Physical input that triggers an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Scancode),
+    Mouse(MouseButton),
+    /// Button of any game controller
+    Pad(Button),
+    /// Axis direction of any game controller
+    Axis(AxisDir),
+}
+
+impl Binding {
+    /// Parses `key:<scancode name>`, `mouse:<Left|Middle|Right|X1|X2>`, `pad:<button>` or
+    /// `axis:<axis><+|->`
+    pub fn parse(s: &str) -> Result<Self> {
+        let (kind, name) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected `<kind>:<name>`: `{}`", s))?;
+        let name = name.trim();
+
+        match kind.trim() {
+            "key" => Scancode::from_name(name)
+                .map(Binding::Key)
+                .ok_or_else(|| anyhow!("unknown key: `{}`", name)),
+            "mouse" => {
+                let btn = match name {
+                    "Left" => MouseButton::Left,
+                    "Middle" => MouseButton::Middle,
+                    "Right" => MouseButton::Right,
+                    "X1" => MouseButton::X1,
+                    "X2" => MouseButton::X2,
+                    _ => bail!("unknown mouse button: `{}`", name),
+                };
+                Ok(Binding::Mouse(btn))
+            }
+            "pad" => Button::from_string(name)
+                .map(Binding::Pad)
+                .ok_or_else(|| anyhow!("unknown controller button: `{}`", name)),
+            "axis" => {
+                let (axis, positive) = if let Some(axis) = name.strip_suffix('+') {
+                    (axis, true)
+                } else if let Some(axis) = name.strip_suffix('-') {
+                    (axis, false)
+                } else {
+                    bail!("axis without `+` or `-`: `{}`", name);
+                };
+                Axis::from_string(axis)
+                    .map(|axis| Binding::Axis(AxisDir::new(axis, positive)))
+                    .ok_or_else(|| anyhow!("unknown controller axis: `{}`", axis))
+            }
+            kind => bail!("unknown binding kind: `{}`", kind),
+        }
+    }
+}
+
+/// Action name -> bindings
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    actions: HashMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a config file (see the module documentation for the format)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)
+            .with_context(|| format!("loading action map: {}", path.display()))?;
+        Self::parse(&src).with_context(|| format!("parsing action map: {}", path.display()))
+    }
+
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut map = Self::new();
+
+        for (i, line) in src.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _comment)) => line,
+                None => line,
+            }
+            .trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, bindings) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `<action> = <bindings>`", i + 1))?;
+
+            let name = name.trim();
+            ensure!(!name.is_empty(), "line {}: empty action name", i + 1);
+
+            for b in bindings.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+                let b = Binding::parse(b).with_context(|| format!("line {}", i + 1))?;
+                map.bind(name, b);
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Adds a binding to the action
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes every binding of the action
+    pub fn unbind(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    /// Bindings of the action (empty if the action is unknown)
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map(|b| b.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(|s| s.as_str())
+    }
+}
@@ -0,0 +1,358 @@
+//! Game controller state and devices
+//!
+//! [`Pads`] is updated from events only, so it works with synthetic events (no device is
+//! required). [`Controllers`] opens the devices on hot-plug and plays rumble effects.
+//!
+//! ```ignore
+//! let mut input = Input::new(ActionMap::parse("jump = key:Space, pad:a")?);
+//! input.event(&Event::ControllerButtonDown {
+//!     timestamp: 0,
+//!     which: 0,
+//!     button: Button::A,
+//! });
+//! assert!(input.action_pressed("jump"));
+//! ```
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::Duration,
+};
+
+use anyhow::*;
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    event::Event,
+    GameControllerSubsystem, Sdl,
+};
+use vek::Vec2;
+
+use crate::input::ButtonSet;
+
+/// Axis with direction, used as a digital button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AxisDir {
+    pub axis: Axis,
+    /// Positive: right (X), down (Y) or pulled (triggers)
+    pub positive: bool,
+}
+
+impl AxisDir {
+    pub fn new(axis: Axis, positive: bool) -> Self {
+        Self { axis, positive }
+    }
+}
+
+/// Dead zones in normalized units (`0.0..1.0`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeadZone {
+    /// Radial dead zone of the sticks
+    pub stick: f32,
+    pub trigger: f32,
+    /// Axis value to be considered as down for [`AxisDir`] bindings
+    pub press: f32,
+}
+
+impl Default for DeadZone {
+    fn default() -> Self {
+        Self {
+            stick: 0.2,
+            trigger: 0.1,
+            press: 0.5,
+        }
+    }
+}
+
+/// Maps `value` in `dead_zone..1.0` to `0.0..1.0`
+fn rescale(value: f32, dead_zone: f32) -> f32 {
+    if value <= dead_zone {
+        0.0
+    } else {
+        ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+fn normalize(value: i16) -> f32 {
+    // `i16::MIN` is clamped to `-1.0`
+    (value as f32 / i16::MAX as f32).max(-1.0)
+}
+
+/// Axes of one stick
+fn stick_axes(axis: Axis) -> Option<(Axis, Axis)> {
+    match axis {
+        Axis::LeftX | Axis::LeftY => Some((Axis::LeftX, Axis::LeftY)),
+        Axis::RightX | Axis::RightY => Some((Axis::RightX, Axis::RightY)),
+        Axis::TriggerLeft | Axis::TriggerRight => None,
+    }
+}
+
+const AXES: [Axis; 6] = [
+    Axis::LeftX,
+    Axis::LeftY,
+    Axis::RightX,
+    Axis::RightY,
+    Axis::TriggerLeft,
+    Axis::TriggerRight,
+];
+
+/// State of one game controller
+#[derive(Debug, Clone, Default)]
+pub struct PadState {
+    pub buttons: ButtonSet<Button>,
+    /// Axes as digital buttons (see [`DeadZone::press`])
+    pub axis_buttons: ButtonSet<AxisDir>,
+    /// Normalized raw axis values (`-1.0..=1.0`)
+    raw: HashMap<Axis, f32>,
+    /// Removed pads are dropped on the next [`Pads::end_frame`]
+    removed: bool,
+}
+
+impl PadState {
+    pub fn is_connected(&self) -> bool {
+        !self.removed
+    }
+
+    /// Axis value without dead zone
+    pub fn raw_axis(&self, axis: Axis) -> f32 {
+        self.raw.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// Axis value with dead zone applied
+    pub fn axis(&self, axis: Axis, dz: &DeadZone) -> f32 {
+        match stick_axes(axis) {
+            Some((x, _y)) => {
+                let v = self.stick(x, dz);
+                if axis == x {
+                    v.x
+                } else {
+                    v.y
+                }
+            }
+            None => rescale(self.raw_axis(axis), dz.trigger),
+        }
+    }
+
+    /// Stick position with radial dead zone applied. `axis` is any axis of the stick
+    pub fn stick(&self, axis: Axis, dz: &DeadZone) -> Vec2<f32> {
+        let (x, y) = match stick_axes(axis) {
+            Some(xy) => xy,
+            None => return Vec2::zero(),
+        };
+
+        let v = Vec2::new(self.raw_axis(x), self.raw_axis(y));
+        let len = v.magnitude();
+        if len <= dz.stick {
+            Vec2::zero()
+        } else {
+            v / len * rescale(len, dz.stick)
+        }
+    }
+
+    fn update_axis_buttons(&mut self, dz: &DeadZone) {
+        for &axis in AXES.iter() {
+            let v = self.axis(axis, dz);
+            for &positive in [true, false].iter() {
+                let dir = AxisDir::new(axis, positive);
+                let v = if positive { v } else { -v };
+                if v >= dz.press {
+                    self.axis_buttons.on_down(dir);
+                } else {
+                    self.axis_buttons.on_up(dir);
+                }
+            }
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.buttons.release_all();
+        self.axis_buttons.release_all();
+        self.raw.clear();
+    }
+}
+
+/// Game controller states keyed by joystick instance ID
+#[derive(Debug, Clone, Default)]
+pub struct Pads {
+    pads: HashMap<u32, PadState>,
+    pub dead_zone: DeadZone,
+}
+
+impl Pads {
+    pub fn event(&mut self, ev: &Event) {
+        match ev {
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.pad_mut(*which).buttons.on_down(*button);
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.pad_mut(*which).buttons.on_up(*button);
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let dz = self.dead_zone;
+                let pad = self.pad_mut(*which);
+                pad.raw.insert(*axis, normalize(*value));
+                pad.update_axis_buttons(&dz);
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(pad) = self.pads.get_mut(which) {
+                    pad.release_all();
+                    pad.removed = true;
+                }
+            }
+            Event::Window {
+                win_event: sdl2::event::WindowEvent::FocusLost,
+                ..
+            } => {
+                for pad in self.pads.values_mut() {
+                    pad.buttons.release_all();
+                    pad.axis_buttons.release_all();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pads are registered on their first button or axis event
+    fn pad_mut(&mut self, id: u32) -> &mut PadState {
+        let pad = self.pads.entry(id).or_default();
+        pad.removed = false;
+        pad
+    }
+
+    /// Clears per-frame state and drops removed pads
+    pub fn end_frame(&mut self) {
+        self.pads.retain(|_, pad| !pad.removed);
+        for pad in self.pads.values_mut() {
+            pad.buttons.end_frame();
+            pad.axis_buttons.end_frame();
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&PadState> {
+        self.pads.get(&id)
+    }
+
+    /// Joystick instance IDs and states
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &PadState)> {
+        self.pads.iter().map(|(id, pad)| (*id, pad))
+    }
+
+    pub fn any_button(&self, f: impl Fn(&ButtonSet<Button>) -> bool) -> bool {
+        self.pads.values().any(|pad| f(&pad.buttons))
+    }
+
+    pub fn any_axis_button(&self, f: impl Fn(&ButtonSet<AxisDir>) -> bool) -> bool {
+        self.pads.values().any(|pad| f(&pad.axis_buttons))
+    }
+
+    /// Largest value of the axis direction among the pads (`0.0..=1.0`)
+    pub fn axis_dir_value(&self, dir: AxisDir) -> f32 {
+        self.pads
+            .values()
+            .map(|pad| {
+                let v = pad.axis(dir.axis, &self.dead_zone);
+                if dir.positive {
+                    v.max(0.0)
+                } else {
+                    (-v).max(0.0)
+                }
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Opened game controller devices
+pub struct Controllers {
+    sys: GameControllerSubsystem,
+    /// Joystick instance ID -> device
+    devices: HashMap<u32, GameController>,
+}
+
+impl std::fmt::Debug for Controllers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Controllers")
+            .field("devices", &self.devices.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Controllers {
+    /// Opens the subsystem. Connected devices are opened via the initial
+    /// `ControllerDeviceAdded` events
+    pub fn new(sdl: &Sdl) -> Result<Self> {
+        let sys = sdl.game_controller().map_err(Error::msg)?;
+        Ok(Self {
+            sys,
+            devices: HashMap::new(),
+        })
+    }
+
+    pub fn subsystem(&self) -> &GameControllerSubsystem {
+        &self.sys
+    }
+
+    /// Handles hot-plug events
+    pub fn event(&mut self, ev: &Event) {
+        match ev {
+            Event::ControllerDeviceAdded { which, .. } => {
+                // `which` is the joystick index here
+                if let Err(err) = self.open(*which) {
+                    log::warn!("failed to open game controller {}: {}", which, err);
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(pad) = self.devices.remove(which) {
+                    log::info!("game controller removed: {}", pad.name());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open(&mut self, joystick_index: u32) -> Result<()> {
+        let pad = self.sys.open(joystick_index)?;
+        let id = pad.instance_id();
+        if let Entry::Vacant(e) = self.devices.entry(id) {
+            log::info!("game controller added: {} (id: {})", pad.name(), id);
+            e.insert(pad);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: u32) -> Option<&GameController> {
+        self.devices.get(&id)
+    }
+
+    /// Joystick instance IDs of the opened devices
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.devices.keys().copied()
+    }
+
+    /// Plays a rumble effect. Intensities are in `0.0..=1.0`
+    pub fn rumble(&mut self, id: u32, low: f32, high: f32, duration: Duration) -> Result<()> {
+        let pad = self
+            .devices
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("no game controller with id {}", id))?;
+        let (low, high) = (to_u16(low), to_u16(high));
+        pad.set_rumble(low, high, duration.as_millis() as u32)?;
+        Ok(())
+    }
+
+    /// Plays a rumble effect on every device, ignoring devices without rumble support
+    pub fn rumble_all(&mut self, low: f32, high: f32, duration: Duration) {
+        let (low, high) = (to_u16(low), to_u16(high));
+        for pad in self.devices.values_mut() {
+            let _ = pad.set_rumble(low, high, duration.as_millis() as u32);
+        }
+    }
+
+    /// Stops rumble effects
+    pub fn stop_rumble(&mut self) {
+        self.rumble_all(0.0, 0.0, Duration::ZERO);
+    }
+}
+
+fn to_u16(x: f32) -> u16 {
+    (x.clamp(0.0, 1.0) * u16::MAX as f32) as u16
+}
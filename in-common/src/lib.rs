@@ -0,0 +1,16 @@
+//! Backend-agnostic (CPU-side) utilities shared by `in-rokol` and `in-wgpu`
+
+pub mod anim;
+pub mod assets;
+pub mod blend;
+pub mod camera;
+pub mod clip;
+pub mod input;
+pub mod mesh;
+pub mod nine_slice;
+pub mod preprocess;
+pub mod reflect;
+pub mod shape;
+pub mod texture;
+pub mod tilemap;
+pub mod uniform;
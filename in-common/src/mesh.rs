@@ -0,0 +1,443 @@
+//! Mesh data on CPU and model import (Wavefront OBJ, glTF 2.0)
+//!
+//! UVs are stored with the top-left origin (glTF convention). OBJ texture coordinates are flipped
+//! on import.
+
+mod gltf;
+mod obj;
+
+use std::path::Path;
+
+use anyhow::*;
+use vek::{Rgba, Vec2, Vec3};
+
+/// Vertex with every attribute an imported mesh can have
+///
+/// Implement `From<MeshVertex>` for backend vertex types to build GPU meshes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub pos: Vec3<f32>,
+    /// Zero if the mesh has no normals
+    pub normal: Vec3<f32>,
+    /// Zero if the mesh has no texture coordinates
+    pub uv: Vec2<f32>,
+    /// White if the mesh has no vertex colors
+    pub color: Rgba<f32>,
+}
+
+/// Vertex attributes in struct-of-arrays layout + triangle list indices
+///
+/// Optional attributes (`normals`, `uvs` and `colors`) are either empty or as long as
+/// `positions`.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub name: String,
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+    pub uvs: Vec<Vec2<f32>>,
+    pub colors: Vec<Rgba<f32>>,
+    pub indices: Vec<u32>,
+    /// Index of [`ModelData::materials`]
+    pub material: Option<usize>,
+}
+
+impl MeshData {
+    pub fn n_verts(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn vertex(&self, i: usize) -> MeshVertex {
+        MeshVertex {
+            pos: self.positions[i],
+            normal: self.normals.get(i).copied().unwrap_or_default(),
+            uv: self.uvs.get(i).copied().unwrap_or_default(),
+            color: self.colors.get(i).copied().unwrap_or_else(Rgba::white),
+        }
+    }
+
+    /// Converts the attributes into vertex type of a backend
+    pub fn vertices<V: From<MeshVertex>>(&self) -> Vec<V> {
+        (0..self.n_verts())
+            .map(|i| V::from(self.vertex(i)))
+            .collect()
+    }
+
+    /// `u16` indices if every vertex can be addressed by them
+    pub fn indices_u16(&self) -> Option<Vec<u16>> {
+        if self.n_verts() > u16::MAX as usize + 1 {
+            return None;
+        }
+        Some(self.indices.iter().map(|&i| i as u16).collect())
+    }
+
+    /// Checks attribute lengths and index range
+    pub fn validate(&self) -> Result<()> {
+        let n = self.n_verts();
+        ensure!(
+            self.normals.is_empty() || self.normals.len() == n,
+            "mesh `{}`: {} normals for {} positions",
+            self.name,
+            self.normals.len(),
+            n
+        );
+        ensure!(
+            self.uvs.is_empty() || self.uvs.len() == n,
+            "mesh `{}`: {} UVs for {} positions",
+            self.name,
+            self.uvs.len(),
+            n
+        );
+        ensure!(
+            self.colors.is_empty() || self.colors.len() == n,
+            "mesh `{}`: {} colors for {} positions",
+            self.name,
+            self.colors.len(),
+            n
+        );
+        ensure!(
+            self.indices.chunks_exact(3).remainder().is_empty(),
+            "mesh `{}`: index count {} is not a triangle list",
+            self.name,
+            self.indices.len()
+        );
+        if let Some(i) = self.indices.iter().find(|&&i| i as usize >= n) {
+            bail!("mesh `{}`: index {} out of {} vertices", self.name, i, n);
+        }
+        Ok(())
+    }
+}
+
+/// Named index range of a mesh sharing the vertex/index buffers with other sub-meshes
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SubMesh {
+    pub name: String,
+    /// First index in the index buffer
+    pub start: u32,
+    /// Number of indices
+    pub count: u32,
+    /// Added to each index before fetching a vertex. Non-negative (`in-rokol` emulates it with
+    /// the vertex buffer offset)
+    pub base_vertex: i32,
+    /// Material slot
+    pub material: Option<usize>,
+}
+
+impl SubMesh {
+    pub fn new(name: impl Into<String>, start: u32, count: u32) -> Self {
+        Self {
+            name: name.into(),
+            start,
+            count,
+            ..Default::default()
+        }
+    }
+
+    pub fn base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+
+    pub fn material(mut self, material: usize) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    /// Range in the index buffer. Panics on overflow (see [`check`](Self::check))
+    pub fn indices(&self) -> std::ops::Range<u32> {
+        self.start..self.end().expect("sub-mesh index range overflows")
+    }
+
+    /// End of the index range. `None` on overflow
+    pub fn end(&self) -> Option<u32> {
+        self.start.checked_add(self.count)
+    }
+
+    /// Fails if the index range overflows or is out of an index buffer of `n_indices`, or if
+    /// the base vertex is negative
+    pub fn check(&self, n_indices: u32) -> Result<()> {
+        ensure!(
+            self.base_vertex >= 0,
+            "sub-mesh `{}`: negative base vertex {}",
+            self.name,
+            self.base_vertex
+        );
+        let end = self.end().with_context(|| {
+            format!(
+                "sub-mesh `{}`: index range {} + {} overflows",
+                self.name, self.start, self.count
+            )
+        })?;
+        ensure!(
+            end <= n_indices,
+            "sub-mesh `{}` is out of index buffer: {}..{} of {} indices",
+            self.name,
+            self.start,
+            end,
+            n_indices
+        );
+        Ok(())
+    }
+}
+
+/// Surface parameters of a mesh
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    /// Multiplied with the texture color
+    pub base_color: Rgba<f32>,
+    /// Index of [`ModelData::images`]
+    pub texture: Option<usize>,
+}
+
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: Rgba::white(),
+            texture: None,
+        }
+    }
+}
+
+/// Meshes split by material (OBJ) or primitive (glTF), with the referenced textures
+#[derive(Debug, Clone, Default)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    /// Decoded textures in RGBA8
+    pub images: Vec<image::RgbaImage>,
+}
+
+impl ModelData {
+    /// Loads `.obj`, `.gltf` or `.glb` file depending on the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase());
+
+        let model = match ext.as_deref() {
+            Some("obj") => self::load_obj(path)?,
+            Some("gltf") | Some("glb") => self::load_gltf(path)?,
+            _ => bail!("unsupported model format: {}", path.display()),
+        };
+
+        for mesh in &model.meshes {
+            mesh.validate()
+                .with_context(|| format!("invalid model: {}", path.display()))?;
+        }
+
+        Ok(model)
+    }
+
+    /// Concatenates every mesh into one vertex/index buffer pair
+    ///
+    /// Indices stay local to each mesh and are offset by [`SubMesh::base_vertex`] on draw, so
+    /// they can be narrowed to `u16` as long as each mesh is small enough.
+    pub fn merge_meshes(&self) -> (MeshData, Vec<SubMesh>) {
+        let mut merged = MeshData {
+            name: "merged".to_string(),
+            ..Default::default()
+        };
+        let mut submeshes = Vec::with_capacity(self.meshes.len());
+
+        let has_normals = self.meshes.iter().any(|m| !m.normals.is_empty());
+        let has_uvs = self.meshes.iter().any(|m| !m.uvs.is_empty());
+        let has_colors = self.meshes.iter().any(|m| !m.colors.is_empty());
+
+        for mesh in &self.meshes {
+            submeshes.push(SubMesh {
+                name: mesh.name.clone(),
+                start: merged.indices.len() as u32,
+                count: mesh.indices.len() as u32,
+                base_vertex: merged.positions.len() as i32,
+                material: mesh.material,
+            });
+
+            // fill missing attributes so that every attribute array stays aligned
+            let n = mesh.n_verts();
+            merged.positions.extend_from_slice(&mesh.positions);
+            if has_normals {
+                merged
+                    .normals
+                    .extend((0..n).map(|i| mesh.normals.get(i).copied().unwrap_or_default()));
+            }
+            if has_uvs {
+                merged
+                    .uvs
+                    .extend((0..n).map(|i| mesh.uvs.get(i).copied().unwrap_or_default()));
+            }
+            if has_colors {
+                merged.colors.extend(
+                    (0..n).map(|i| mesh.colors.get(i).copied().unwrap_or_else(Rgba::white)),
+                );
+            }
+            merged.indices.extend_from_slice(&mesh.indices);
+        }
+
+        (merged, submeshes)
+    }
+
+    pub fn material(&self, mesh: &MeshData) -> Option<&MaterialData> {
+        mesh.material.and_then(|i| self.materials.get(i))
+    }
+
+    pub fn image(&self, mesh: &MeshData) -> Option<&image::RgbaImage> {
+        self.material(mesh)
+            .and_then(|m| m.texture)
+            .and_then(|i| self.images.get(i))
+    }
+}
+
+/// Loads Wavefront OBJ file (and the MTL files and textures it refers to)
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ModelData> {
+    obj::load(path.as_ref()).with_context(|| format!("loading OBJ: {}", path.as_ref().display()))
+}
+
+/// Loads glTF 2.0 file (`.gltf` or `.glb`) with the buffers and images it refers to
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<ModelData> {
+    gltf::load(path.as_ref()).with_context(|| format!("loading glTF: {}", path.as_ref().display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn submesh_range() {
+        let sub = SubMesh::new("a", 6, 3);
+        assert_eq!(sub.indices(), 6..9);
+        assert!(sub.check(9).is_ok());
+        assert!(sub.check(8).is_err());
+
+        let huge = SubMesh::new("b", u32::MAX - 1, 2);
+        assert_eq!(huge.end(), None);
+        let err = huge.check(u32::MAX).unwrap_err();
+        assert!(err.to_string().contains("overflows"), "{}", err);
+    }
+
+    #[test]
+    fn negative_base_vertex() {
+        assert!(SubMesh::new("a", 0, 3).base_vertex(4).check(3).is_ok());
+        let err = SubMesh::new("a", 0, 3)
+            .base_vertex(-1)
+            .check(3)
+            .unwrap_err();
+        assert!(err.to_string().contains("negative base vertex"), "{}", err);
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/mesh")
+            .join(name)
+    }
+
+    #[test]
+    fn load_obj() {
+        let model = ModelData::load(fixture("quad.obj")).unwrap();
+
+        // split by material
+        let names = model
+            .meshes
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["tri", "quad"]);
+        let (tri, quad) = (&model.meshes[0], &model.meshes[1]);
+
+        assert_eq!(tri.n_verts(), 3);
+        assert_eq!(tri.indices, [0, 1, 2]);
+        assert_eq!(tri.positions[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(tri.normals, [Vec3::unit_z(); 3]);
+        // flipped to the top-left origin
+        assert_eq!(tri.uvs[0], Vec2::new(0.0, 1.0));
+        assert_eq!(tri.uvs[2], Vec2::new(1.0, 0.0));
+        assert_eq!(quad.n_verts(), 4);
+        assert_eq!(quad.indices.len(), 6);
+
+        let red = model.material(tri).unwrap();
+        assert_eq!(red.name, "red");
+        assert_eq!(red.base_color, Rgba::new(1.0, 0.0, 0.0, 0.5));
+        assert!(model.image(tri).is_none());
+
+        let checker = model.image(quad).unwrap();
+        assert_eq!(checker.dimensions(), (2, 2));
+        assert_eq!(checker.get_pixel(1, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn load_gltf() {
+        let model = ModelData::load(fixture("tris.gltf")).unwrap();
+
+        // one mesh per primitive
+        let names = model
+            .meshes
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["tris#0", "tris#1"]);
+        let (a, b) = (&model.meshes[0], &model.meshes[1]);
+
+        // node transforms are baked
+        assert_eq!(
+            a.positions,
+            [
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(3.0, 0.0, 0.0),
+                Vec3::new(1.0, 2.0, 0.0)
+            ]
+        );
+        assert_eq!(a.normals, [Vec3::unit_z(); 3]);
+        assert_eq!(a.uvs[1], Vec2::new(1.0, 0.0));
+        assert_eq!(a.indices, [0, 2, 1]);
+        assert_eq!(
+            model.material(a).unwrap().base_color,
+            Rgba::new(1.0, 0.0, 0.0, 0.5)
+        );
+
+        // non-indexed, without normals and UVs
+        assert_eq!(b.positions[1], Vec3::new(3.0, 0.0, 2.0));
+        assert_eq!(b.indices, [0, 1, 2]);
+        assert!(b.normals.is_empty() && b.uvs.is_empty());
+        assert_eq!(model.image(b).unwrap().get_pixel(1, 1).0, [255; 4]);
+    }
+
+    #[test]
+    fn unsupported_model() {
+        let err = ModelData::load(fixture("quad.mtl")).unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported model format"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let model = ModelData::load(fixture("quad.obj")).unwrap();
+        let mut tris = ModelData::load(fixture("tris.gltf")).unwrap();
+        tris.meshes.extend(model.meshes);
+
+        let (merged, subs) = tris.merge_meshes();
+        let ranges = subs
+            .iter()
+            .map(|s| (s.start, s.count, s.base_vertex))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [(0, 3, 0), (3, 3, 3), (6, 3, 6), (9, 6, 9)]);
+        assert_eq!(subs[3].name, "quad");
+        assert_eq!(subs[3].material, Some(1));
+
+        // indices stay local, missing attributes are filled
+        assert_eq!(merged.indices[9..], [0, 1, 2, 0, 2, 3]);
+        assert_eq!(merged.n_verts(), 13);
+        assert_eq!((merged.normals.len(), merged.uvs.len()), (13, 13));
+        assert_eq!(merged.normals[3], Vec3::zero());
+        assert!(merged.colors.is_empty());
+        for sub in &subs {
+            sub.check(merged.indices.len() as u32).unwrap();
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! glTF 2.0 import via `gltf`
+//!
+//! Node transforms are baked into the vertices, so every primitive ends up in model space.
+
+use std::path::Path;
+
+use anyhow::*;
+use vek::{Mat4, Rgba, Vec2, Vec3, Vec4};
+
+use crate::mesh::{MaterialData, MeshData, ModelData};
+
+pub fn load(path: &Path) -> Result<ModelData> {
+    let (doc, buffers, images) = ::gltf::import(path)?;
+
+    let materials = doc
+        .materials()
+        .map(|m| {
+            let pbr = m.pbr_metallic_roughness();
+            MaterialData {
+                name: m.name().unwrap_or_default().to_string(),
+                base_color: Rgba::from(pbr.base_color_factor()),
+                texture: pbr
+                    .base_color_texture()
+                    .map(|info| info.texture().source().index()),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let images = images
+        .into_iter()
+        .map(self::to_rgba8)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut meshes = Vec::new();
+    let scene = doc
+        .default_scene()
+        .or_else(|| doc.scenes().next())
+        .ok_or_else(|| anyhow!("no scene"))?;
+
+    for node in scene.nodes() {
+        self::visit_node(&node, Mat4::identity(), &buffers, &mut meshes)?;
+    }
+
+    Ok(ModelData {
+        meshes,
+        materials,
+        images,
+    })
+}
+
+fn visit_node(
+    node: &::gltf::Node,
+    parent: Mat4<f32>,
+    buffers: &[::gltf::buffer::Data],
+    meshes: &mut Vec<MeshData>,
+) -> Result<()> {
+    let local = Mat4::from_col_arrays(node.transform().matrix());
+    let transform = parent * local;
+
+    if let Some(mesh) = node.mesh() {
+        for prim in mesh.primitives() {
+            if prim.mode() != ::gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "skipping non-triangle primitive {:?} in mesh {:?}",
+                    prim.mode(),
+                    mesh.name()
+                );
+                continue;
+            }
+
+            let name = match mesh.name() {
+                Some(name) => format!("{}#{}", name, prim.index()),
+                None => format!("mesh{}#{}", mesh.index(), prim.index()),
+            };
+            meshes.push(self::load_primitive(&prim, name, transform, buffers)?);
+        }
+    }
+
+    for child in node.children() {
+        self::visit_node(&child, transform, buffers, meshes)?;
+    }
+
+    Ok(())
+}
+
+fn load_primitive(
+    prim: &::gltf::Primitive,
+    name: String,
+    transform: Mat4<f32>,
+    buffers: &[::gltf::buffer::Data],
+) -> Result<MeshData> {
+    let reader = prim.reader(|buf| Some(&buffers[buf.index()]));
+
+    // normals are transformed with the inverse transpose
+    let normal_mat = transform.inverted().transposed();
+
+    let positions = reader
+        .read_positions()
+        .ok_or_else(|| anyhow!("primitive `{}` has no positions", name))?
+        .map(|p| Vec3::from(transform * Vec4::from_point(Vec3::from(p))))
+        .collect::<Vec<_>>();
+
+    let normals = reader
+        .read_normals()
+        .map(|xs| {
+            xs.map(|n| Vec3::from(normal_mat * Vec4::from_direction(Vec3::from(n))).normalized())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let uvs = reader
+        .read_tex_coords(0)
+        .map(|xs| xs.into_f32().map(Vec2::from).collect())
+        .unwrap_or_default();
+
+    let colors = reader
+        .read_colors(0)
+        .map(|xs| xs.into_rgba_f32().map(Rgba::from).collect())
+        .unwrap_or_default();
+
+    let indices = match reader.read_indices() {
+        Some(xs) => xs.into_u32().collect(),
+        // non-indexed
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    Ok(MeshData {
+        name,
+        positions,
+        normals,
+        uvs,
+        colors,
+        indices,
+        material: prim.material().index(),
+    })
+}
+
+fn to_rgba8(img: ::gltf::image::Data) -> Result<image::RgbaImage> {
+    use ::gltf::image::Format;
+
+    let (w, h) = (img.width, img.height);
+    let src = img.pixels;
+
+    let pixels: Vec<u8> = match img.format {
+        Format::R8G8B8A8 => src,
+        Format::R8G8B8 => src
+            .chunks_exact(3)
+            .flat_map(|x| [x[0], x[1], x[2], 255])
+            .collect(),
+        Format::R8G8 => src
+            .chunks_exact(2)
+            .flat_map(|x| [x[0], x[1], 0, 255])
+            .collect(),
+        Format::R8 => src.iter().flat_map(|&x| [x, x, x, 255]).collect(),
+        format => bail!("unsupported glTF image format: {:?}", format),
+    };
+
+    image::RgbaImage::from_raw(w, h, pixels)
+        .ok_or_else(|| anyhow!("glTF image size mismatch ({}x{})", w, h))
+}
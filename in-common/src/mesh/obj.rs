@@ -0,0 +1,100 @@
+//! Wavefront OBJ import via `tobj`
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::*;
+use vek::{Rgba, Vec2, Vec3};
+
+use crate::mesh::{MaterialData, MeshData, ModelData};
+
+pub fn load(path: &Path) -> Result<ModelData> {
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            // one index buffer for every attribute
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        },
+    )?;
+
+    // missing MTL files are not fatal
+    let materials = materials.unwrap_or_else(|err| {
+        log::warn!("failed to load MTL for {}: {}", path.display(), err);
+        Vec::new()
+    });
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut images = Vec::new();
+    // texture path -> index of `images`
+    let mut image_map = HashMap::new();
+
+    let materials = materials
+        .iter()
+        .map(|m| {
+            let texture = if m.diffuse_texture.is_empty() {
+                None
+            } else {
+                let tex_path = dir.join(&m.diffuse_texture);
+                match image_map.get(&tex_path) {
+                    Some(&i) => Some(i),
+                    None => {
+                        let img = image::open(&tex_path)
+                            .with_context(|| format!("loading texture: {}", tex_path.display()))?
+                            .into_rgba8();
+                        images.push(img);
+                        image_map.insert(tex_path, images.len() - 1);
+                        Some(images.len() - 1)
+                    }
+                }
+            };
+
+            Ok(MaterialData {
+                name: m.name.clone(),
+                base_color: Rgba::new(m.diffuse[0], m.diffuse[1], m.diffuse[2], m.dissolve),
+                texture,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // `tobj` splits models on `o`, `g` and `usemtl`, so each model has one material
+    let meshes = models
+        .into_iter()
+        .map(|model| {
+            let mesh = model.mesh;
+            MeshData {
+                name: model.name,
+                positions: mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|x| Vec3::new(x[0], x[1], x[2]))
+                    .collect(),
+                normals: mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(|x| Vec3::new(x[0], x[1], x[2]))
+                    .collect(),
+                // OBJ has bottom-left origin
+                uvs: mesh
+                    .texcoords
+                    .chunks_exact(2)
+                    .map(|x| Vec2::new(x[0], 1.0 - x[1]))
+                    .collect(),
+                colors: mesh
+                    .vertex_color
+                    .chunks_exact(3)
+                    .map(|x| Rgba::new(x[0], x[1], x[2], 1.0))
+                    .collect(),
+                indices: mesh.indices,
+                material: mesh.material_id.filter(|&i| i < materials.len()),
+            }
+        })
+        .collect();
+
+    Ok(ModelData {
+        meshes,
+        materials,
+        images,
+    })
+}
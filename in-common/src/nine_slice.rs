@@ -0,0 +1,368 @@
+//! Nine-slice (nine-patch) panels
+//!
+//! A texture region is split into 3x3 cells by border insets. Corners keep their size, edges
+//! and the center are stretched or tiled to fill the target rectangle. The mesh is built in
+//! Y-down (screen) coordinates with counter-clockwise quads on screen:
+//!
+//! ```ignore
+//! let panel = NineSlice::new(&texture, Insets::uniform(8)).center(SliceMode::Tile);
+//! let mesh = panel.build(Rect::new(16.0, 16.0, 320.0, 96.0));
+//! // in-wgpu
+//! let verts = mesh.vertices::<TriVertex>();
+//! // in-rokol (textures are uploaded upside down)
+//! let verts = panel.flip_v(true).build(rect).vertices::<TexturedVertex>();
+//! ```
+
+use vek::{Rect, Rgba, Vec2, Vec3};
+
+use crate::{anim::TextureSize, mesh::MeshData};
+
+/// Border widths in source pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Insets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Insets {
+    pub fn new(left: u32, top: u32, right: u32, bottom: u32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn uniform(x: u32) -> Self {
+        Self::new(x, x, x, x)
+    }
+}
+
+/// How edges or the center fill their cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceMode {
+    Stretch,
+    /// Repeats the source at its (scaled) size. The last tile is cut off
+    Tile,
+}
+
+/// Texture region with border insets
+#[derive(Debug, Clone, PartialEq)]
+pub struct NineSlice {
+    /// Texture size in pixels
+    pub texture_size: [u32; 2],
+    /// Source region in pixels
+    pub region: Rect<u32, u32>,
+    pub insets: Insets,
+    pub edges: SliceMode,
+    pub center: SliceMode,
+    /// Target pixels per source pixel (borders and tiles)
+    pub scale: f32,
+    pub color: Rgba<f32>,
+    /// Flips UV `v` for bottom-left texture origin
+    pub flip_v: bool,
+}
+
+/// Destination and source interval of a quad along one axis
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    dst: [f32; 2],
+    src: [f32; 2],
+}
+
+impl NineSlice {
+    /// The whole texture
+    pub fn new(texture: &impl TextureSize, insets: Insets) -> Self {
+        let size = texture.texture_size();
+        Self::region(texture, Rect::new(0, 0, size[0], size[1]), insets)
+    }
+
+    /// An atlas region, e.g. [`SpriteSheet::frames`](crate::anim::SpriteSheet::frames)
+    pub fn region(texture: &impl TextureSize, region: Rect<u32, u32>, insets: Insets) -> Self {
+        assert!(
+            insets.left + insets.right <= region.w && insets.top + insets.bottom <= region.h,
+            "insets {:?} don't fit in region {:?}",
+            insets,
+            region
+        );
+
+        Self {
+            texture_size: texture.texture_size(),
+            region,
+            insets,
+            edges: SliceMode::Stretch,
+            center: SliceMode::Stretch,
+            scale: 1.0,
+            color: Rgba::white(),
+            flip_v: false,
+        }
+    }
+
+    pub fn edges(mut self, mode: SliceMode) -> Self {
+        self.edges = mode;
+        self
+    }
+
+    pub fn center(mut self, mode: SliceMode) -> Self {
+        self.center = mode;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<Rgba<f32>>) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn flip_v(mut self, flip_v: bool) -> Self {
+        self.flip_v = flip_v;
+        self
+    }
+
+    pub fn build(&self, target: Rect<f32, f32>) -> MeshData {
+        let mut mesh = MeshData {
+            name: "nine-slice".to_string(),
+            ..Default::default()
+        };
+        self.push(&mut mesh, target);
+        mesh
+    }
+
+    /// Appends the quads to a mesh, e.g. for batching panels
+    ///
+    /// Borders are shrunk proportionally if they don't fit in the target.
+    pub fn push(&self, mesh: &mut MeshData, target: Rect<f32, f32>) {
+        let r = self.region;
+        let ins = self.insets;
+
+        let cols = self::axis(
+            [target.x, target.x + target.w],
+            [r.x, r.x + ins.left, r.x + r.w - ins.right, r.x + r.w],
+            self.scale,
+        );
+        let rows = self::axis(
+            [target.y, target.y + target.h],
+            [r.y, r.y + ins.top, r.y + r.h - ins.bottom, r.y + r.h],
+            self.scale,
+        );
+
+        let size = Vec2::new(
+            self.texture_size[0].max(1) as f32,
+            self.texture_size[1].max(1) as f32,
+        );
+
+        for (iy, row) in rows.iter().enumerate() {
+            for (ix, col) in cols.iter().enumerate() {
+                let (mode_x, mode_y) = match (ix, iy) {
+                    (1, 1) => (self.center, self.center),
+                    (1, _) => (self.edges, SliceMode::Stretch),
+                    (_, 1) => (SliceMode::Stretch, self.edges),
+                    _ => (SliceMode::Stretch, SliceMode::Stretch),
+                };
+
+                for y in self::spans(*row, mode_y, self.scale) {
+                    for x in self::spans(*col, mode_x, self.scale) {
+                        self.push_quad(mesh, x, y, size);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push_quad(&self, mesh: &mut MeshData, x: Span, y: Span, size: Vec2<f32>) {
+        let (u0, u1) = (x.src[0] / size.x, x.src[1] / size.x);
+        let (mut v0, mut v1) = (y.src[0] / size.y, y.src[1] / size.y);
+        if self.flip_v {
+            v0 = 1.0 - v0;
+            v1 = 1.0 - v1;
+        }
+
+        let base = mesh.positions.len() as u32;
+        // top-left, top-right, bottom-right, bottom-left
+        let corners = [
+            (x.dst[0], y.dst[0], u0, v0),
+            (x.dst[1], y.dst[0], u1, v0),
+            (x.dst[1], y.dst[1], u1, v1),
+            (x.dst[0], y.dst[1], u0, v1),
+        ];
+        for &(px, py, u, v) in &corners {
+            mesh.positions.push(Vec3::new(px, py, 0.0));
+            mesh.uvs.push(Vec2::new(u, v));
+            mesh.colors.push(self.color);
+        }
+
+        // counter-clockwise on screen (Y-down)
+        mesh.indices
+            .extend_from_slice(&[base, base + 3, base + 2, base, base + 2, base + 1]);
+    }
+}
+
+/// Three cells along one axis
+///
+/// * `src`: source edges of the cells in pixels
+fn axis(dst: [f32; 2], src: [u32; 4], scale: f32) -> [Span; 3] {
+    let src = src.map(|x| x as f32);
+    let len = (dst[1] - dst[0]).max(0.0);
+
+    let mut a = (src[1] - src[0]) * scale;
+    let mut b = (src[3] - src[2]) * scale;
+    if a + b > len {
+        let k = len / (a + b);
+        a *= k;
+        b *= k;
+    }
+
+    [
+        Span {
+            dst: [dst[0], dst[0] + a],
+            src: [src[0], src[1]],
+        },
+        Span {
+            dst: [dst[0] + a, dst[0] + len - b],
+            src: [src[1], src[2]],
+        },
+        Span {
+            dst: [dst[0] + len - b, dst[0] + len],
+            src: [src[2], src[3]],
+        },
+    ]
+}
+
+/// Splits a cell into quads. Empty cells have no quad
+fn spans(cell: Span, mode: SliceMode, scale: f32) -> Vec<Span> {
+    let len = cell.dst[1] - cell.dst[0];
+    let src_len = cell.src[1] - cell.src[0];
+    if len <= 0.0 || src_len <= 0.0 {
+        return Vec::new();
+    }
+
+    let tile = src_len * scale;
+    if mode == SliceMode::Stretch || tile <= 0.0 || tile >= len {
+        let src = if mode == SliceMode::Tile && tile > len {
+            // cut off the single tile
+            [cell.src[0], cell.src[0] + src_len * len / tile]
+        } else {
+            cell.src
+        };
+        return vec![Span { dst: cell.dst, src }];
+    }
+
+    let n = (len / tile).ceil() as usize;
+    (0..n)
+        .map(|i| {
+            let d0 = cell.dst[0] + tile * i as f32;
+            let d1 = (d0 + tile).min(cell.dst[1]);
+            Span {
+                dst: [d0, d1],
+                src: [cell.src[0], cell.src[0] + src_len * (d1 - d0) / tile],
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 32x32 texture with 8 pixel borders
+    fn panel() -> NineSlice {
+        NineSlice::new(&[32, 32], Insets::uniform(8))
+    }
+
+    fn n_quads(mesh: &MeshData) -> usize {
+        assert_eq!(mesh.indices.len() % 6, 0);
+        assert_eq!(mesh.n_verts(), mesh.indices.len() / 6 * 4);
+        mesh.indices.len() / 6
+    }
+
+    /// Positions and UVs of the `i`-th quad: top-left and bottom-right corners
+    fn quad(mesh: &MeshData, i: usize) -> ([f32; 4], [f32; 4]) {
+        let (p0, p1) = (mesh.positions[4 * i], mesh.positions[4 * i + 2]);
+        let (uv0, uv1) = (mesh.uvs[4 * i], mesh.uvs[4 * i + 2]);
+        ([p0.x, p0.y, p1.x, p1.y], [uv0.x, uv0.y, uv1.x, uv1.y])
+    }
+
+    #[test]
+    fn stretch() {
+        let mesh = panel().build(Rect::new(0.0, 0.0, 100.0, 60.0));
+        assert_eq!(n_quads(&mesh), 9);
+
+        // corners keep their size, the center is stretched
+        assert_eq!(
+            quad(&mesh, 0),
+            ([0.0, 0.0, 8.0, 8.0], [0.0, 0.0, 0.25, 0.25])
+        );
+        assert_eq!(
+            quad(&mesh, 4),
+            ([8.0, 8.0, 92.0, 52.0], [0.25, 0.25, 0.75, 0.75])
+        );
+        assert_eq!(
+            quad(&mesh, 8),
+            ([92.0, 52.0, 100.0, 60.0], [0.75, 0.75, 1.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn tile() {
+        // the 40 pixel center is filled with 2.5 tiles of 16 pixels along each axis
+        let panel = panel().edges(SliceMode::Tile).center(SliceMode::Tile);
+        let mesh = panel.build(Rect::new(0.0, 0.0, 56.0, 56.0));
+        // corners + edges + center
+        assert_eq!(n_quads(&mesh), 4 + 4 * 3 + 3 * 3);
+
+        // the last tile of the top edge is cut off
+        assert_eq!(
+            quad(&mesh, 3),
+            ([40.0, 0.0, 48.0, 8.0], [0.25, 0.0, 0.5, 0.25])
+        );
+
+        // tiles are scaled with the borders: two 32 pixel tiles per edge and no center row
+        let mesh = panel.scale(2.0).build(Rect::new(0.0, 0.0, 96.0, 32.0));
+        assert_eq!(n_quads(&mesh), 4 + 2 * 2);
+        assert_eq!(quad(&mesh, 2).0, [48.0, 0.0, 80.0, 16.0]);
+    }
+
+    #[test]
+    fn shrink_borders() {
+        // borders are halved and the center is empty
+        let mesh = panel().build(Rect::new(10.0, 10.0, 8.0, 32.0));
+        assert_eq!(n_quads(&mesh), 6);
+        assert_eq!(
+            quad(&mesh, 0),
+            ([10.0, 10.0, 14.0, 18.0], [0.0, 0.0, 0.25, 0.25])
+        );
+        assert_eq!(
+            quad(&mesh, 1),
+            ([14.0, 10.0, 18.0, 18.0], [0.75, 0.0, 1.0, 0.25])
+        );
+
+        let mesh = panel().build(Rect::new(0.0, 0.0, 0.0, 0.0));
+        assert_eq!(n_quads(&mesh), 0);
+    }
+
+    #[test]
+    fn region_and_flip_v() {
+        let panel = NineSlice::region(&[64, 64], Rect::new(32, 0, 32, 32), Insets::uniform(8));
+        let mesh = panel.flip_v(true).build(Rect::new(0.0, 0.0, 64.0, 64.0));
+        assert_eq!(quad(&mesh, 0).1, [0.5, 1.0, 0.625, 0.875]);
+        assert_eq!(quad(&mesh, 8).1, [0.875, 0.625, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn winding() {
+        // counter-clockwise on screen: negative signed area in Y-down coordinates
+        let mesh = panel().build(Rect::new(0.0, 0.0, 64.0, 64.0));
+        for tri in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[tri[i] as usize]);
+            let cross = (b - a).x * (c - a).y - (b - a).y * (c - a).x;
+            assert!(cross < 0.0, "{:?}", tri);
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Shader preprocessor for WGSL and GLSL
+//!
+//! Directives:
+//!
+//! - `#include "path"`: path relative to the asset root. Each file is included once
+//! - `#define NAME [value]` and `#undef NAME`: `NAME` is replaced with `value` in code
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`
+//!
+//! Other directives (`#version`, `#extension`, ..) are kept as they are. A permutation of a
+//! shader is the output for a set of [`Defines`]:
+//!
+//! ```ignore
+//! let mut pre = Preprocessor::new("assets/shaders");
+//! let src = pre.permutation("sprite.wgsl", &Defines::new().flag("ALPHA_TEST"))?;
+//! // `wgsl:12:5` in compiler errors becomes `common.wgsl:3:5`
+//! let msg = src.map_error(&compiler_error);
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+
+/// Define set of a shader permutation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Defines {
+    map: BTreeMap<String, String>,
+}
+
+impl Defines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a feature toggle without value
+    pub fn flag(self, name: impl Into<String>) -> Self {
+        self.set(name, "")
+    }
+
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.map.insert(name.into(), value.into());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.map.remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(|v| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Preprocessed shader code with a map from output lines to source lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderSource {
+    code: String,
+    /// Files relative to the asset root, in include order
+    files: Vec<PathBuf>,
+    /// (file index, 1-based line) per output line
+    lines: Vec<(usize, u32)>,
+}
+
+impl ShaderSource {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The shader and its includes, relative to the asset root
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Source file and line of a 1-based output line
+    pub fn location(&self, line: u32) -> Option<(&Path, u32)> {
+        let (file, line) = *self.lines.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    /// Rewrites output line references in a compiler message to source locations
+    ///
+    /// Handles `wgsl:<line>` (naga) and `0:<line>` (GLSL compilers).
+    pub fn map_error(&self, msg: &str) -> String {
+        let mut out = String::with_capacity(msg.len());
+        let mut rest = msg;
+        let mut prev = None;
+
+        while !rest.is_empty() {
+            let at_boundary = !matches!(prev, Some(c) if self::is_ident_char(c));
+
+            if at_boundary {
+                if let Some((line, len)) = ["wgsl:", "0:"]
+                    .iter()
+                    .find_map(|prefix| self::line_ref(rest, prefix))
+                {
+                    if let Some((file, src_line)) = self.location(line) {
+                        write!(out, "{}:{}", file.display(), src_line).unwrap();
+                        rest = &rest[len..];
+                        prev = None;
+                        continue;
+                    }
+                }
+            }
+
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+            prev = Some(c);
+        }
+
+        out
+    }
+}
+
+/// `<prefix><line>` followed by `:` or `(`. Returns the line and the byte length of the match
+fn line_ref(s: &str, prefix: &str) -> Option<(u32, usize)> {
+    let digits = s.strip_prefix(prefix)?;
+    let n = digits.bytes().take_while(u8::is_ascii_digit).count();
+    if n == 0 || !matches!(digits.as_bytes().get(n), Some(b':') | Some(b'(')) {
+        return None;
+    }
+    Some((digits[..n].parse().ok()?, prefix.len() + n))
+}
+
+/// Expands shader files under an asset root and caches permutations
+#[derive(Debug)]
+pub struct Preprocessor {
+    root: PathBuf,
+    cache: HashMap<(PathBuf, Defines), ShaderSource>,
+}
+
+impl Preprocessor {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Preprocesses a file (relative to the root) without caching
+    pub fn process(&self, path: impl AsRef<Path>, defines: &Defines) -> Result<ShaderSource> {
+        let path = path.as_ref();
+        let src = self.read(path)?;
+
+        // so that including the root file doesn't expand it again
+        let full = self.root.join(path);
+        let key = full
+            .canonicalize()
+            .with_context(|| format!("reading shader {}", full.display()))?;
+        self.expand(path, &src, defines, [key].into_iter().collect())
+    }
+
+    /// Preprocesses source code, e.g. from `include_str!`. Includes are read from the root
+    ///
+    /// * `path`: name of the source in line locations
+    pub fn process_str(
+        &self,
+        path: impl AsRef<Path>,
+        src: &str,
+        defines: &Defines,
+    ) -> Result<ShaderSource> {
+        self.expand(path.as_ref(), src, defines, HashSet::new())
+    }
+
+    fn expand(
+        &self,
+        path: &Path,
+        src: &str,
+        defines: &Defines,
+        included: HashSet<PathBuf>,
+    ) -> Result<ShaderSource> {
+        let mut expand = Expand {
+            pre: self,
+            defines: defines.clone(),
+            included,
+            out: ShaderSource {
+                code: String::new(),
+                files: Vec::new(),
+                lines: Vec::new(),
+            },
+        };
+        expand.file(path, src)?;
+        Ok(expand.out)
+    }
+
+    /// Cached [`process`](Self::process)
+    pub fn permutation(
+        &mut self,
+        path: impl AsRef<Path>,
+        defines: &Defines,
+    ) -> Result<&ShaderSource> {
+        let key = (path.as_ref().to_path_buf(), defines.clone());
+        if !self.cache.contains_key(&key) {
+            let src = self.process(&key.0, defines)?;
+            self.cache.insert(key.clone(), src);
+        }
+        Ok(&self.cache[&key])
+    }
+
+    /// Drops the cached permutations, e.g. after shader files are modified
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        let full = self.root.join(path);
+        fs::read_to_string(&full).with_context(|| format!("reading shader {}", full.display()))
+    }
+}
+
+/// `#ifdef` block
+#[derive(Debug, Clone, Copy)]
+struct Cond {
+    /// If the lines are emitted
+    active: bool,
+    /// If the enclosing block is emitted
+    parent: bool,
+    has_else: bool,
+    line: u32,
+}
+
+/// State of one [`Preprocessor::process_str`] call
+struct Expand<'a> {
+    pre: &'a Preprocessor,
+    defines: Defines,
+    /// Canonical paths of the included files
+    included: HashSet<PathBuf>,
+    out: ShaderSource,
+}
+
+impl<'a> Expand<'a> {
+    fn file(&mut self, path: &Path, src: &str) -> Result<()> {
+        let file_ix = self.out.files.len();
+        self.out.files.push(path.to_path_buf());
+
+        let mut conds: Vec<Cond> = Vec::new();
+        for (i, line) in src.lines().enumerate() {
+            let line_no = i as u32 + 1;
+            let active = conds.last().map(|c| c.active).unwrap_or(true);
+
+            self.line(line, line_no, file_ix, active, &mut conds)
+                .with_context(|| format!("{}:{}", path.display(), line_no))?;
+        }
+
+        if let Some(cond) = conds.last() {
+            bail!("{}:{}: unterminated `#ifdef`", path.display(), cond.line);
+        }
+        Ok(())
+    }
+
+    fn line(
+        &mut self,
+        line: &str,
+        line_no: u32,
+        file_ix: usize,
+        active: bool,
+        conds: &mut Vec<Cond>,
+    ) -> Result<()> {
+        let directive = match line.trim_start().strip_prefix('#') {
+            Some(d) => d.trim(),
+            None => {
+                if active {
+                    self.emit(line, file_ix, line_no);
+                }
+                return Ok(());
+            }
+        };
+        let (name, arg) = match directive.find(char::is_whitespace) {
+            Some(ix) => (&directive[..ix], directive[ix..].trim()),
+            None => (directive, ""),
+        };
+
+        match name {
+            "ifdef" | "ifndef" => {
+                let defined = self.defines.contains(self::ident(arg)?);
+                conds.push(Cond {
+                    active: active && (defined == (name == "ifdef")),
+                    parent: active,
+                    has_else: false,
+                    line: line_no,
+                });
+            }
+            "else" => {
+                let cond = conds.last_mut().context("`#else` without `#ifdef`")?;
+                ensure!(!cond.has_else, "second `#else`");
+                cond.has_else = true;
+                cond.active = cond.parent && !cond.active;
+            }
+            "endif" => {
+                conds.pop().context("`#endif` without `#ifdef`")?;
+            }
+            "if" | "elif" => bail!("`#{}` is not supported, use `#ifdef`", name),
+            _ if !active => {}
+            "define" => {
+                let (def, value) = match arg.find(char::is_whitespace) {
+                    Some(ix) => (&arg[..ix], arg[ix..].trim()),
+                    None => (arg, ""),
+                };
+                ensure!(!def.contains('('), "function-like macros are not supported");
+                self.defines.insert(self::ident(def)?, value);
+            }
+            "undef" => {
+                self.defines.remove(self::ident(arg)?);
+            }
+            "include" => {
+                let path = arg
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .context("expected `#include \"path\"`")?;
+                self.include(Path::new(path))?;
+            }
+            // `#version`, `#extension`, `#pragma`, ..
+            _ => self.emit(line, file_ix, line_no),
+        }
+
+        Ok(())
+    }
+
+    fn include(&mut self, path: &Path) -> Result<()> {
+        let full = self.pre.root.join(path);
+        let key = full
+            .canonicalize()
+            .with_context(|| format!("including {}", full.display()))?;
+        if !self.included.insert(key) {
+            return Ok(());
+        }
+
+        let src = self.pre.read(path)?;
+        self.file(path, &src)
+    }
+
+    /// Appends a code line, replacing defined names with their values
+    fn emit(&mut self, line: &str, file_ix: usize, line_no: u32) {
+        let code = &mut self.out.code;
+        let mut rest = line;
+
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            code.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let len = rest
+                .find(|c: char| !self::is_ident_char(c))
+                .unwrap_or(rest.len());
+
+            let word = &rest[..len];
+            match self.defines.get(word) {
+                Some(value) if !value.is_empty() => code.push_str(value),
+                _ => code.push_str(word),
+            }
+            rest = &rest[len..];
+        }
+
+        code.push_str(rest);
+        code.push('\n');
+        self.out.lines.push((file_ix, line_no));
+    }
+}
+
+fn ident(s: &str) -> Result<&str> {
+    let valid = matches!(s.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(self::is_ident_char);
+    ensure!(valid, "expected a name, found `{}`", s);
+    Ok(s)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pre() -> Preprocessor {
+        Preprocessor::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/shaders"
+        ))
+    }
+
+    fn code(src: &str, defines: &Defines) -> String {
+        self::pre()
+            .process_str("test.wgsl", src, defines)
+            .unwrap()
+            .code
+    }
+
+    #[test]
+    fn nested_ifdef() {
+        let src = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+end
+";
+        let d = Defines::new();
+        assert_eq!(code(src, &d), "not_a\nend\n");
+        assert_eq!(code(src, &d.clone().flag("A")), "a\na_not_b\nend\n");
+        assert_eq!(code(src, &d.clone().flag("B")), "not_a\nnot_a_b\nend\n");
+        assert_eq!(code(src, &d.clone().flag("A").flag("B")), "a\na_b\nend\n");
+    }
+
+    #[test]
+    fn defines() {
+        let src = "\
+#define SIZE 4
+#ifdef DEBUG
+#define COLOR red
+#undef SIZE
+#include \"missing.wgsl\"
+#endif
+color = COLOR * SIZE; // SIZE_2 is not replaced
+#undef SIZE
+size = SIZE;
+";
+        // inactive `#define`, `#undef` and `#include` are skipped
+        assert_eq!(
+            code(src, &Defines::new()),
+            "color = COLOR * 4; // SIZE_2 is not replaced\nsize = SIZE;\n"
+        );
+        assert_eq!(
+            code("x = COLOR;\n", &Defines::new().set("COLOR", "blue")),
+            "x = blue;\n"
+        );
+
+        let err = pre()
+            .process_str("test.wgsl", src, &Defines::new().flag("DEBUG"))
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("missing.wgsl"), "{:#}", err);
+    }
+
+    #[test]
+    fn include_once() {
+        let src = pre().process("main.wgsl", &Defines::new()).unwrap();
+        // `main.wgsl` includes itself and `consts.wgsl` twice
+        assert_eq!(
+            src.code(),
+            "let scale = 2.0;\nfn common() {}\nfn main() {}\n"
+        );
+        assert_eq!(
+            src.files(),
+            ["main.wgsl", "common.wgsl", "consts.wgsl"].map(PathBuf::from)
+        );
+
+        assert_eq!(src.location(1), Some((Path::new("consts.wgsl"), 2)));
+        assert_eq!(src.location(2), Some((Path::new("common.wgsl"), 2)));
+        assert_eq!(src.location(3), Some((Path::new("main.wgsl"), 4)));
+        assert_eq!(src.location(0), None);
+        assert_eq!(src.location(4), None);
+    }
+
+    #[test]
+    fn map_error() {
+        let src = pre().process("main.wgsl", &Defines::new()).unwrap();
+
+        assert_eq!(
+            src.map_error("error at wgsl:3:4: unknown type"),
+            "error at main.wgsl:4:4: unknown type"
+        );
+        assert_eq!(
+            src.map_error("0:1(12): error: syntax error"),
+            "consts.wgsl:2(12): error: syntax error"
+        );
+        // out of range, not followed by `:` or `(`, or inside of a word
+        for msg in ["wgsl:9:1", "wgsl:1 x", "10:1(2)", "xwgsl:1:2"] {
+            assert_eq!(src.map_error(msg), msg);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src: &str| {
+            let err = pre()
+                .process_str("test.wgsl", src, &Defines::new())
+                .unwrap_err();
+            format!("{:#}", err)
+        };
+
+        let msg = err("a\n#ifdef A\nb\n");
+        assert!(
+            msg.contains("test.wgsl:2: unterminated `#ifdef`"),
+            "{}",
+            msg
+        );
+        assert!(err("#endif\n").contains("without `#ifdef`"));
+        assert!(err("#ifdef A\n#else\n#else\n#endif\n").contains("second `#else`"));
+        assert!(err("#if A\n").contains("not supported"));
+        assert!(err("#define F(x) x\n").contains("function-like"));
+        assert!(err("#include <a.wgsl>\n").contains("expected `#include"));
+    }
+}
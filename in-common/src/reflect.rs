@@ -0,0 +1,448 @@
+//! GLSL shader reflection
+//!
+//! Lists the loose `uniform` declarations and the samplers of a shader stage, so that uniform
+//! blocks and image slots can be built from the source instead of by hand:
+//!
+//! ```ignore
+//! let fs = StageReflection::parse(&fs_src)?;
+//! assert_eq!(fs.images[0].name, "tex1");
+//!
+//! let vs = StageReflection::parse(&vs_src)?;
+//! // error if `Globals` doesn't match the `uniform` declarations
+//! vs.check_block::<Globals>()?;
+//! ```
+//!
+//! Uniforms are laid out as one std140 block in declaration order. Uniform blocks
+//! (`uniform Name { .. };`) and preprocessor directives are not handled; preprocess the source
+//! first (see [`crate::preprocess`]).
+
+use anyhow::*;
+
+use crate::uniform::{self, UniformBlock, UniformLayout, UniformType};
+
+/// Sampler type of an image slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    /// `sampler2D`
+    Dim2,
+    /// `sampler2DArray`
+    Array,
+    /// `sampler3D`
+    Dim3,
+    /// `samplerCube`
+    Cube,
+}
+
+impl ImageKind {
+    /// Parses a GLSL sampler type name, e.g. `sampler2D`
+    pub fn from_glsl(name: &str) -> Option<Self> {
+        Some(match name {
+            "sampler2D" => Self::Dim2,
+            "sampler2DArray" => Self::Array,
+            "sampler3D" => Self::Dim3,
+            "samplerCube" => Self::Cube,
+            _ => return None,
+        })
+    }
+}
+
+/// Loose `uniform` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedUniform {
+    pub name: String,
+    pub ty: UniformType,
+    /// Array length. `1` for a single value
+    pub count: usize,
+    /// Byte offset in the std140 block
+    pub offset: usize,
+}
+
+/// `uniform sampler*` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedImage {
+    pub name: String,
+    pub kind: ImageKind,
+}
+
+/// Uniforms and images of one shader stage, in declaration order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StageReflection {
+    pub uniforms: Vec<ReflectedUniform>,
+    pub images: Vec<ReflectedImage>,
+}
+
+impl StageReflection {
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut refl = Self::default();
+        let mut end = 0;
+
+        for (line, decl) in self::global_statements(&self::strip_comments(src))? {
+            refl.declaration(decl, &mut end)
+                .with_context(|| format!("line {}", line))?;
+        }
+
+        Ok(refl)
+    }
+
+    /// Size of the uniform block in bytes (a multiple of 16). `0` if there's no uniform
+    pub fn block_size(&self) -> usize {
+        match self.uniforms.last() {
+            Some(u) => uniform::round_up(u.offset + u.ty.array_size(u.count), 16),
+            None => 0,
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&ReflectedUniform> {
+        self.uniforms.iter().find(|u| u.name == name)
+    }
+
+    /// Image slot of a sampler
+    pub fn image_slot(&self, name: &str) -> Option<usize> {
+        self.images.iter().position(|img| img.name == name)
+    }
+
+    /// Fails if the std140 block has padding between or after the uniforms
+    ///
+    /// Backends that upload the loose uniforms one by one from the block (e.g. `sokol`'s GL
+    /// backend) read them tightly packed.
+    pub fn check_packed(&self) -> Result<()> {
+        let mut packed = 0;
+        for u in &self.uniforms {
+            let size = u.ty.size() * u.count;
+            ensure!(
+                u.offset == packed && size == u.ty.array_size(u.count),
+                "uniform `{}` is padded in the std140 block; reorder the uniforms or add `float`s",
+                u.name
+            );
+            packed += size;
+        }
+
+        ensure!(
+            packed == self.block_size(),
+            "the uniforms are {} bytes, padded to {} in the std140 block; add `float`s",
+            packed,
+            self.block_size()
+        );
+        Ok(())
+    }
+
+    /// Checks the fields of `T` against the uniforms, one field per uniform in the same order
+    ///
+    /// Field names are not compared; types, array lengths, offsets and the size are.
+    pub fn check_block<T: UniformBlock>(&self) -> Result<()> {
+        let name = std::any::type_name::<T>();
+        let layout = UniformLayout::of::<T>()?;
+        let fields = layout.fields();
+
+        ensure!(
+            fields.len() == self.uniforms.len(),
+            "`{}` has {} fields, but the shader declares {} uniforms ({})",
+            name,
+            fields.len(),
+            self.uniforms.len(),
+            self.uniforms
+                .iter()
+                .map(|u| u.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        for (f, u) in fields.iter().zip(&self.uniforms) {
+            ensure!(
+                f.ty == u.ty && f.count == u.count,
+                "`{}::{}` is {:?} x {}, but uniform `{}` is {:?} x {}",
+                name,
+                f.name,
+                f.ty,
+                f.count,
+                u.name,
+                u.ty,
+                u.count
+            );
+            ensure!(
+                f.offset == u.offset,
+                "`{}::{}` is at offset {}, but uniform `{}` is at offset {}",
+                name,
+                f.name,
+                f.offset,
+                u.name,
+                u.offset
+            );
+        }
+
+        layout.check_size(self.block_size())
+    }
+
+    /// Adds a global declaration. `end` is the end of the uniform block so far
+    fn declaration(&mut self, decl: &str, end: &mut usize) -> Result<()> {
+        let mut rest = decl.trim();
+        if let Some(l) = rest.strip_prefix("layout") {
+            let l = l
+                .trim_start()
+                .strip_prefix('(')
+                .context("expected `layout(..)`")?;
+            rest = &l[l.find(')').context("unclosed `layout(`")? + 1..];
+        }
+
+        let mut words = rest.split_whitespace();
+        if words.next() != Some("uniform") {
+            // inputs, outputs, constants, structs, ..
+            return Ok(());
+        }
+
+        let mut ty = words.next().context("expected a uniform type")?;
+        if matches!(ty, "lowp" | "mediump" | "highp") {
+            ty = words.next().context("expected a uniform type")?;
+        }
+
+        let declarators = words.collect::<Vec<_>>().join("");
+        ensure!(!declarators.is_empty(), "expected a uniform name");
+
+        for declarator in declarators.split(',') {
+            let (name, count) = self::declarator(declarator)?;
+
+            if let Some(kind) = ImageKind::from_glsl(ty) {
+                ensure!(count == 1, "sampler arrays are not supported: `{}`", name);
+                self.images.push(ReflectedImage { name, kind });
+            } else if let Some(ty) = UniformType::from_glsl(ty) {
+                let offset = uniform::round_up(*end, ty.array_align(count));
+                *end = offset + ty.array_size(count);
+                self.uniforms.push(ReflectedUniform {
+                    name,
+                    ty,
+                    count,
+                    offset,
+                });
+            } else {
+                bail!("unsupported uniform type `{}` of `{}`", ty, name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `name` or `name[N]`
+fn declarator(s: &str) -> Result<(String, usize)> {
+    let (name, count) = match s.find('[') {
+        Some(ix) => {
+            let n = s[ix + 1..]
+                .strip_suffix(']')
+                .with_context(|| format!("expected `name[N]`, found `{}`", s))?;
+            let count = n
+                .parse::<usize>()
+                .with_context(|| format!("array length of `{}` is not a number", s))?;
+            ensure!(count > 0, "`{}` is an empty array", s);
+            (&s[..ix], count)
+        }
+        None => (s, 1),
+    };
+
+    let valid = matches!(name.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    ensure!(valid, "expected a uniform name, found `{}`", name);
+    Ok((name.to_string(), count))
+}
+
+/// Replaces comments and preprocessor directives with spaces, keeping the line breaks
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                while matches!(chars.peek(), Some(&c) if c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            '#' if line_start => {
+                while matches!(chars.peek(), Some(&c) if c != '\n') {
+                    chars.next();
+                }
+            }
+            _ => {
+                out.push(c);
+                if c == '\n' {
+                    line_start = true;
+                } else if !c.is_whitespace() {
+                    line_start = false;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// `;`-terminated statements outside of braces with their 1-based start lines
+fn global_statements(src: &str) -> Result<Vec<(u32, &str)>> {
+    let mut stmts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut line = 1;
+    let mut start_line = None;
+
+    for (ix, c) in src.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 && self::is_uniform_block(&src[start..ix]) {
+                    bail!(
+                        "line {}: uniform blocks are not supported, declare loose uniforms",
+                        start_line.unwrap_or(line)
+                    );
+                }
+                depth += 1;
+            }
+            '}' => {
+                depth = depth
+                    .checked_sub(1)
+                    .with_context(|| format!("line {}: unmatched `}}`", line))?;
+                if depth == 0 {
+                    // function body or struct definition
+                    start = ix + 1;
+                    start_line = None;
+                }
+            }
+            ';' if depth == 0 => {
+                stmts.push((start_line.unwrap_or(line), &src[start..ix]));
+                start = ix + 1;
+                start_line = None;
+            }
+            '\n' => line += 1,
+            _ if depth == 0 && start_line.is_none() && !c.is_whitespace() => {
+                start_line = Some(line);
+            }
+            _ => {}
+        }
+    }
+
+    ensure!(depth == 0, "unclosed `{{`");
+    Ok(stmts)
+}
+
+fn is_uniform_block(head: &str) -> bool {
+    head.split(|c: char| c.is_whitespace() || c == ')')
+        .any(|w| w == "uniform")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn parse(src: &str) -> StageReflection {
+        StageReflection::parse(src).unwrap()
+    }
+
+    /// Every shader of `in-rokol` is reflected and tightly packed
+    #[test]
+    fn rokol_shaders() -> Result<()> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../in-rokol/src/glsl");
+        let mut n = 0;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let src = std::fs::read_to_string(&path)?;
+            let refl = StageReflection::parse(&src)
+                .and_then(|refl| refl.check_packed().map(|()| refl))
+                .with_context(|| format!("{}", path.display()))?;
+
+            let name = path.file_name().unwrap().to_str().unwrap();
+            match name {
+                "cube.vs" | "cube_multi.vs" | "more_cubes.vs" | "texture_multi.vs"
+                | "tilemap.vs" => {
+                    assert_eq!(refl.uniforms.len(), 1, "{}", name);
+                    let mvp = refl.uniform("mvp").unwrap();
+                    assert_eq!((mvp.ty, mvp.count, mvp.offset), (UniformType::Mat4, 1, 0));
+                    assert_eq!(refl.block_size(), 64);
+                }
+                "cube_multi.fs" | "more_cubes.fs" | "texture_multi.fs" => {
+                    assert_eq!(refl.image_slot("tex1"), Some(0), "{}", name);
+                    assert_eq!(refl.image_slot("tex2"), Some(1), "{}", name);
+                }
+                "cube.fs" | "texture.fs" | "tilemap.fs" => {
+                    assert_eq!(refl.images.len(), 1, "{}", name);
+                    assert_eq!(refl.images[0].kind, ImageKind::Dim2);
+                }
+                _ => {}
+            }
+            n += 1;
+        }
+        assert!(n >= 16, "{} shaders in {}", n, dir.display());
+
+        Ok(())
+    }
+
+    #[test]
+    fn std140_offsets() {
+        let refl = parse(
+            "uniform float a;
+            uniform highp vec3 b; // comment
+            layout(location = 0) uniform vec2 c[2], d;
+            void main() { uniform_like(); }",
+        );
+        let layout = refl
+            .uniforms
+            .iter()
+            .map(|u| (u.name.as_str(), u.count, u.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layout,
+            [("a", 1, 0), ("b", 1, 16), ("c", 2, 32), ("d", 1, 64)]
+        );
+        assert_eq!(refl.block_size(), 80);
+    }
+
+    #[test]
+    fn tight_packing() {
+        parse("uniform mat4 m; uniform vec3 v; uniform float f;")
+            .check_packed()
+            .unwrap();
+
+        // `vec3` is aligned to 16 bytes after a `float`
+        let err = parse("uniform float f; uniform vec3 v;")
+            .check_packed()
+            .unwrap_err();
+        assert!(err.to_string().contains("`v`"), "{}", err);
+
+        // the block size is a multiple of 16 bytes
+        let err = parse("uniform vec3 v;").check_packed().unwrap_err();
+        assert!(
+            err.to_string().contains("12 bytes, padded to 16"),
+            "{}",
+            err
+        );
+        parse("uniform vec3 v; uniform float f;")
+            .check_packed()
+            .unwrap();
+
+        // array elements have a 16-byte stride
+        assert!(parse("uniform float xs[2];").check_packed().is_err());
+        assert!(parse("uniform vec4 xs[2];").check_packed().is_ok());
+    }
+
+    #[test]
+    fn rejects_uniform_blocks() {
+        assert!(StageReflection::parse("uniform vs_params { mat4 mvp; };").is_err());
+        assert!(StageReflection::parse("layout(std140) uniform P { mat4 mvp; };").is_err());
+        // structs and functions are skipped
+        let refl = parse("struct S { float x; }; uniform sampler2D tex; void f() { }");
+        assert_eq!(refl.image_slot("tex"), Some(0));
+    }
+}
@@ -0,0 +1,382 @@
+//! 2D shape tessellation into [`MeshData`]
+//!
+//! Shapes are built on the XY plane with counter-clockwise triangles and UVs mapped to the
+//! bounding box of each shape (top-left origin). Convert the result with
+//! [`MeshData::vertices`] and [`MeshData::indices`] (or [`MeshData::indices_u16`]).
+//!
+//! ```ignore
+//! let mesh = Tessellator::new().polygon(Vec2::zero(), 0.5, 5, 0.0).build();
+//! let mesh = StaticMesh::new(&device, &mesh.vertices::<TriVertex>(), &mesh.indices_u16().unwrap());
+//! ```
+
+mod stroke;
+mod triangulate;
+
+pub use self::{
+    stroke::{LineCap, LineJoin, StrokeStyle},
+    triangulate::triangulate,
+};
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use anyhow::*;
+use vek::{Rect, Rgba, Vec2, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Maximum number of segments for a full circle
+const MAX_SEGMENTS: u32 = 1024;
+
+/// Number of segments to approximate an arc within the tolerance
+///
+/// * `radius`: the larger radius for ellipses
+/// * `angle`: arc angle in radians
+pub fn arc_segments(radius: f32, angle: f32, tolerance: f32) -> u32 {
+    let angle = angle.abs();
+    if radius <= tolerance || angle <= f32::EPSILON {
+        return 1;
+    }
+
+    // the distance from a chord to the arc is `r * (1 - cos(step / 2))`
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    let n = (angle / step).ceil() as u32;
+
+    // at least 8 segments for a full circle
+    let min = ((8.0 * angle / TAU).ceil() as u32).max(1);
+    n.max(min).min(MAX_SEGMENTS)
+}
+
+/// Accumulates 2D shapes into one mesh
+#[derive(Debug, Clone)]
+pub struct Tessellator {
+    /// Maximum distance between curves and their approximating segments
+    pub tolerance: f32,
+    /// Color of subsequent shapes
+    pub color: Rgba<f32>,
+    /// Positions are in Y-down (screen) coordinates: UV `v` grows with Y and triangles are
+    /// clockwise in the given coordinates, so they're counter-clockwise on screen
+    pub y_down: bool,
+    mesh: MeshData,
+}
+
+impl Default for Tessellator {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            color: Rgba::white(),
+            y_down: false,
+            mesh: MeshData::default(),
+        }
+    }
+}
+
+impl Tessellator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the mesh and clears the tessellator
+    pub fn build(&mut self) -> MeshData {
+        std::mem::take(&mut self.mesh)
+    }
+
+    pub fn mesh(&self) -> &MeshData {
+        &self.mesh
+    }
+
+    pub fn tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn color(&mut self, color: impl Into<Rgba<f32>>) -> &mut Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn y_down(&mut self, y_down: bool) -> &mut Self {
+        self.y_down = y_down;
+        self
+    }
+
+    /// Regular polygon
+    ///
+    /// * `angle`: angle of the first vertex in radians (counter-clockwise from +X)
+    pub fn polygon(
+        &mut self,
+        center: Vec2<f32>,
+        radius: f32,
+        n_sides: u32,
+        angle: f32,
+    ) -> &mut Self {
+        assert!(n_sides >= 3, "polygon with {} sides", n_sides);
+        let outline = (0..n_sides)
+            .map(|i| center + self::unit(angle + TAU * i as f32 / n_sides as f32) * radius)
+            .collect::<Vec<_>>();
+        self.convex(&outline)
+    }
+
+    pub fn circle(&mut self, center: Vec2<f32>, radius: f32) -> &mut Self {
+        self.ellipse(center, Vec2::broadcast(radius))
+    }
+
+    /// Axis-aligned ellipse
+    pub fn ellipse(&mut self, center: Vec2<f32>, radii: Vec2<f32>) -> &mut Self {
+        let n = self::arc_segments(radii.reduce_partial_max(), TAU, self.tolerance).max(3);
+        let outline = (0..n)
+            .map(|i| center + self::unit(TAU * i as f32 / n as f32) * radii)
+            .collect::<Vec<_>>();
+        self.fan(center, &outline, true)
+    }
+
+    /// Pie slice from `start` to `end` angle in radians (counter-clockwise from +X)
+    pub fn arc(&mut self, center: Vec2<f32>, radius: f32, start: f32, end: f32) -> &mut Self {
+        let (start, end) = if start <= end {
+            (start, end)
+        } else {
+            (end, start)
+        };
+        let sweep = (end - start).min(TAU);
+        if sweep >= TAU {
+            return self.circle(center, radius);
+        }
+
+        let n = self::arc_segments(radius, sweep, self.tolerance);
+        let outline = (0..=n)
+            .map(|i| center + self::unit(start + sweep * i as f32 / n as f32) * radius)
+            .collect::<Vec<_>>();
+        self.fan(center, &outline, false)
+    }
+
+    pub fn rect(&mut self, rect: impl Into<Rect<f32, f32>>) -> &mut Self {
+        let r = rect.into();
+        self.convex(&[
+            Vec2::new(r.x, r.y),
+            Vec2::new(r.x + r.w, r.y),
+            Vec2::new(r.x + r.w, r.y + r.h),
+            Vec2::new(r.x, r.y + r.h),
+        ])
+    }
+
+    /// Rectangle with circular corners. The radius is clamped to half of the shorter side
+    pub fn rounded_rect(&mut self, rect: impl Into<Rect<f32, f32>>, radius: f32) -> &mut Self {
+        let r = rect.into();
+        let radius = radius.max(0.0).min(r.w.min(r.h) / 2.0);
+        if radius <= 0.0 {
+            return self.rect(r);
+        }
+
+        let n = self::arc_segments(radius, FRAC_PI_2, self.tolerance);
+        let (x0, y0) = (r.x + radius, r.y + radius);
+        let (x1, y1) = (r.x + r.w - radius, r.y + r.h - radius);
+
+        // corners in counter-clockwise order (Y-up), starting from the bottom-left
+        let corners = [
+            (Vec2::new(x0, y0), PI),
+            (Vec2::new(x1, y0), PI * 1.5),
+            (Vec2::new(x1, y1), 0.0),
+            (Vec2::new(x0, y1), FRAC_PI_2),
+        ];
+
+        let mut outline = Vec::with_capacity(4 * (n as usize + 1));
+        for (c, start) in corners.iter() {
+            for i in 0..=n {
+                outline.push(*c + self::unit(start + FRAC_PI_2 * i as f32 / n as f32) * radius);
+            }
+        }
+
+        let center = Vec2::new(r.x + r.w / 2.0, r.y + r.h / 2.0);
+        self.fan(center, &outline, true)
+    }
+
+    /// Convex polygon in counter-clockwise (Y-up) order, triangulated as a fan from the first
+    /// vertex
+    pub fn convex(&mut self, outline: &[Vec2<f32>]) -> &mut Self {
+        if outline.len() < 3 {
+            return self;
+        }
+
+        let base = self.push_verts(outline);
+        for i in 1..outline.len() as u32 - 1 {
+            self.push_tri(base, base + i, base + i + 1);
+        }
+        self
+    }
+
+    /// Arbitrary (concave) outline with holes. See [`triangulate`] for the accepted inputs
+    ///
+    /// UVs are mapped to the bounding box of the outline.
+    pub fn polygon_with_holes(
+        &mut self,
+        outline: &[Vec2<f32>],
+        holes: &[&[Vec2<f32>]],
+    ) -> Result<&mut Self> {
+        let mut contours = Vec::with_capacity(holes.len() + 1);
+        contours.push(outline);
+        contours.extend_from_slice(holes);
+        let indices = self::triangulate(&contours)?;
+
+        let base = self.push_verts(outline);
+        for hole in holes {
+            self.push_verts_in(hole, outline);
+        }
+
+        for tri in indices.chunks_exact(3) {
+            self.push_tri(base + tri[0], base + tri[1], base + tri[2]);
+        }
+        Ok(self)
+    }
+
+    /// Convex outline triangulated as a fan from an extra center vertex
+    fn fan(&mut self, center: Vec2<f32>, outline: &[Vec2<f32>], closed: bool) -> &mut Self {
+        let mut verts = Vec::with_capacity(outline.len() + 1);
+        verts.push(center);
+        verts.extend_from_slice(outline);
+        let base = self.push_verts(&verts);
+
+        let n = outline.len() as u32;
+        for i in 0..n - 1 {
+            self.push_tri(base, base + 1 + i, base + 2 + i);
+        }
+        if closed {
+            self.push_tri(base, base + n, base + 1);
+        }
+        self
+    }
+
+    /// Pushes vertices with UVs mapped to their bounding box
+    fn push_verts(&mut self, verts: &[Vec2<f32>]) -> u32 {
+        self.push_verts_in(verts, verts)
+    }
+
+    /// Pushes vertices with UVs mapped to the bounding box of `bounds`
+    fn push_verts_in(&mut self, verts: &[Vec2<f32>], bounds: &[Vec2<f32>]) -> u32 {
+        let min = bounds
+            .iter()
+            .fold(Vec2::broadcast(f32::MAX), |a, b| Vec2::partial_min(a, *b));
+        let max = bounds
+            .iter()
+            .fold(Vec2::broadcast(f32::MIN), |a, b| Vec2::partial_max(a, *b));
+        let size = (max - min).map(|x| if x > 0.0 { x } else { 1.0 });
+
+        let base = self.mesh.positions.len() as u32;
+        for &p in verts {
+            let t = (p - min) / size;
+            let uv = if self.y_down {
+                t
+            } else {
+                Vec2::new(t.x, 1.0 - t.y)
+            };
+
+            self.mesh.positions.push(Vec3::from(p));
+            self.mesh.uvs.push(uv);
+            self.mesh.colors.push(self.color);
+        }
+
+        base
+    }
+
+    fn push_tri(&mut self, a: u32, b: u32, c: u32) {
+        if self.y_down {
+            self.mesh.indices.extend_from_slice(&[a, c, b]);
+        } else {
+            self.mesh.indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+}
+
+fn unit(angle: f32) -> Vec2<f32> {
+    Vec2::new(angle.cos(), angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signed areas of the triangles (positive if counter-clockwise in Y-up coordinates)
+    fn areas(mesh: &MeshData) -> Vec<f32> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|tri| {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[tri[i] as usize].xy());
+                ((b - a).x * (c - a).y - (b - a).y * (c - a).x) / 2.0
+            })
+            .collect()
+    }
+
+    fn shapes(y_down: bool) -> MeshData {
+        let mut t = Tessellator::new();
+        t.y_down(y_down)
+            .polygon(Vec2::zero(), 1.0, 5, 0.3)
+            .circle(Vec2::new(4.0, 0.0), 2.0)
+            .arc(Vec2::zero(), 1.0, 1.0, -1.0)
+            .rect(Rect::new(0.0, 0.0, 2.0, 1.0))
+            .rounded_rect(Rect::new(0.0, 0.0, 4.0, 2.0), 0.5);
+        t.build()
+    }
+
+    #[test]
+    fn winding() {
+        assert!(areas(&shapes(false)).iter().all(|&a| a > 0.0));
+        assert!(areas(&shapes(true)).iter().all(|&a| a < 0.0));
+    }
+
+    #[test]
+    fn uvs_span_bbox() {
+        let mut t = Tessellator::new();
+        let mesh = t.circle(Vec2::new(3.0, 3.0), 2.0).build();
+        let min = mesh
+            .uvs
+            .iter()
+            .fold(Vec2::broadcast(1.0), |a, b| Vec2::partial_min(a, *b));
+        let max = mesh
+            .uvs
+            .iter()
+            .fold(Vec2::broadcast(0.0), |a, b| Vec2::partial_max(a, *b));
+        assert_eq!((min, max), (Vec2::zero(), Vec2::one()));
+
+        // `v` is zero at the top: max Y (Y-up) or min Y (Y-down)
+        let mesh = t.rect(Rect::new(0.0, 0.0, 2.0, 1.0)).build();
+        assert_eq!(mesh.positions[3], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh.uvs[3], Vec2::new(0.0, 0.0));
+        let mesh = t.y_down(true).rect(Rect::new(0.0, 0.0, 2.0, 1.0)).build();
+        assert_eq!(mesh.uvs[3], Vec2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn segments() {
+        assert_eq!(arc_segments(0.01, TAU, 0.01), 1);
+        assert_eq!(arc_segments(1.0, 0.0, 0.01), 1);
+        // at least 8 for a circle, at most `MAX_SEGMENTS`
+        assert_eq!(arc_segments(0.1, TAU, 0.05), 8);
+        assert_eq!(arc_segments(0.1, PI, 0.05), 4);
+        assert_eq!(arc_segments(1e6, TAU, 1e-3), MAX_SEGMENTS);
+
+        // the chord error is within the tolerance
+        for (radius, tolerance) in [(1.0, 0.01), (100.0, 0.25), (10.0, 0.001)] {
+            let n = arc_segments(radius, TAU, tolerance);
+            let error = radius * (1.0 - (PI / n as f32).cos());
+            assert!(error <= tolerance * 1.01, "{} {} {}", radius, tolerance, n);
+            assert!(arc_segments(radius, TAU, tolerance / 4.0) > n);
+        }
+    }
+
+    #[test]
+    fn rounded_rect() {
+        let mut t = Tessellator::new();
+        let rect = t.rect(Rect::new(0.0, 0.0, 4.0, 2.0)).build();
+        let zero = t.rounded_rect(Rect::new(0.0, 0.0, 4.0, 2.0), 0.0).build();
+        assert_eq!(
+            (zero.positions, zero.uvs, zero.indices),
+            (rect.positions, rect.uvs, rect.indices)
+        );
+
+        // clamped to a stadium
+        let mesh = t.rounded_rect(Rect::new(0.0, 0.0, 4.0, 2.0), 5.0).build();
+        for p in &mesh.positions {
+            assert!((-1e-5..=4.0 + 1e-5).contains(&p.x) && (-1e-5..=2.0 + 1e-5).contains(&p.y));
+        }
+        let top = mesh.positions.iter().map(|p| p.y).fold(0.0, f32::max);
+        assert!((top - 2.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,539 @@
+//! Thick lines: polylines and closed paths into triangles
+//!
+//! Each segment is a quad and the gaps at corners are filled with join geometry. Overlapping
+//! triangles on the inner side of corners are not removed, so translucent strokes get darker
+//! there.
+//!
+//! UVs: `u` is the distance along the path divided by its length, `v` goes from `0.0` (left
+//! side) to `1.0` (right side).
+
+use std::f32::consts::PI;
+
+use vek::{Rgba, Vec2, Vec3};
+
+use crate::shape::{self, Tessellator};
+
+/// Shape of stroke corners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Sharp corner, falling back to [`LineJoin::Bevel`] beyond [`StrokeStyle::miter_limit`]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Shape of open path ends (and dashes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// Ends exactly at the end point
+    Butt,
+    Round,
+    /// Extended by half the width
+    Square,
+}
+
+/// Stroke parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Maximum ratio of the miter length to half the width
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths. Empty for solid strokes
+    pub dash: Vec<f32>,
+    /// Distance into the dash pattern at the start of the path
+    pub dash_offset: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+            dash: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            ..Default::default()
+        }
+    }
+
+    pub fn join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn miter_limit(mut self, limit: f32) -> Self {
+        self.miter_limit = limit;
+        self
+    }
+
+    pub fn dash(mut self, pattern: impl Into<Vec<f32>>, offset: f32) -> Self {
+        self.dash = pattern.into();
+        self.dash_offset = offset;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct StrokePoint {
+    pos: Vec2<f32>,
+    color: Rgba<f32>,
+    /// Distance from the start of the path
+    dist: f32,
+}
+
+impl StrokePoint {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        Self {
+            pos: Vec2::lerp(a.pos, b.pos, t),
+            color: Rgba::lerp(a.color, b.color, t),
+            dist: a.dist + (b.dist - a.dist) * t,
+        }
+    }
+}
+
+/// Left normal
+fn normal(d: Vec2<f32>) -> Vec2<f32> {
+    Vec2::new(-d.y, d.x)
+}
+
+fn cross(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+impl Tessellator {
+    /// Strokes a polyline (or a closed path) with the current color
+    pub fn stroke(&mut self, points: &[Vec2<f32>], closed: bool, style: &StrokeStyle) -> &mut Self {
+        let colors = vec![self.color; points.len()];
+        self.stroke_colored(points, &colors, closed, style)
+    }
+
+    /// Strokes a polyline (or a closed path) with per-point colors
+    pub fn stroke_colored(
+        &mut self,
+        points: &[Vec2<f32>],
+        colors: &[Rgba<f32>],
+        closed: bool,
+        style: &StrokeStyle,
+    ) -> &mut Self {
+        assert_eq!(points.len(), colors.len(), "stroke: points and colors");
+
+        let mut pts = Vec::<StrokePoint>::with_capacity(points.len());
+        let mut dist = 0.0;
+        for (&pos, &color) in points.iter().zip(colors) {
+            if let Some(last) = pts.last() {
+                if last.pos == pos {
+                    continue;
+                }
+                dist += (pos - last.pos).magnitude();
+            }
+            pts.push(StrokePoint { pos, color, dist });
+        }
+
+        if closed && pts.len() > 1 && pts[0].pos == pts[pts.len() - 1].pos {
+            pts.pop();
+        }
+        if pts.len() < 2 || style.width <= 0.0 {
+            return self;
+        }
+
+        let closed = closed && pts.len() > 2;
+        let total = if closed {
+            let (first, last) = (pts[0], pts[pts.len() - 1]);
+            last.dist + (first.pos - last.pos).magnitude()
+        } else {
+            pts[pts.len() - 1].dist
+        };
+
+        let pattern_len = style.dash.iter().sum::<f32>();
+        if style.dash.is_empty() || pattern_len <= 0.0 || style.dash.iter().any(|&x| x < 0.0) {
+            self.stroke_polyline(&pts, closed, style, total);
+        } else {
+            for dash in self::split_dashes(&pts, closed, total, style) {
+                match dash {
+                    Dash::Line(pts) => self.stroke_polyline(&pts, false, style, total),
+                    Dash::Dot(p, dir) => self.stroke_dot(p, dir, style, total),
+                }
+            }
+        }
+
+        self
+    }
+
+    fn stroke_polyline(
+        &mut self,
+        pts: &[StrokePoint],
+        closed: bool,
+        style: &StrokeStyle,
+        total: f32,
+    ) {
+        // zero-length segments have no direction
+        let mut pts = pts.to_vec();
+        pts.dedup_by(|b, a| self::is_same_point(a.pos, b.pos));
+        if closed && pts.len() > 1 && self::is_same_point(pts[0].pos, pts[pts.len() - 1].pos) {
+            pts.pop();
+        }
+        if pts.len() < 2 {
+            return;
+        }
+        let closed = closed && pts.len() > 2;
+
+        let hw = style.width / 2.0;
+        let n = pts.len();
+        let n_segs = if closed { n } else { n - 1 };
+        let dirs = (0..n_segs)
+            .map(|i| (pts[(i + 1) % n].pos - pts[i].pos).normalized())
+            .collect::<Vec<_>>();
+
+        // segments
+        for i in 0..n_segs {
+            let (a, b) = (pts[i], pts[(i + 1) % n]);
+            let (d, nrm) = (dirs[i], self::normal(dirs[i]) * hw);
+
+            let (mut pa, mut pb) = (a.pos, b.pos);
+            if !closed && style.cap == LineCap::Square {
+                if i == 0 {
+                    pa -= d * hw;
+                }
+                if i == n_segs - 1 {
+                    pb += d * hw;
+                }
+            }
+
+            let v0 = self.push_stroke_vert(pa + nrm, Vec2::new(a.dist / total, 0.0), a.color);
+            let v1 = self.push_stroke_vert(pa - nrm, Vec2::new(a.dist / total, 1.0), a.color);
+            let v2 = self.push_stroke_vert(pb + nrm, Vec2::new(b.dist / total, 0.0), b.color);
+            let v3 = self.push_stroke_vert(pb - nrm, Vec2::new(b.dist / total, 1.0), b.color);
+            self.push_tri_ccw(v0, v1, v3);
+            self.push_tri_ccw(v0, v3, v2);
+        }
+
+        // joins
+        let joints = if closed { 0..n } else { 1..n - 1 };
+        for j in joints {
+            let d0 = dirs[(j + n_segs - 1) % n_segs];
+            let d1 = dirs[j % n_segs];
+            self.stroke_join(pts[j], d0, d1, hw, style, total);
+        }
+
+        // caps
+        if !closed && style.cap == LineCap::Round {
+            let (first, last) = (pts[0], pts[n - 1]);
+            let (d0, d1) = (dirs[0], dirs[n_segs - 1]);
+            self.stroke_fan(first, self::normal(d0), PI, hw, d0, total);
+            self.stroke_fan(last, -self::normal(d1), PI, hw, d1, total);
+        }
+    }
+
+    /// Cap-only zero-length dash: a circle or a square along `dir`. Nothing for butt caps
+    fn stroke_dot(&mut self, p: StrokePoint, dir: Vec2<f32>, style: &StrokeStyle, total: f32) {
+        let hw = style.width / 2.0;
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Round => self.stroke_fan(p, self::normal(dir), 2.0 * PI, hw, dir, total),
+            LineCap::Square => {
+                let (d, nrm) = (dir * hw, self::normal(dir) * hw);
+                let u = p.dist / total;
+                let v0 = self.push_stroke_vert(p.pos - d + nrm, Vec2::new(u, 0.0), p.color);
+                let v1 = self.push_stroke_vert(p.pos - d - nrm, Vec2::new(u, 1.0), p.color);
+                let v2 = self.push_stroke_vert(p.pos + d + nrm, Vec2::new(u, 0.0), p.color);
+                let v3 = self.push_stroke_vert(p.pos + d - nrm, Vec2::new(u, 1.0), p.color);
+                self.push_tri_ccw(v0, v1, v3);
+                self.push_tri_ccw(v0, v3, v2);
+            }
+        }
+    }
+
+    /// Fills the gap on the outer side of a corner
+    fn stroke_join(
+        &mut self,
+        p: StrokePoint,
+        d0: Vec2<f32>,
+        d1: Vec2<f32>,
+        hw: f32,
+        style: &StrokeStyle,
+        total: f32,
+    ) {
+        let (cross, dot) = (self::cross(d0, d1), d0.dot(d1));
+        if cross.abs() <= 1e-6 && dot > 0.0 {
+            // straight
+            return;
+        }
+
+        // the outer side is on the right for left turns
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let n0 = self::normal(d0) * side;
+        let n1 = self::normal(d1) * side;
+
+        let bevel = |this: &mut Self| {
+            if self::cross(n0, n1).abs() <= 1e-6 {
+                // U-turn: the bevel is a line
+                return;
+            }
+            let c = this.push_stroke_vert(p.pos, Vec2::new(p.dist / total, 0.5), p.color);
+            let a = this.push_stroke_join_vert(p, n0 * hw, d0, total);
+            let b = this.push_stroke_join_vert(p, n1 * hw, d0, total);
+            this.push_tri_ccw(c, a, b);
+        };
+
+        match style.join {
+            LineJoin::Bevel => bevel(self),
+            LineJoin::Miter => {
+                // U-turns have no miter (and `n0 + n1` is zero)
+                if n0.dot(n1) < -1.0 + 1e-6 {
+                    bevel(self);
+                    return;
+                }
+
+                let m = (n0 + n1).normalized();
+                let cos_half = m.dot(n0);
+                if cos_half.is_nan() || cos_half <= 1e-6 || 1.0 / cos_half > style.miter_limit {
+                    bevel(self);
+                    return;
+                }
+
+                let c = self.push_stroke_vert(p.pos, Vec2::new(p.dist / total, 0.5), p.color);
+                let a = self.push_stroke_join_vert(p, n0 * hw, d0, total);
+                let tip = self.push_stroke_join_vert(p, m * (hw / cos_half), d0, total);
+                let b = self.push_stroke_join_vert(p, n1 * hw, d0, total);
+                self.push_tri_ccw(c, a, tip);
+                self.push_tri_ccw(c, tip, b);
+            }
+            LineJoin::Round => {
+                let sweep = if cross.abs() <= 1e-6 {
+                    // U-turn: go around the front
+                    if self::cross(n0, d0) > 0.0 {
+                        PI
+                    } else {
+                        -PI
+                    }
+                } else {
+                    self::cross(n0, n1).atan2(n0.dot(n1))
+                };
+                self.stroke_fan(p, n0, sweep, hw, d0, total);
+            }
+        }
+    }
+
+    /// Circular fan from `from` (unit vector) rotating counter-clockwise by `sweep`
+    fn stroke_fan(
+        &mut self,
+        p: StrokePoint,
+        from: Vec2<f32>,
+        sweep: f32,
+        hw: f32,
+        dir: Vec2<f32>,
+        total: f32,
+    ) {
+        let n = shape::arc_segments(hw, sweep, self.tolerance);
+        let c = self.push_stroke_vert(p.pos, Vec2::new(p.dist / total, 0.5), p.color);
+
+        let mut prev = self.push_stroke_join_vert(p, from * hw, dir, total);
+        for i in 1..=n {
+            let angle = sweep * i as f32 / n as f32;
+            let (sin, cos) = angle.sin_cos();
+            let v = Vec2::new(from.x * cos - from.y * sin, from.x * sin + from.y * cos);
+            let next = self.push_stroke_join_vert(p, v * hw, dir, total);
+            self.push_tri_ccw(c, prev, next);
+            prev = next;
+        }
+    }
+
+    /// Vertex at `p + offset` with `v` from the side of `dir`
+    fn push_stroke_join_vert(
+        &mut self,
+        p: StrokePoint,
+        offset: Vec2<f32>,
+        dir: Vec2<f32>,
+        total: f32,
+    ) -> u32 {
+        let hw = offset.magnitude().max(f32::EPSILON);
+        let side = (offset / hw).dot(self::normal(dir)).clamp(-1.0, 1.0);
+        let uv = Vec2::new(p.dist / total, 0.5 - side * 0.5);
+        self.push_stroke_vert(p.pos + offset, uv, p.color)
+    }
+
+    fn push_stroke_vert(&mut self, pos: Vec2<f32>, uv: Vec2<f32>, color: Rgba<f32>) -> u32 {
+        let ix = self.mesh.positions.len() as u32;
+        self.mesh.positions.push(Vec3::from(pos));
+        self.mesh.uvs.push(uv);
+        self.mesh.colors.push(color);
+        ix
+    }
+
+    /// Pushes a triangle reordered to be counter-clockwise. Degenerate triangles are skipped
+    fn push_tri_ccw(&mut self, a: u32, b: u32, c: u32) {
+        let p = |i: u32| self.mesh.positions[i as usize].xy();
+        let area = self::cross(p(b) - p(a), p(c) - p(a));
+        if area > 0.0 {
+            self.push_tri(a, b, c);
+        } else if area < 0.0 {
+            self.push_tri(a, c, b);
+        }
+    }
+}
+
+fn is_same_point(a: Vec2<f32>, b: Vec2<f32>) -> bool {
+    // smaller distances can't be normalized
+    a.distance_squared(b) <= f32::MIN_POSITIVE
+}
+
+enum Dash {
+    Line(Vec<StrokePoint>),
+    /// Zero-length dash with the direction of the path there
+    Dot(StrokePoint, Vec2<f32>),
+}
+
+/// Splits the path into dashes
+fn split_dashes(pts: &[StrokePoint], closed: bool, total: f32, style: &StrokeStyle) -> Vec<Dash> {
+    // odd patterns are repeated to alternate dashes and gaps
+    let mut pattern = style.dash.clone();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let pattern_len = pattern.iter().sum::<f32>();
+
+    // skip `dash_offset` into the pattern
+    let mut ix = 0;
+    let mut remaining = pattern[0];
+    let mut offset = style.dash_offset.rem_euclid(pattern_len);
+    while offset > 0.0 {
+        if offset < remaining {
+            remaining -= offset;
+            break;
+        }
+        offset -= remaining;
+        ix = (ix + 1) % pattern.len();
+        remaining = pattern[ix];
+    }
+
+    let mut dashes = Vec::new();
+    let mut dash = Vec::new();
+    let on = |ix: usize| ix & 1 == 0;
+
+    let n = pts.len();
+    let n_segs = if closed { n } else { n - 1 };
+    for i in 0..n_segs {
+        let a = pts[i];
+        let mut b = pts[(i + 1) % n];
+        if i == n - 1 {
+            // closing segment
+            b.dist = total;
+        }
+
+        let len = b.dist - a.dist;
+        let dir = (b.pos - a.pos) / len;
+        let mut t = 0.0;
+        while t < len {
+            if on(ix) && dash.is_empty() {
+                dash.push(StrokePoint::lerp(a, b, t / len));
+            }
+
+            let step = remaining.min(len - t);
+            t += step;
+            remaining -= step;
+
+            if on(ix) && step > 0.0 {
+                dash.push(StrokePoint::lerp(a, b, t / len));
+            }
+
+            if remaining <= 0.0 {
+                match dash.len() {
+                    _ if !on(ix) => {}
+                    0 => {}
+                    1 => dashes.push(Dash::Dot(dash[0], dir)),
+                    _ => dashes.push(Dash::Line(std::mem::take(&mut dash))),
+                }
+                dash.clear();
+                ix = (ix + 1) % pattern.len();
+                remaining = pattern[ix];
+            }
+        }
+    }
+
+    if dash.len() > 1 {
+        dashes.push(Dash::Line(dash));
+    }
+
+    dashes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(points: &[[f32; 2]], style: &StrokeStyle) -> crate::mesh::MeshData {
+        let points = points.iter().map(|&p| Vec2::from(p)).collect::<Vec<_>>();
+        Tessellator::new().stroke(&points, false, style).build()
+    }
+
+    /// No NaN and no vertex outside of the triangles
+    fn assert_clean(mesh: &crate::mesh::MeshData) {
+        assert!(mesh
+            .positions
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite()));
+        assert!(mesh
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < mesh.positions.len()));
+        for i in 0..mesh.positions.len() as u32 {
+            assert!(mesh.indices.contains(&i), "vertex {} is not used", i);
+        }
+    }
+
+    #[test]
+    fn zero_length_dashes_are_dots() {
+        let line = [[0.0, 0.0], [20.0, 0.0]];
+
+        let style = StrokeStyle::new(2.0).dash(vec![0.0, 2.0], 0.0);
+        let round = self::stroke(&line, &style.clone().cap(LineCap::Round));
+        assert_clean(&round);
+        assert!(!round.indices.is_empty());
+
+        // 10 dots, each a quad
+        let square = self::stroke(&line, &style.clone().cap(LineCap::Square));
+        assert_clean(&square);
+        assert_eq!(square.positions.len(), 10 * 4);
+        assert_eq!(square.indices.len(), 10 * 6);
+        assert!(square.positions.iter().all(|p| p.y.abs() <= 1.0));
+
+        let butt = self::stroke(&line, &style.cap(LineCap::Butt));
+        assert!(butt.positions.is_empty());
+    }
+
+    #[test]
+    fn miter_u_turn_falls_back_to_bevel() {
+        let style = StrokeStyle::new(2.0).join(LineJoin::Miter);
+        let mesh = self::stroke(&[[0.0, 0.0], [10.0, 0.0], [0.0, 0.0]], &style);
+        assert_clean(&mesh);
+
+        // two segment quads; the bevel of a U-turn is a line and is not emitted
+        assert_eq!(mesh.positions.len(), 2 * 4);
+        assert_eq!(mesh.indices.len(), 2 * 6);
+        assert!(mesh.positions.iter().all(|p| p.x <= 10.0));
+    }
+
+    #[test]
+    fn round_u_turn() {
+        let style = StrokeStyle::new(2.0).join(LineJoin::Round);
+        let mesh = self::stroke(&[[0.0, 0.0], [10.0, 0.0], [0.0, 0.0]], &style);
+        assert_clean(&mesh);
+        assert!(mesh.positions.iter().any(|p| p.x > 10.5));
+    }
+}
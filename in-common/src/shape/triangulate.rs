@@ -0,0 +1,707 @@
+//! Polygon triangulation with holes (ear clipping with hole bridging, after `earcut`)
+
+use anyhow::*;
+use vek::Vec2;
+
+/// Vertex in a circular doubly linked list
+#[derive(Debug, Clone)]
+struct Node {
+    /// Output index
+    i: u32,
+    x: f64,
+    y: f64,
+    prev: usize,
+    next: usize,
+}
+
+/// Arena of linked list nodes
+#[derive(Debug, Default)]
+struct Nodes {
+    nodes: Vec<Node>,
+}
+
+impl std::ops::Index<usize> for Nodes {
+    type Output = Node;
+    fn index(&self, ix: usize) -> &Node {
+        &self.nodes[ix]
+    }
+}
+
+/// Triangulates an outline with holes into a triangle list
+///
+/// Indices refer to the contours concatenated in order (`contours[0]` is the outline, the rest
+/// are holes). Any winding is accepted; triangles are counter-clockwise in Y-up coordinates.
+///
+/// Duplicate points, collinear points and contours touching themselves or each other are
+/// handled. Properly crossing edges and non-finite coordinates are reported as errors.
+/// Degenerate contours (less than three distinct points) produce no triangles.
+pub fn triangulate(contours: &[&[Vec2<f32>]]) -> Result<Vec<u32>> {
+    let mut indices = Vec::new();
+    if contours.is_empty() {
+        return Ok(indices);
+    }
+
+    for (c, pts) in contours.iter().enumerate() {
+        if let Some(i) = pts
+            .iter()
+            .position(|p| !(p.x.is_finite() && p.y.is_finite()))
+        {
+            bail!("point {} of contour {} is not finite: {}", i, c, pts[i]);
+        }
+    }
+
+    self::check_intersections(contours)?;
+
+    let mut nodes = Nodes::default();
+    let mut base = 0u32;
+
+    let outer = nodes.link(contours[0], base, true);
+    base += contours[0].len() as u32;
+    let mut outer = match outer {
+        Some(n) => n,
+        None => return Ok(indices),
+    };
+
+    let mut holes = Vec::new();
+    for contour in &contours[1..] {
+        if let Some(hole) = nodes.link(contour, base, false) {
+            holes.push(nodes.leftmost(hole));
+        }
+        base += contour.len() as u32;
+    }
+
+    // bridge holes from the left so that bridges don't cross
+    holes.sort_by(|&a, &b| {
+        let (a, b) = (&nodes[a], &nodes[b]);
+        a.x.total_cmp(&b.x)
+            .then(a.y.total_cmp(&b.y))
+            .then(a.i.cmp(&b.i))
+    });
+
+    for hole in holes {
+        outer = nodes
+            .eliminate_hole(hole, outer)
+            .ok_or_else(|| anyhow!("a hole is outside of the outline"))?;
+    }
+
+    nodes.earcut(outer, &mut indices, 0);
+    Ok(indices)
+}
+
+/// Fails if any two edges cross each other (touching is allowed)
+fn check_intersections(contours: &[&[Vec2<f32>]]) -> Result<()> {
+    let edges = contours
+        .iter()
+        .enumerate()
+        .flat_map(|(c, pts)| {
+            (0..pts.len()).map(move |i| {
+                let a = pts[i].as_::<f64>();
+                let b = pts[(i + 1) % pts.len()].as_::<f64>();
+                (c, i, a, b)
+            })
+        })
+        .filter(|(_, _, a, b)| a != b)
+        .collect::<Vec<_>>();
+
+    for (ix, &(c1, i1, a, b)) in edges.iter().enumerate() {
+        for &(c2, i2, c, d) in &edges[ix + 1..] {
+            let o1 = self::orient(a, b, c);
+            let o2 = self::orient(a, b, d);
+            let o3 = self::orient(c, d, a);
+            let o4 = self::orient(c, d, b);
+
+            if o1 * o2 < 0.0 && o3 * o4 < 0.0 {
+                bail!(
+                    "self-intersection: edge {} of contour {} crosses edge {} of contour {}",
+                    i1,
+                    c1,
+                    i2,
+                    c2
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Twice the signed area of the triangle (positive if counter-clockwise)
+fn orient(a: Vec2<f64>, b: Vec2<f64>, c: Vec2<f64>) -> f64 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn signed_area(pts: &[Vec2<f32>]) -> f64 {
+    (0..pts.len())
+        .map(|i| {
+            let (a, b) = (pts[i].as_::<f64>(), pts[(i + 1) % pts.len()].as_::<f64>());
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f64>()
+}
+
+fn point_in_triangle(a: (f64, f64), b: (f64, f64), c: (f64, f64), p: (f64, f64)) -> bool {
+    (c.0 - p.0) * (a.1 - p.1) >= (a.0 - p.0) * (c.1 - p.1)
+        && (a.0 - p.0) * (b.1 - p.1) >= (b.0 - p.0) * (a.1 - p.1)
+        && (b.0 - p.0) * (c.1 - p.1) >= (c.0 - p.0) * (b.1 - p.1)
+}
+
+fn sign(x: f64) -> i32 {
+    if x > 0.0 {
+        1
+    } else if x < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+impl Nodes {
+    fn xy(&self, n: usize) -> (f64, f64) {
+        (self.nodes[n].x, self.nodes[n].y)
+    }
+
+    fn prev(&self, n: usize) -> usize {
+        self.nodes[n].prev
+    }
+
+    fn next(&self, n: usize) -> usize {
+        self.nodes[n].next
+    }
+
+    /// Negative if `p`, `q`, `r` are counter-clockwise
+    fn area(&self, p: usize, q: usize, r: usize) -> f64 {
+        let (p, q, r) = (&self.nodes[p], &self.nodes[q], &self.nodes[r]);
+        (q.y - p.y) * (r.x - q.x) - (q.x - p.x) * (r.y - q.y)
+    }
+
+    fn equals(&self, a: usize, b: usize) -> bool {
+        self.xy(a) == self.xy(b)
+    }
+
+    fn insert(&mut self, i: u32, x: f64, y: f64, last: Option<usize>) -> usize {
+        let n = self.nodes.len();
+        self.nodes.push(Node {
+            i,
+            x,
+            y,
+            prev: n,
+            next: n,
+        });
+
+        if let Some(last) = last {
+            let next = self.nodes[last].next;
+            self.nodes[n].next = next;
+            self.nodes[n].prev = last;
+            self.nodes[next].prev = n;
+            self.nodes[last].next = n;
+        }
+
+        n
+    }
+
+    fn remove(&mut self, n: usize) {
+        let (prev, next) = (self.nodes[n].prev, self.nodes[n].next);
+        self.nodes[next].prev = prev;
+        self.nodes[prev].next = next;
+    }
+
+    /// Links a contour counter-clockwise (outline) or clockwise (hole). Returns `None` if it's
+    /// degenerate
+    fn link(&mut self, pts: &[Vec2<f32>], base: u32, ccw: bool) -> Option<usize> {
+        let forward = (self::signed_area(pts) > 0.0) == ccw;
+        let order: Box<dyn Iterator<Item = usize>> = if forward {
+            Box::new(0..pts.len())
+        } else {
+            Box::new((0..pts.len()).rev())
+        };
+
+        let mut last = None;
+        for i in order {
+            let p = pts[i].as_::<f64>();
+            last = Some(self.insert(base + i as u32, p.x, p.y, last));
+        }
+
+        let last = last?;
+        let start = self.filter_points(self.next(last), None);
+        if self.next(start) == start || self.next(self.next(start)) == start {
+            None
+        } else {
+            Some(start)
+        }
+    }
+
+    /// Removes duplicate and collinear points
+    fn filter_points(&mut self, start: usize, end: Option<usize>) -> usize {
+        let mut end = end.unwrap_or(start);
+        let mut p = start;
+
+        loop {
+            let mut again = false;
+            let (prev, next) = (self.prev(p), self.next(p));
+
+            if self.equals(p, next) || self.area(prev, p, next) == 0.0 {
+                self.remove(p);
+                p = prev;
+                end = prev;
+                if p == self.next(p) {
+                    break;
+                }
+                again = true;
+            } else {
+                p = next;
+            }
+
+            if !again && p == end {
+                break;
+            }
+        }
+
+        end
+    }
+
+    fn leftmost(&self, start: usize) -> usize {
+        let mut p = start;
+        let mut left = start;
+        loop {
+            let (a, b) = (&self.nodes[p], &self.nodes[left]);
+            if a.x < b.x || (a.x == b.x && a.y < b.y) {
+                left = p;
+            }
+            p = self.next(p);
+            if p == start {
+                break;
+            }
+        }
+        left
+    }
+
+    /// Connects the hole to the outline with a zero-width bridge
+    fn eliminate_hole(&mut self, hole: usize, outer: usize) -> Option<usize> {
+        let bridge = self.find_hole_bridge(hole, outer)?;
+        let bridge_rev = self.split(bridge, hole);
+        self.filter_points(bridge_rev, Some(self.next(bridge_rev)));
+        Some(self.filter_points(bridge, Some(self.next(bridge))))
+    }
+
+    /// Finds an outline vertex visible from the leftmost hole vertex (David Eberly's algorithm)
+    fn find_hole_bridge(&self, hole: usize, outer: usize) -> Option<usize> {
+        let (hx, hy) = self.xy(hole);
+        let mut qx = f64::NEG_INFINITY;
+        let mut m = None;
+
+        if self.equals(hole, outer) {
+            return Some(outer);
+        }
+
+        // the nearest edge to the left of the hole point
+        let mut p = outer;
+        loop {
+            let next = self.next(p);
+            if self.equals(hole, next) {
+                return Some(next);
+            }
+
+            let (px, py) = self.xy(p);
+            let (nx, ny) = self.xy(next);
+            if hy <= py && hy >= ny && ny != py {
+                let x = px + (hy - py) * (nx - px) / (ny - py);
+                if x <= hx && x > qx {
+                    qx = x;
+                    m = Some(if px < nx { p } else { next });
+                    if x == hx {
+                        // the hole touches the outline
+                        return m;
+                    }
+                }
+            }
+
+            p = next;
+            if p == outer {
+                break;
+            }
+        }
+
+        let mut m = m?;
+
+        // look for the vertex with the minimum angle to the ray among the vertices inside the
+        // triangle (hole point, intersection, edge endpoint)
+        let stop = m;
+        let (mx, my) = self.xy(m);
+        let mut tan_min = f64::INFINITY;
+
+        p = m;
+        loop {
+            let (px, py) = self.xy(p);
+            let (a, c) = if hy < my {
+                ((hx, hy), (qx, hy))
+            } else {
+                ((qx, hy), (hx, hy))
+            };
+
+            if hx >= px && px >= mx && hx != px && self::point_in_triangle(a, (mx, my), c, (px, py))
+            {
+                let tan = (hy - py).abs() / (hx - px);
+                let mpx = self.nodes[m].x;
+                if self.locally_inside(p, hole)
+                    && (tan < tan_min
+                        || (tan == tan_min
+                            && (px > mpx || (px == mpx && self.sector_contains_sector(m, p)))))
+                {
+                    m = p;
+                    tan_min = tan;
+                }
+            }
+
+            p = self.next(p);
+            if p == stop {
+                break;
+            }
+        }
+
+        Some(m)
+    }
+
+    fn sector_contains_sector(&self, m: usize, p: usize) -> bool {
+        self.area(self.prev(m), m, self.prev(p)) < 0.0
+            && self.area(self.next(p), m, self.next(m)) < 0.0
+    }
+
+    /// If the diagonal `a`-`b` is inside the polygon around `a`
+    fn locally_inside(&self, a: usize, b: usize) -> bool {
+        let (prev, next) = (self.prev(a), self.next(a));
+        if self.area(prev, a, next) < 0.0 {
+            self.area(a, b, next) >= 0.0 && self.area(a, prev, b) >= 0.0
+        } else {
+            self.area(a, b, prev) < 0.0 || self.area(a, next, b) < 0.0
+        }
+    }
+
+    /// If the middle of the diagonal `a`-`b` is inside the polygon
+    fn middle_inside(&self, a: usize, b: usize) -> bool {
+        let (ax, ay) = self.xy(a);
+        let (bx, by) = self.xy(b);
+        let (px, py) = ((ax + bx) / 2.0, (ay + by) / 2.0);
+
+        let mut inside = false;
+        let mut p = a;
+        loop {
+            let (x0, y0) = self.xy(p);
+            let (x1, y1) = self.xy(self.next(p));
+            if ((y0 > py) != (y1 > py)) && y1 != y0 && px < (x1 - x0) * (py - y0) / (y1 - y0) + x0 {
+                inside = !inside;
+            }
+            p = self.next(p);
+            if p == a {
+                break;
+            }
+        }
+        inside
+    }
+
+    fn on_segment(&self, p: usize, q: usize, r: usize) -> bool {
+        let (px, py) = self.xy(p);
+        let (qx, qy) = self.xy(q);
+        let (rx, ry) = self.xy(r);
+        qx <= px.max(rx) && qx >= px.min(rx) && qy <= py.max(ry) && qy >= py.min(ry)
+    }
+
+    fn intersects(&self, p1: usize, q1: usize, p2: usize, q2: usize) -> bool {
+        let o1 = self::sign(self.area(p1, q1, p2));
+        let o2 = self::sign(self.area(p1, q1, q2));
+        let o3 = self::sign(self.area(p2, q2, p1));
+        let o4 = self::sign(self.area(p2, q2, q1));
+
+        (o1 != o2 && o3 != o4)
+            || (o1 == 0 && self.on_segment(p1, p2, q1))
+            || (o2 == 0 && self.on_segment(p1, q2, q1))
+            || (o3 == 0 && self.on_segment(p2, p1, q2))
+            || (o4 == 0 && self.on_segment(p2, q1, q2))
+    }
+
+    /// If the diagonal `a`-`b` intersects any edge of the polygon
+    fn intersects_polygon(&self, a: usize, b: usize) -> bool {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        let mut p = a;
+        loop {
+            let next = self.next(p);
+            let (pi, ni) = (self.nodes[p].i, self.nodes[next].i);
+            if pi != ai && ni != ai && pi != bi && ni != bi && self.intersects(p, next, a, b) {
+                return true;
+            }
+            p = next;
+            if p == a {
+                return false;
+            }
+        }
+    }
+
+    fn is_valid_diagonal(&self, a: usize, b: usize) -> bool {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        self.nodes[self.next(a)].i != bi
+            && self.nodes[self.prev(a)].i != bi
+            && !self.intersects_polygon(a, b)
+            && ((self.locally_inside(a, b)
+                && self.locally_inside(b, a)
+                && self.middle_inside(a, b)
+                && (self.area(self.prev(a), a, self.prev(b)) != 0.0
+                    || self.area(a, self.prev(b), b) != 0.0))
+                || (self.equals(a, b)
+                    && self.area(self.prev(a), a, self.next(a)) > 0.0
+                    && self.area(self.prev(b), b, self.next(b)) > 0.0))
+            && ai != bi
+    }
+
+    /// Splits the polygon into two with the diagonal `a`-`b`, duplicating the two vertices.
+    /// Returns the duplicate of `b`
+    fn split(&mut self, a: usize, b: usize) -> usize {
+        let (ai, ax, ay) = (self.nodes[a].i, self.nodes[a].x, self.nodes[a].y);
+        let (bi, bx, by) = (self.nodes[b].i, self.nodes[b].x, self.nodes[b].y);
+        let a2 = self.insert(ai, ax, ay, None);
+        let b2 = self.insert(bi, bx, by, None);
+        let an = self.next(a);
+        let bp = self.prev(b);
+
+        self.nodes[a].next = b;
+        self.nodes[b].prev = a;
+
+        self.nodes[a2].next = an;
+        self.nodes[an].prev = a2;
+
+        self.nodes[b2].next = a2;
+        self.nodes[a2].prev = b2;
+
+        self.nodes[bp].next = b2;
+        self.nodes[b2].prev = bp;
+
+        b2
+    }
+
+    fn is_ear(&self, ear: usize) -> bool {
+        let (a, c) = (self.prev(ear), self.next(ear));
+        if self.area(a, ear, c) >= 0.0 {
+            // reflex
+            return false;
+        }
+
+        let (pa, pb, pc) = (self.xy(a), self.xy(ear), self.xy(c));
+        let mut p = self.next(c);
+        while p != a {
+            let pp = self.xy(p);
+            if pp != pa
+                && self::point_in_triangle(pa, pb, pc, pp)
+                && self.area(self.prev(p), p, self.next(p)) >= 0.0
+            {
+                return false;
+            }
+            p = self.next(p);
+        }
+
+        true
+    }
+
+    /// Main ear slicing loop
+    ///
+    /// Passes: 0. plain, 1. after filtering points, 2. after curing local intersections,
+    /// 3. splitting the polygon
+    fn earcut(&mut self, ear: usize, indices: &mut Vec<u32>, pass: u32) {
+        let mut ear = ear;
+        let mut stop = ear;
+
+        while self.prev(ear) != self.next(ear) {
+            let (prev, next) = (self.prev(ear), self.next(ear));
+
+            if self.is_ear(ear) {
+                indices.extend_from_slice(&[
+                    self.nodes[prev].i,
+                    self.nodes[ear].i,
+                    self.nodes[next].i,
+                ]);
+                self.remove(ear);
+
+                ear = self.next(next);
+                stop = ear;
+                continue;
+            }
+
+            ear = next;
+
+            if ear == stop {
+                match pass {
+                    0 => {
+                        let ear = self.filter_points(ear, None);
+                        self.earcut(ear, indices, 1);
+                    }
+                    1 => {
+                        let ear = self.filter_points(ear, None);
+                        let ear = self.cure_local_intersections(ear, indices);
+                        self.earcut(ear, indices, 2);
+                    }
+                    _ => self.split_earcut(ear, indices),
+                }
+                break;
+            }
+        }
+    }
+
+    /// Clips two-vertex self-touching loops
+    fn cure_local_intersections(&mut self, start: usize, indices: &mut Vec<u32>) -> usize {
+        let mut start = start;
+        let mut p = start;
+
+        loop {
+            let a = self.prev(p);
+            let b = self.next(self.next(p));
+
+            if !self.equals(a, b)
+                && self.intersects(a, p, self.next(p), b)
+                && self.locally_inside(a, b)
+                && self.locally_inside(b, a)
+            {
+                indices.extend_from_slice(&[self.nodes[a].i, self.nodes[p].i, self.nodes[b].i]);
+                let next = self.next(p);
+                self.remove(p);
+                self.remove(next);
+                p = b;
+                start = b;
+            }
+
+            p = self.next(p);
+            if p == start {
+                break;
+            }
+        }
+
+        self.filter_points(p, None)
+    }
+
+    /// Splits the polygon with a valid diagonal and triangulates both halves
+    fn split_earcut(&mut self, start: usize, indices: &mut Vec<u32>) {
+        let mut a = start;
+        loop {
+            let mut b = self.next(self.next(a));
+            while b != self.prev(a) {
+                if self.is_valid_diagonal(a, b) {
+                    let c = self.split(a, b);
+                    let a = self.filter_points(a, Some(self.next(a)));
+                    let c = self.filter_points(c, Some(self.next(c)));
+                    self.earcut(a, indices, 0);
+                    self.earcut(c, indices, 0);
+                    return;
+                }
+                b = self.next(b);
+            }
+
+            a = self.next(a);
+            if a == start {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pts(xs: &[[f32; 2]]) -> Vec<Vec2<f32>> {
+        xs.iter().map(|&p| Vec2::from(p)).collect()
+    }
+
+    /// Triangulates and checks the triangles: valid indices, counter-clockwise, non-degenerate
+    /// and covering `area`. Returns the number of triangles
+    fn check(contours: &[&[Vec2<f32>]], area: f64) -> usize {
+        let indices = triangulate(contours).unwrap();
+        assert_eq!(indices.len() % 3, 0);
+
+        let verts = contours.concat();
+        let mut sum = 0.0;
+        for tri in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| verts[tri[i] as usize].as_::<f64>());
+            let a2 = orient(a, b, c);
+            assert!(a2 > 0.0, "bad triangle {:?}: {} {} {}", tri, a, b, c);
+            sum += a2 / 2.0;
+        }
+        assert!((sum - area).abs() < 1e-9, "area {}, expected {}", sum, area);
+
+        indices.len() / 3
+    }
+
+    #[test]
+    fn convex() {
+        let square = pts(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert_eq!(check(&[&square], 1.0), 2);
+
+        // clockwise input gives counter-clockwise triangles, too
+        let mut cw = square.clone();
+        cw.reverse();
+        assert_eq!(check(&[&cw], 1.0), 2);
+    }
+
+    #[test]
+    fn concave() {
+        // L shape
+        let l = pts(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]);
+        assert_eq!(check(&[&l], 3.0), 4);
+
+        // arrow head with a reflex vertex
+        let arrow = pts(&[[0.0, 0.0], [2.0, 1.0], [0.0, 2.0], [0.5, 1.0]]);
+        assert_eq!(check(&[&arrow], 1.5), 2);
+    }
+
+    #[test]
+    fn holes() {
+        let outline = pts(&[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]]);
+        let hole = pts(&[[1.0, 1.0], [1.0, 2.0], [2.0, 2.0], [2.0, 1.0]]);
+        assert_eq!(check(&[&outline, &hole], 15.0), 8);
+
+        let hole2 = pts(&[[3.0, 2.5], [2.5, 3.0], [3.0, 3.5], [3.5, 3.0]]);
+        assert_eq!(check(&[&outline, &hole, &hole2], 14.5), 14);
+
+        // a hole touching the outline at a vertex
+        let touching = pts(&[[0.0, 0.0], [1.0, 2.0], [2.0, 1.0]]);
+        check(&[&outline, &touching], 16.0 - 1.5);
+
+        let outside = pts(&[[5.0, 5.0], [6.0, 5.0], [6.0, 6.0]]);
+        assert!(triangulate(&[&outline, &outside]).is_err());
+    }
+
+    #[test]
+    fn collinear_and_duplicate_points() {
+        let square = pts(&[
+            [0.0, 0.0],
+            [0.5, 0.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 0.5],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.0, 0.5],
+        ]);
+        check(&[&square], 1.0);
+
+        // all points on a line
+        let line = pts(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]);
+        assert_eq!(check(&[&line], 0.0), 0);
+        assert_eq!(check(&[&line[..2]], 0.0), 0);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let bowtie = pts(&[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert!(triangulate(&[&bowtie]).is_err());
+
+        let outline = pts(&[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]]);
+        for bad in [f32::NAN, f32::INFINITY] {
+            let hole = pts(&[[1.0, 1.0], [bad, 2.0], [2.0, 2.0]]);
+            let err = triangulate(&[&outline, &hole]).unwrap_err();
+            assert!(err.to_string().contains("not finite"), "{}", err);
+        }
+    }
+}
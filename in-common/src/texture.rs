@@ -0,0 +1,270 @@
+//! Multi-layer texture data: 2D arrays, 3D volumes and cube maps
+//!
+//! [`TextureLayers`] is a stack of same-sized RGBA8 images. Upload them with
+//! `Texture::array` / `volume` / `cube` (`in-wgpu`) or `TextureBuilder::array` / `volume` /
+//! `cube` (`in-rokol`).
+
+use std::path::Path;
+
+use anyhow::*;
+use image::{imageops, RgbaImage};
+
+/// Cube map faces in the layer order of both backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    pub const ALL: [Self; 6] = [
+        Self::PosX,
+        Self::NegX,
+        Self::PosY,
+        Self::NegY,
+        Self::PosZ,
+        Self::NegZ,
+    ];
+}
+
+/// Same-sized RGBA8 images
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureLayers {
+    size: [u32; 2],
+    layers: Vec<RgbaImage>,
+}
+
+impl TextureLayers {
+    pub fn new(layers: Vec<RgbaImage>) -> Result<Self> {
+        let first = layers.first().context("no texture layer")?;
+        let size = [first.width(), first.height()];
+
+        for (i, layer) in layers.iter().enumerate() {
+            ensure!(
+                [layer.width(), layer.height()] == size,
+                "layer {} is {}x{}, expected {}x{}",
+                i,
+                layer.width(),
+                layer.height(),
+                size[0],
+                size[1]
+            );
+        }
+
+        Ok(Self { size, layers })
+    }
+
+    /// One image file per layer
+    pub fn load<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let layers = paths
+            .into_iter()
+            .map(|p| {
+                let p = p.as_ref();
+                let img = image::open(p).with_context(|| format!("loading {}", p.display()))?;
+                Ok(img.into_rgba8())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(layers)
+    }
+
+    /// Slices a grid of `[columns, rows]` cells into layers in row-major order
+    pub fn from_grid(img: &RgbaImage, grid: [u32; 2]) -> Result<Self> {
+        ensure!(grid[0] > 0 && grid[1] > 0, "empty grid {:?}", grid);
+        let (w, h) = (img.width() / grid[0], img.height() / grid[1]);
+        ensure!(
+            w * grid[0] == img.width() && h * grid[1] == img.height(),
+            "{}x{} image is not divisible into {:?} cells",
+            img.width(),
+            img.height(),
+            grid
+        );
+
+        let layers = (0..grid[1])
+            .flat_map(|row| (0..grid[0]).map(move |col| (col, row)))
+            .map(|(col, row)| imageops::crop_imm(img, col * w, row * h, w, h).to_image())
+            .collect();
+        Self::new(layers)
+    }
+
+    /// Six faces in [`CubeFace::ALL`] order
+    pub fn cube(faces: [RgbaImage; 6]) -> Result<Self> {
+        let cube = Self::new(faces.into())?;
+        ensure!(
+            cube.size[0] == cube.size[1],
+            "cube faces have to be square: {}x{}",
+            cube.size[0],
+            cube.size[1]
+        );
+        Ok(cube)
+    }
+
+    /// Cube faces from a horizontal (4x3) or vertical (3x4) cross
+    ///
+    /// ```text
+    ///    +Y                +Y
+    /// -X +Z +X -Z       -X +Z +X
+    ///    -Y                -Y
+    ///                      -Z (upside down)
+    /// ```
+    pub fn cube_from_cross(img: &RgbaImage) -> Result<Self> {
+        let (w, h) = (img.width(), img.height());
+        let (face, cells, vertical) = if w * 3 == h * 4 {
+            // [+X, -X, +Y, -Y, +Z, -Z]
+            (
+                w / 4,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+                false,
+            )
+        } else if w * 4 == h * 3 {
+            (
+                w / 3,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+                true,
+            )
+        } else {
+            bail!("{}x{} image is not a 4x3 or 3x4 cube cross", w, h);
+        };
+
+        let mut faces = cells.map(|(col, row)| {
+            imageops::crop_imm(img, col * face, row * face, face, face).to_image()
+        });
+        if vertical {
+            imageops::rotate180_in_place(&mut faces[5]);
+        }
+
+        Self::cube(faces)
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn n_layers(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    pub fn layers(&self) -> &[RgbaImage] {
+        &self.layers
+    }
+
+    pub fn layer(&self, i: usize) -> &RgbaImage {
+        &self.layers[i]
+    }
+
+    /// Layers concatenated in order
+    pub fn pixels(&self) -> Vec<u8> {
+        self.layers
+            .iter()
+            .flat_map(|l| l.as_raw().iter().copied())
+            .collect()
+    }
+
+    /// See [`crate::blend::premultiply_alpha`]
+    pub fn premultiply_alpha(&mut self) {
+        for layer in &mut self.layers {
+            crate::blend::premultiply_alpha(layer);
+        }
+    }
+
+    /// See [`crate::blend::premultiply_alpha_srgb`]
+    pub fn premultiply_alpha_srgb(&mut self) {
+        for layer in &mut self.layers {
+            crate::blend::premultiply_alpha_srgb(layer);
+        }
+    }
+
+    /// [OpenGL] flips every layer vertically
+    pub fn flip_vertical(&mut self) {
+        for layer in &mut self.layers {
+            imageops::flip_vertical_in_place(layer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// Each pixel stores its coordinates
+    fn coords(w: u32, h: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, y| Rgba([x as u8, y as u8, 0, 255]))
+    }
+
+    /// Source coordinates of the top-left and top-right pixels of each layer
+    fn corners(layers: &TextureLayers) -> Vec<[[u8; 2]; 2]> {
+        let w = layers.size()[0];
+        layers
+            .layers()
+            .iter()
+            .map(|l| {
+                let px = |x| [l.get_pixel(x, 0)[0], l.get_pixel(x, 0)[1]];
+                [px(0), px(w - 1)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn horizontal_cross() {
+        let cube = TextureLayers::cube_from_cross(&coords(8, 6)).unwrap();
+        assert_eq!(cube.size(), [2, 2]);
+        assert_eq!(
+            corners(&cube),
+            [
+                [[4, 2], [5, 2]],
+                [[0, 2], [1, 2]],
+                [[2, 0], [3, 0]],
+                [[2, 4], [3, 4]],
+                [[2, 2], [3, 2]],
+                [[6, 2], [7, 2]],
+            ]
+        );
+    }
+
+    #[test]
+    fn vertical_cross() {
+        let cube = TextureLayers::cube_from_cross(&coords(6, 8)).unwrap();
+        assert_eq!(cube.n_layers(), 6);
+        // -Z is rotated back
+        assert_eq!(corners(&cube)[4], [[2, 2], [3, 2]]);
+        assert_eq!(corners(&cube)[5], [[3, 7], [2, 7]]);
+    }
+
+    #[test]
+    fn invalid_cross() {
+        for (w, h) in [(8, 8), (8, 5), (12, 6)] {
+            let err = TextureLayers::cube_from_cross(&coords(w, h)).unwrap_err();
+            assert!(err.to_string().contains("cube cross"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn grid() {
+        let layers = TextureLayers::from_grid(&coords(6, 4), [3, 2]).unwrap();
+        assert_eq!((layers.size(), layers.n_layers()), ([2, 2], 6));
+        // row-major
+        assert_eq!(corners(&layers)[1], [[2, 0], [3, 0]]);
+        assert_eq!(corners(&layers)[3], [[0, 2], [1, 2]]);
+        assert_eq!(layers.pixels().len(), 6 * 2 * 2 * 4);
+
+        assert!(TextureLayers::from_grid(&coords(5, 4), [3, 2]).is_err());
+        assert!(TextureLayers::from_grid(&coords(6, 4), [0, 2]).is_err());
+        assert!(TextureLayers::new(vec![coords(2, 2), coords(2, 3)]).is_err());
+        assert!(TextureLayers::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn flip_vertical() {
+        let mut layers = TextureLayers::new(vec![coords(2, 3), coords(2, 3)]).unwrap();
+        layers.flip_vertical();
+        for layer in layers.layers() {
+            assert_eq!(layer.get_pixel(1, 0).0, [1, 2, 0, 255]);
+            assert_eq!(layer.get_pixel(1, 2).0, [1, 0, 0, 255]);
+        }
+    }
+}
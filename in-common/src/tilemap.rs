@@ -0,0 +1,719 @@
+//! Tiled maps (`.tmx` / `.json`) and chunked meshes
+//!
+//! Only orthogonal, finite maps with tile layers are supported. Object and image layers are
+//! skipped, group layers are flattened. Tilesets can be embedded or external (`.tsx` / `.json`)
+//! and have to be single images.
+//!
+//! Each tile layer is split into square chunks of tiles. A [`ChunkMesh`] is a mesh per
+//! (chunk, tileset) in world pixels (Y-down, the map's origin at the top-left). Draw the chunks
+//! that intersect [`Camera2d::view_rect`](crate::camera::Camera2d::view_rect) and rebuild the
+//! visible [`ChunkMesh::animated`] ones every frame.
+
+mod json;
+mod tmx;
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::*;
+use vek::{Rect, Rgba, Vec2, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Tiles are flipped horizontally
+pub const FLIP_H: u32 = 0x8000_0000;
+/// Tiles are flipped vertically
+pub const FLIP_V: u32 = 0x4000_0000;
+/// Tiles are flipped diagonally (transposed). Applied before the other flips
+pub const FLIP_D: u32 = 0x2000_0000;
+/// Hexagonal maps only. Ignored
+const ROTATE_HEX: u32 = 0x1000_0000;
+const FLAGS: u32 = FLIP_H | FLIP_V | FLIP_D | ROTATE_HEX;
+
+/// Global tile ID with flip flags. Zero is an empty cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Tile(pub u32);
+
+impl Tile {
+    pub fn gid(self) -> u32 {
+        self.0 & !FLAGS
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.gid() == 0
+    }
+
+    pub fn flip_h(self) -> bool {
+        self.0 & FLIP_H != 0
+    }
+
+    pub fn flip_v(self) -> bool {
+        self.0 & FLIP_V != 0
+    }
+
+    pub fn flip_d(self) -> bool {
+        self.0 & FLIP_D != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileAnimFrame {
+    /// Local tile ID in the tileset
+    pub tile: u32,
+    pub duration: Duration,
+}
+
+/// Tiles sliced from one image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub name: String,
+    /// Resolved relative to the map (or the external tileset) file
+    pub image: PathBuf,
+    pub image_size: [u32; 2],
+    pub tile_size: [u32; 2],
+    pub tile_count: u32,
+    pub columns: u32,
+    pub margin: u32,
+    pub spacing: u32,
+    /// Drawing offset in pixels
+    pub offset: Vec2<f32>,
+    /// Local tile ID -> frames
+    pub animations: HashMap<u32, Vec<TileAnimFrame>>,
+}
+
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid < self.first_gid + self.tile_count
+    }
+
+    /// Pixel rect of a local tile ID in the image
+    pub fn tile_rect(&self, local: u32) -> Rect<u32, u32> {
+        let columns = self.columns.max(1);
+        let (col, row) = (local % columns, local / columns);
+        Rect::new(
+            self.margin + col * (self.tile_size[0] + self.spacing),
+            self.margin + row * (self.tile_size[1] + self.spacing),
+            self.tile_size[0],
+            self.tile_size[1],
+        )
+    }
+
+    /// Normalized rect of a local tile ID in the image (top-left origin)
+    pub fn uv_rect(&self, local: u32) -> Rect<f32, f32> {
+        let r = self.tile_rect(local);
+        let (w, h) = (self.image_size[0] as f32, self.image_size[1] as f32);
+        Rect::new(
+            r.x as f32 / w,
+            r.y as f32 / h,
+            r.w as f32 / w,
+            r.h as f32 / h,
+        )
+    }
+
+    /// Local tile ID shown at `time` (animations loop from time zero)
+    pub fn animated_tile(&self, local: u32, time: Duration) -> u32 {
+        let frames = match self.animations.get(&local) {
+            Some(frames) if !frames.is_empty() => frames,
+            _ => return local,
+        };
+
+        let total = frames.iter().map(|f| f.duration.as_millis()).sum::<u128>();
+        if total == 0 {
+            return frames[0].tile;
+        }
+
+        let mut t = time.as_millis() % total;
+        for f in frames {
+            let d = f.duration.as_millis();
+            if t < d {
+                return f.tile;
+            }
+            t -= d;
+        }
+        frames[frames.len() - 1].tile
+    }
+}
+
+/// Grid of tiles
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    /// Width and height in tiles
+    pub size: [u32; 2],
+    /// Row-major
+    pub tiles: Vec<Tile>,
+    /// Multiplied with the parent groups' opacity
+    pub opacity: f32,
+    pub visible: bool,
+    /// Drawing offset in pixels, including the parent groups' offsets
+    pub offset: Vec2<f32>,
+}
+
+impl TileLayer {
+    pub fn get(&self, x: u32, y: u32) -> Tile {
+        if x >= self.size[0] || y >= self.size[1] {
+            return Tile::default();
+        }
+        self.tiles[(y * self.size[0] + x) as usize]
+    }
+}
+
+/// Orthogonal Tiled map
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileMap {
+    /// Width and height in tiles
+    pub size: [u32; 2],
+    /// Grid cell size in pixels
+    pub tile_size: [u32; 2],
+    /// Sorted by `first_gid`
+    pub tilesets: Vec<Tileset>,
+    /// Tile layers from bottom to top
+    pub layers: Vec<TileLayer>,
+}
+
+/// Mesh of one tileset in a chunk of a layer
+#[derive(Debug, Clone)]
+pub struct ChunkMesh {
+    /// Index of [`TileMap::layers`]
+    pub layer: usize,
+    /// Index of [`TileMap::tilesets`]
+    pub tileset: usize,
+    /// Tiles covered by the chunk
+    pub tiles: Rect<u32, u32>,
+    /// Bounding box of the quads in pixels
+    pub bounds: Rect<f32, f32>,
+    /// Has animated tiles
+    pub animated: bool,
+    /// Quads with `u16`-addressable vertices. UVs have the top-left origin
+    pub mesh: MeshData,
+}
+
+impl ChunkMesh {
+    pub fn is_visible(&self, view: Rect<f32, f32>) -> bool {
+        self.bounds.collides_with_rect(view)
+    }
+}
+
+impl TileMap {
+    /// Loads `.tmx` or `.json` (`.tmj`) map
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        let map = match ext {
+            "tmx" => tmx::load(path),
+            "json" | "tmj" => json::load(path),
+            _ => Err(anyhow!("unknown map format: `{}`", ext)),
+        }
+        .with_context(|| format!("loading map: {}", path.display()))?;
+
+        map.validate()
+            .with_context(|| format!("loading map: {}", path.display()))?;
+        Ok(map)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for layer in &self.layers {
+            ensure!(
+                layer.tiles.len() == (layer.size[0] * layer.size[1]) as usize,
+                "layer `{}`: {} tiles for {}x{}",
+                layer.name,
+                layer.tiles.len(),
+                layer.size[0],
+                layer.size[1]
+            );
+
+            // they're skipped when building the chunks
+            let unknown = layer
+                .tiles
+                .iter()
+                .filter(|t| !t.is_empty() && self.tileset_of(t.gid()).is_none())
+                .count();
+            if unknown > 0 {
+                log::warn!(
+                    "layer `{}`: {} tiles outside every tileset",
+                    layer.name,
+                    unknown
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the tileset containing the global tile ID. `None` if it's outside every tileset
+    pub fn tileset_of(&self, gid: u32) -> Option<usize> {
+        self.tilesets
+            .iter()
+            .rposition(|ts| gid >= ts.first_gid)
+            .filter(|&i| self.tilesets[i].contains(gid))
+    }
+
+    /// Meshes of a layer, split into `chunk_size` x `chunk_size` tiles
+    ///
+    /// * `time`: for choosing animated tile frames
+    pub fn build_chunks(&self, layer: usize, chunk_size: u32, time: Duration) -> Vec<ChunkMesh> {
+        assert!(
+            chunk_size > 0 && chunk_size * chunk_size * 4 <= u16::MAX as u32 + 1,
+            "chunk size {} is out of `u16` indices",
+            chunk_size
+        );
+
+        let size = self.layers[layer].size;
+        let mut chunks = Vec::new();
+
+        for cy in (0..size[1]).step_by(chunk_size as usize) {
+            for cx in (0..size[0]).step_by(chunk_size as usize) {
+                let tiles = Rect::new(
+                    cx,
+                    cy,
+                    chunk_size.min(size[0] - cx),
+                    chunk_size.min(size[1] - cy),
+                );
+
+                for tileset in 0..self.tilesets.len() {
+                    if let Some(chunk) = self.build_chunk(layer, tileset, tiles, time) {
+                        chunks.push(chunk);
+                    }
+                }
+            }
+        }
+
+        chunks
+    }
+
+    /// Updates the animated tiles of a chunk. The vertex count doesn't change
+    pub fn rebuild_chunk(&self, chunk: &mut ChunkMesh, time: Duration) {
+        if let Some(new) = self.build_chunk(chunk.layer, chunk.tileset, chunk.tiles, time) {
+            *chunk = new;
+        }
+    }
+
+    /// Mesh of the tiles of one tileset in `tiles`. `None` if there's no such tile
+    pub fn build_chunk(
+        &self,
+        layer: usize,
+        tileset: usize,
+        tiles: Rect<u32, u32>,
+        time: Duration,
+    ) -> Option<ChunkMesh> {
+        let l = &self.layers[layer];
+        let ts = &self.tilesets[tileset];
+        let color = Rgba::new(1.0, 1.0, 1.0, l.opacity);
+
+        let mut mesh = MeshData {
+            name: format!("{}[{},{}]", l.name, tiles.x, tiles.y),
+            ..Default::default()
+        };
+        let mut bounds: Option<Rect<f32, f32>> = None;
+        let mut animated = false;
+
+        for y in tiles.y..tiles.y + tiles.h {
+            for x in tiles.x..tiles.x + tiles.w {
+                let tile = l.get(x, y);
+                if tile.is_empty() || self.tileset_of(tile.gid()) != Some(tileset) {
+                    continue;
+                }
+
+                let local = tile.gid() - ts.first_gid;
+                animated |= ts.animations.contains_key(&local);
+                let local = ts.animated_tile(local, time);
+
+                // tiles are aligned to the bottom-left of the cell
+                let (w, h) = (ts.tile_size[0] as f32, ts.tile_size[1] as f32);
+                let pos = Vec2::new(
+                    (x * self.tile_size[0]) as f32,
+                    ((y + 1) * self.tile_size[1]) as f32 - h,
+                ) + l.offset
+                    + ts.offset;
+
+                let quad = Rect::new(pos.x, pos.y, w, h);
+                bounds = Some(bounds.map_or(quad, |b| b.union(quad)));
+
+                self::push_tile(&mut mesh, quad, ts.uv_rect(local), tile, color);
+            }
+        }
+
+        bounds.map(|bounds| ChunkMesh {
+            layer,
+            tileset,
+            tiles,
+            bounds,
+            animated,
+            mesh,
+        })
+    }
+}
+
+/// Pushes a quad with flipped UVs
+fn push_tile(
+    mesh: &mut MeshData,
+    quad: Rect<f32, f32>,
+    uv: Rect<f32, f32>,
+    tile: Tile,
+    color: Rgba<f32>,
+) {
+    let (u0, v0, u1, v1) = (uv.x, uv.y, uv.x + uv.w, uv.y + uv.h);
+    // top-left, top-right, bottom-right, bottom-left
+    let mut uvs = [
+        Vec2::new(u0, v0),
+        Vec2::new(u1, v0),
+        Vec2::new(u1, v1),
+        Vec2::new(u0, v1),
+    ];
+
+    if tile.flip_d() {
+        uvs.swap(1, 3);
+    }
+    if tile.flip_h() {
+        uvs.swap(0, 1);
+        uvs.swap(2, 3);
+    }
+    if tile.flip_v() {
+        uvs.swap(0, 3);
+        uvs.swap(1, 2);
+    }
+
+    let base = mesh.positions.len() as u32;
+    let (x0, y0, x1, y1) = (quad.x, quad.y, quad.x + quad.w, quad.y + quad.h);
+    for (i, &(x, y)) in [(x0, y0), (x1, y0), (x1, y1), (x0, y1)].iter().enumerate() {
+        mesh.positions.push(Vec3::new(x, y, 0.0));
+        mesh.uvs.push(uvs[i]);
+        mesh.colors.push(color);
+    }
+
+    // counter-clockwise on screen (Y-down)
+    mesh.indices
+        .extend_from_slice(&[base, base + 3, base + 2, base, base + 2, base + 1]);
+}
+
+/// Decodes layer data: `csv` or `base64` (optionally `zlib` / `gzip` compressed)
+fn decode_tiles(encoding: &str, compression: &str, data: &str) -> Result<Vec<Tile>> {
+    match encoding {
+        "csv" => data
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u32>()
+                    .map(Tile)
+                    .map_err(|_| anyhow!("bad tile `{}`", s))
+            })
+            .collect(),
+        "base64" => {
+            let bytes = base64::decode(data.trim()).context("bad base64 layer data")?;
+
+            let mut raw = Vec::new();
+            match compression {
+                "" => raw = bytes,
+                "zlib" => {
+                    flate2::read::ZlibDecoder::new(&bytes[..]).read_to_end(&mut raw)?;
+                }
+                "gzip" => {
+                    flate2::read::GzDecoder::new(&bytes[..]).read_to_end(&mut raw)?;
+                }
+                _ => bail!("unsupported compression: `{}`", compression),
+            }
+
+            ensure!(
+                raw.chunks_exact(4).remainder().is_empty(),
+                "layer data of {} bytes is not a list of `u32`",
+                raw.len()
+            );
+            Ok(raw
+                .chunks_exact(4)
+                .map(|b| Tile(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+                .collect())
+        }
+        _ => bail!("unsupported encoding: `{}`", encoding),
+    }
+}
+
+/// Checks a tileset is a single image sliced into tiles
+fn validate_tileset(ts: &Tileset) -> Result<()> {
+    ensure!(
+        !ts.image.as_os_str().is_empty(),
+        "tileset `{}`: image collections are not supported",
+        ts.name
+    );
+    ensure!(
+        ts.tile_size[0] > 0 && ts.tile_size[1] > 0,
+        "tileset `{}`: zero tile size",
+        ts.name
+    );
+    ensure!(
+        ts.image_size[0] > 0 && ts.image_size[1] > 0,
+        "tileset `{}`: image without width or height",
+        ts.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tileset(first_gid: u32, tile_count: u32) -> Tileset {
+        Tileset {
+            first_gid,
+            name: format!("ts{}", first_gid),
+            image: PathBuf::from("tiles.png"),
+            image_size: [64, 64],
+            tile_size: [16, 16],
+            tile_count,
+            columns: 4,
+            margin: 0,
+            spacing: 0,
+            offset: Vec2::zero(),
+            animations: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn gids_outside_tilesets() {
+        let map = TileMap {
+            size: [4, 1],
+            tile_size: [16, 16],
+            tilesets: vec![tileset(1, 4), tileset(10, 4)],
+            layers: vec![TileLayer {
+                name: "ground".to_string(),
+                size: [4, 1],
+                tiles: vec![Tile(1), Tile(5), Tile(13), Tile(14)],
+                opacity: 1.0,
+                visible: true,
+                offset: Vec2::zero(),
+            }],
+        };
+
+        assert_eq!(map.tileset_of(4), Some(0));
+        assert_eq!(map.tileset_of(5), None);
+        assert_eq!(map.tileset_of(10), Some(1));
+        assert_eq!(map.tileset_of(14), None);
+        map.validate().unwrap();
+
+        // the unknown tiles are skipped
+        let quads = |ts| {
+            let chunk = map.build_chunk(0, ts, Rect::new(0, 0, 4, 1), Duration::ZERO);
+            chunk.map_or(0, |c| c.mesh.positions.len() / 4)
+        };
+        assert_eq!(quads(0), 1);
+        assert_eq!(quads(1), 1);
+    }
+
+    #[test]
+    fn tileset_image_size_is_required() {
+        let mut ts = tileset(1, 4);
+        validate_tileset(&ts).unwrap();
+        ts.image_size = [64, 0];
+        assert!(validate_tileset(&ts).is_err());
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/tilemap")
+            .join(name)
+    }
+
+    /// `map.tmx` and `map.json` have the same content
+    fn check_fixture(map: &TileMap, hidden_layer: &str) {
+        assert_eq!(map.size, [3, 2]);
+        assert_eq!(map.tile_size, [16, 16]);
+
+        let ts = &map.tilesets[0];
+        assert_eq!(map.tilesets.len(), 1);
+        assert_eq!(ts.first_gid, 1);
+        assert_eq!(ts.name, "tiles");
+        assert_eq!(ts.image, fixture("tiles.png"));
+        assert_eq!(ts.image_size, [32, 32]);
+        assert_eq!(ts.tile_size, [16, 16]);
+        assert_eq!((ts.tile_count, ts.columns), (4, 2));
+        assert_eq!(ts.offset, Vec2::new(0.0, 2.0));
+        assert_eq!(
+            ts.animations[&0],
+            vec![
+                TileAnimFrame {
+                    tile: 0,
+                    duration: Duration::from_millis(100),
+                },
+                TileAnimFrame {
+                    tile: 1,
+                    duration: Duration::from_millis(200),
+                },
+            ]
+        );
+
+        // object layers are skipped and groups are flattened
+        let names = map
+            .layers
+            .iter()
+            .map(|l| l.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["csv", "base64", hidden_layer]);
+
+        let tiles = [1, FLIP_H | 2, 0, 4, 3, 2].map(Tile);
+        for layer in &map.layers {
+            assert_eq!(layer.size, [3, 2]);
+            assert_eq!(layer.tiles, tiles, "layer `{}`", layer.name);
+        }
+        assert_eq!(map.layers[0].get(1, 0).gid(), 2);
+        assert!(map.layers[0].get(1, 0).flip_h());
+        assert!(map.layers[0].get(3, 0).is_empty());
+
+        // opacity, visibility and offset are inherited from groups
+        let [csv, base64, hidden] = [&map.layers[0], &map.layers[1], &map.layers[2]];
+        assert_eq!(
+            (csv.opacity, csv.visible, csv.offset),
+            (1.0, true, Vec2::zero())
+        );
+        assert_eq!(
+            (base64.opacity, base64.visible, base64.offset),
+            (0.25, true, Vec2::new(8.0, 8.0))
+        );
+        assert_eq!(
+            (hidden.opacity, hidden.visible, hidden.offset),
+            (0.5, false, Vec2::new(8.0, 4.0))
+        );
+    }
+
+    #[test]
+    fn load_tmx() {
+        let map = TileMap::load(fixture("map.tmx")).unwrap();
+        self::check_fixture(&map, "zlib");
+    }
+
+    #[test]
+    fn load_json() {
+        let map = TileMap::load(fixture("map.json")).unwrap();
+        self::check_fixture(&map, "gzip");
+    }
+
+    #[test]
+    fn layer_data() {
+        // 1, 2, 0 as little-endian `u32`s
+        let raw = [1u8, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0];
+        let tiles = vec![Tile(1), Tile(2), Tile(0)];
+
+        assert_eq!(decode_tiles("csv", "", "\n1,2,\n0\n").unwrap(), tiles);
+        assert_eq!(
+            decode_tiles("base64", "", &base64::encode(raw)).unwrap(),
+            tiles
+        );
+
+        let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut z, &raw).unwrap();
+        let z = base64::encode(z.finish().unwrap());
+        assert_eq!(decode_tiles("base64", "zlib", &z).unwrap(), tiles);
+
+        assert!(decode_tiles("csv", "", "1,x").is_err());
+        assert!(decode_tiles("base64", "", &base64::encode(&raw[..5])).is_err());
+        assert!(decode_tiles("base64", "zstd", &z).is_err());
+        assert!(decode_tiles("xml", "", "").is_err());
+    }
+
+    #[test]
+    fn flip_flags() {
+        let uvs = |flags: u32| {
+            let mut mesh = MeshData::default();
+            let (quad, uv) = (Rect::new(0.0, 0.0, 1.0, 1.0), Rect::new(0.0, 0.0, 1.0, 1.0));
+            push_tile(&mut mesh, quad, uv, Tile(1 | flags), Rgba::white());
+            mesh.uvs
+                .iter()
+                .map(|uv| (uv.x as u32, uv.y as u32))
+                .collect::<Vec<_>>()
+        };
+
+        // top-left, top-right, bottom-right, bottom-left
+        assert_eq!(uvs(0), [(0, 0), (1, 0), (1, 1), (0, 1)]);
+        assert_eq!(uvs(FLIP_H), [(1, 0), (0, 0), (0, 1), (1, 1)]);
+        assert_eq!(uvs(FLIP_V), [(0, 1), (1, 1), (1, 0), (0, 0)]);
+        assert_eq!(uvs(FLIP_H | FLIP_V), [(1, 1), (0, 1), (0, 0), (1, 0)]);
+        assert_eq!(uvs(FLIP_D), [(0, 0), (0, 1), (1, 1), (1, 0)]);
+        // transposed, then flipped: rotated by 90 degrees clockwise
+        assert_eq!(uvs(FLIP_D | FLIP_H), [(0, 1), (0, 0), (1, 0), (1, 1)]);
+        // counterclockwise
+        assert_eq!(uvs(FLIP_D | FLIP_V), [(1, 0), (1, 1), (0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn animated_tile() {
+        let mut ts = tileset(1, 4);
+        ts.animations.insert(
+            0,
+            vec![
+                TileAnimFrame {
+                    tile: 0,
+                    duration: Duration::from_millis(100),
+                },
+                TileAnimFrame {
+                    tile: 1,
+                    duration: Duration::from_millis(200),
+                },
+            ],
+        );
+
+        let at = |ms| ts.animated_tile(0, Duration::from_millis(ms));
+        assert_eq!([at(0), at(99), at(100), at(299)], [0, 0, 1, 1]);
+        // loops
+        assert_eq!([at(300), at(399), at(400), at(3_150)], [0, 0, 1, 1]);
+        // not animated
+        assert_eq!(ts.animated_tile(2, Duration::from_millis(100)), 2);
+
+        ts.animations.insert(
+            3,
+            vec![TileAnimFrame {
+                tile: 2,
+                duration: Duration::ZERO,
+            }],
+        );
+        assert_eq!(ts.animated_tile(3, Duration::from_millis(100)), 2);
+    }
+
+    #[test]
+    fn chunks_at_map_edges() {
+        let map = TileMap {
+            size: [5, 3],
+            tile_size: [16, 16],
+            tilesets: vec![tileset(1, 4)],
+            layers: vec![TileLayer {
+                name: "ground".to_string(),
+                size: [5, 3],
+                tiles: vec![Tile(1); 15],
+                opacity: 1.0,
+                visible: true,
+                offset: Vec2::zero(),
+            }],
+        };
+
+        let chunks = map.build_chunks(0, 2, Duration::ZERO);
+        let rects = chunks
+            .iter()
+            .map(|c| (c.tiles.x, c.tiles.y, c.tiles.w, c.tiles.h))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rects,
+            [
+                (0, 0, 2, 2),
+                (2, 0, 2, 2),
+                (4, 0, 1, 2),
+                (0, 2, 2, 1),
+                (2, 2, 2, 1),
+                (4, 2, 1, 1),
+            ]
+        );
+
+        let quads = chunks
+            .iter()
+            .map(|c| c.mesh.positions.len() / 4)
+            .collect::<Vec<_>>();
+        assert_eq!(quads, [4, 4, 2, 2, 2, 1]);
+
+        let last = chunks.last().unwrap();
+        assert_eq!(last.bounds, Rect::new(64.0, 32.0, 16.0, 16.0));
+        assert_eq!(last.mesh.indices.len(), 6);
+        assert!(!last.animated);
+    }
+}
@@ -0,0 +1,261 @@
+//! `.json` / `.tmj` maps and tilesets
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::*;
+use serde::Deserialize;
+use vek::Vec2;
+
+use crate::tilemap::{Tile, TileAnimFrame, TileLayer, TileMap, Tileset};
+
+#[derive(Debug, Deserialize)]
+struct Map {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    orientation: String,
+    /// Embedded tilesets or `{ firstgid, source }`
+    tilesets: Vec<serde_json::Value>,
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TilesetData {
+    #[serde(default)]
+    name: String,
+    tilewidth: u32,
+    tileheight: u32,
+    tilecount: u32,
+    columns: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    imagewidth: u32,
+    #[serde(default)]
+    imageheight: u32,
+    tileoffset: Option<Offset>,
+    #[serde(default)]
+    tiles: Vec<TileData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Offset {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct TileData {
+    id: u32,
+    #[serde(default)]
+    animation: Vec<Frame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Frame {
+    tileid: u32,
+    duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Layer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    /// Array of GIDs or base64 string
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    encoding: String,
+    #[serde(default)]
+    compression: String,
+    #[serde(default = "self::one")]
+    opacity: f32,
+    #[serde(default = "self::yes")]
+    visible: bool,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    /// Group layers
+    #[serde(default)]
+    layers: Vec<Layer>,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn yes() -> bool {
+    true
+}
+
+pub fn load(path: &Path) -> Result<TileMap> {
+    let src = std::fs::read_to_string(path)?;
+    let map: Map = serde_json::from_str(&src)?;
+
+    ensure!(
+        map.orientation.is_empty() || map.orientation == "orthogonal",
+        "unsupported orientation: `{}`",
+        map.orientation
+    );
+    ensure!(!map.infinite, "infinite maps are not supported");
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut tilesets = Vec::new();
+    for value in map.tilesets {
+        let first_gid = value
+            .get("firstgid")
+            .and_then(|x| x.as_u64())
+            .context("tileset without `firstgid`")? as u32;
+
+        let ts = match value.get("source").and_then(|x| x.as_str()) {
+            Some(source) => {
+                let path = dir.join(source);
+                ensure!(
+                    path.extension().and_then(|e| e.to_str()) != Some("tsx"),
+                    "`.tsx` tileset in JSON map: {}",
+                    source
+                );
+                self::load_tileset(&path, first_gid)?
+            }
+            None => self::tileset(TilesetData::deserialize(value)?, dir, first_gid)?,
+        };
+        tilesets.push(ts);
+    }
+    tilesets.sort_by_key(|ts| ts.first_gid);
+
+    let mut layers = Vec::new();
+    self::layers(&map.layers, 1.0, true, Vec2::zero(), &mut layers)?;
+
+    Ok(TileMap {
+        size: [map.width, map.height],
+        tile_size: [map.tilewidth, map.tileheight],
+        tilesets,
+        layers,
+    })
+}
+
+fn load_tileset(path: &Path, first_gid: u32) -> Result<Tileset> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("loading tileset: {}", path.display()))?;
+    let data: TilesetData = serde_json::from_str(&src)
+        .with_context(|| format!("loading tileset: {}", path.display()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    self::tileset(data, dir, first_gid)
+}
+
+fn tileset(data: TilesetData, dir: &Path, first_gid: u32) -> Result<Tileset> {
+    let animations = data
+        .tiles
+        .into_iter()
+        .filter(|t| !t.animation.is_empty())
+        .map(|t| {
+            let frames = t
+                .animation
+                .iter()
+                .map(|f| TileAnimFrame {
+                    tile: f.tileid,
+                    duration: Duration::from_millis(f.duration),
+                })
+                .collect();
+            (t.id, frames)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let ts = Tileset {
+        first_gid,
+        name: data.name,
+        image: if data.image.is_empty() {
+            Default::default()
+        } else {
+            dir.join(data.image)
+        },
+        image_size: [data.imagewidth, data.imageheight],
+        tile_size: [data.tilewidth, data.tileheight],
+        tile_count: data.tilecount,
+        columns: data.columns,
+        margin: data.margin,
+        spacing: data.spacing,
+        offset: data
+            .tileoffset
+            .map_or(Vec2::zero(), |o| Vec2::new(o.x, o.y)),
+        animations,
+    };
+    super::validate_tileset(&ts)?;
+    Ok(ts)
+}
+
+/// Collects tile layers, flattening groups
+fn layers(
+    src: &[Layer],
+    opacity: f32,
+    visible: bool,
+    offset: Vec2<f32>,
+    out: &mut Vec<TileLayer>,
+) -> Result<()> {
+    for layer in src {
+        let opacity = opacity * layer.opacity;
+        let visible = visible && layer.visible;
+        let offset = offset + Vec2::new(layer.offsetx, layer.offsety);
+
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let tiles =
+                    self::layer_data(layer).with_context(|| format!("layer `{}`", layer.name))?;
+                out.push(TileLayer {
+                    name: layer.name.clone(),
+                    size: [layer.width, layer.height],
+                    tiles,
+                    opacity,
+                    visible,
+                    offset,
+                });
+            }
+            "group" => {
+                self::layers(&layer.layers, opacity, visible, offset, out)
+                    .with_context(|| format!("group `{}`", layer.name))?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn layer_data(layer: &Layer) -> Result<Vec<Tile>> {
+    match &layer.data {
+        Some(serde_json::Value::Array(xs)) => xs
+            .iter()
+            .map(|x| {
+                x.as_u64()
+                    .and_then(|x| u32::try_from(x).ok())
+                    .map(Tile)
+                    .with_context(|| format!("bad tile `{}`", x))
+            })
+            .collect(),
+        Some(serde_json::Value::String(s)) => {
+            let encoding = if layer.encoding.is_empty() {
+                "base64"
+            } else {
+                &layer.encoding
+            };
+            super::decode_tiles(encoding, &layer.compression, s)
+        }
+        Some(_) => bail!("bad `data`"),
+        None => bail!("no `data` (infinite maps are not supported)"),
+    }
+}
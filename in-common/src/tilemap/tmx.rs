@@ -0,0 +1,200 @@
+//! `.tmx` / `.tsx` (XML)
+
+use std::{collections::HashMap, path::Path, str::FromStr, time::Duration};
+
+use anyhow::*;
+use roxmltree::{Document, Node};
+use vek::Vec2;
+
+use crate::tilemap::{TileAnimFrame, TileLayer, TileMap, Tileset};
+
+pub fn load(path: &Path) -> Result<TileMap> {
+    let src = std::fs::read_to_string(path)?;
+    let doc = Document::parse(&src)?;
+    let map = doc.root_element();
+    ensure!(map.has_tag_name("map"), "root element is not `map`");
+
+    let orientation = map.attribute("orientation").unwrap_or("orthogonal");
+    ensure!(
+        orientation == "orthogonal",
+        "unsupported orientation: `{}`",
+        orientation
+    );
+    ensure!(
+        self::attr_or(map, "infinite", 0u32)? == 0,
+        "infinite maps are not supported"
+    );
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut tilesets = Vec::new();
+    for node in map.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = self::attr(node, "firstgid")?;
+        let ts = match node.attribute("source") {
+            Some(source) => self::load_tsx(&dir.join(source), first_gid)?,
+            None => self::tileset(node, dir, first_gid)?,
+        };
+        tilesets.push(ts);
+    }
+    tilesets.sort_by_key(|ts| ts.first_gid);
+
+    let mut layers = Vec::new();
+    self::layers(map, 1.0, true, Vec2::zero(), &mut layers)?;
+
+    Ok(TileMap {
+        size: [self::attr(map, "width")?, self::attr(map, "height")?],
+        tile_size: [
+            self::attr(map, "tilewidth")?,
+            self::attr(map, "tileheight")?,
+        ],
+        tilesets,
+        layers,
+    })
+}
+
+fn load_tsx(path: &Path, first_gid: u32) -> Result<Tileset> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("loading tileset: {}", path.display()))?;
+    let doc = Document::parse(&src)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    self::tileset(doc.root_element(), dir, first_gid)
+        .with_context(|| format!("loading tileset: {}", path.display()))
+}
+
+fn tileset(node: Node, dir: &Path, first_gid: u32) -> Result<Tileset> {
+    let name = node.attribute("name").unwrap_or("").to_string();
+
+    let image = node.children().find(|n| n.has_tag_name("image"));
+    let (image, image_size) = match image {
+        Some(img) => (
+            dir.join(self::attr::<String>(img, "source")?),
+            [
+                self::attr_or(img, "width", 0)?,
+                self::attr_or(img, "height", 0)?,
+            ],
+        ),
+        None => (Default::default(), [0, 0]),
+    };
+
+    let offset = match node.children().find(|n| n.has_tag_name("tileoffset")) {
+        Some(o) => Vec2::new(self::attr_or(o, "x", 0.0)?, self::attr_or(o, "y", 0.0)?),
+        None => Vec2::zero(),
+    };
+
+    let mut animations = HashMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let anim = match tile.children().find(|n| n.has_tag_name("animation")) {
+            Some(anim) => anim,
+            None => continue,
+        };
+
+        let frames = anim
+            .children()
+            .filter(|n| n.has_tag_name("frame"))
+            .map(|f| {
+                Ok(TileAnimFrame {
+                    tile: self::attr(f, "tileid")?,
+                    duration: Duration::from_millis(self::attr(f, "duration")?),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        animations.insert(self::attr(tile, "id")?, frames);
+    }
+
+    let ts = Tileset {
+        first_gid,
+        name,
+        image,
+        image_size,
+        tile_size: [
+            self::attr(node, "tilewidth")?,
+            self::attr(node, "tileheight")?,
+        ],
+        tile_count: self::attr(node, "tilecount")?,
+        columns: self::attr(node, "columns")?,
+        margin: self::attr_or(node, "margin", 0)?,
+        spacing: self::attr_or(node, "spacing", 0)?,
+        offset,
+        animations,
+    };
+    super::validate_tileset(&ts)?;
+    Ok(ts)
+}
+
+/// Collects tile layers, flattening groups
+fn layers(
+    parent: Node,
+    opacity: f32,
+    visible: bool,
+    offset: Vec2<f32>,
+    out: &mut Vec<TileLayer>,
+) -> Result<()> {
+    for node in parent.children().filter(|n| n.is_element()) {
+        let name = node.attribute("name").unwrap_or("");
+        let opacity = opacity * self::attr_or(node, "opacity", 1.0f32)?;
+        let visible = visible && self::attr_or(node, "visible", 1u32)? != 0;
+        let offset = offset
+            + Vec2::new(
+                self::attr_or(node, "offsetx", 0.0)?,
+                self::attr_or(node, "offsety", 0.0)?,
+            );
+
+        match node.tag_name().name() {
+            "layer" => {
+                let tiles = self::layer_data(node).with_context(|| format!("layer `{}`", name))?;
+                out.push(TileLayer {
+                    name: name.to_string(),
+                    size: [self::attr(node, "width")?, self::attr(node, "height")?],
+                    tiles,
+                    opacity,
+                    visible,
+                    offset,
+                });
+            }
+            "group" => {
+                self::layers(node, opacity, visible, offset, out)
+                    .with_context(|| format!("group `{}`", name))?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn layer_data(layer: Node) -> Result<Vec<super::Tile>> {
+    let data = layer
+        .children()
+        .find(|n| n.has_tag_name("data"))
+        .context("no `data`")?;
+
+    match data.attribute("encoding") {
+        Some(encoding) => super::decode_tiles(
+            encoding,
+            data.attribute("compression").unwrap_or(""),
+            data.text().unwrap_or(""),
+        ),
+        // XML `<tile gid=".."/>` elements
+        None => data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|t| Ok(super::Tile(self::attr_or(t, "gid", 0)?)))
+            .collect(),
+    }
+}
+
+fn attr<T: FromStr>(node: Node, name: &str) -> Result<T> {
+    let s = node
+        .attribute(name)
+        .with_context(|| format!("`{}` has no `{}`", node.tag_name().name(), name))?;
+    s.parse()
+        .map_err(|_| anyhow!("`{}`: bad `{}`: `{}`", node.tag_name().name(), name, s))
+}
+
+fn attr_or<T: FromStr>(node: Node, name: &str, default: T) -> Result<T> {
+    if node.has_attribute(name) {
+        self::attr(node, name)
+    } else {
+        Ok(default)
+    }
+}
@@ -0,0 +1,390 @@
+//! Uniform block layout validation (GLSL std140 / WGSL uniform address space)
+//!
+//! Rust structs don't pad like GPU uniform blocks: a `vec3` is aligned to 16 bytes, and so is
+//! every array element. [`UniformBlock`] lists the fields of a `#[repr(C)]` struct so that
+//! [`UniformLayout::of`] can reject structs whose bytes would be read as garbage:
+//!
+//! ```ignore
+//! #[derive(Clone, Copy)]
+//! #[repr(C)]
+//! struct Light {
+//!     pos: [f32; 3],
+//!     radius: f32,
+//!     colors: [[f32; 4]; 2],
+//! }
+//!
+//! in_common::impl_uniform_block!(Light {
+//!     pos: Vec3,
+//!     radius: Float,
+//!     colors: [Vec4; 2],
+//! });
+//! ```
+
+use anyhow::*;
+
+/// Field type in a uniform block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    /// Column-major
+    Mat4,
+}
+
+impl UniformType {
+    /// Size in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Self::Float | Self::Int | Self::UInt => 4,
+            Self::Vec2 | Self::IVec2 | Self::UVec2 => 8,
+            Self::Vec3 | Self::IVec3 | Self::UVec3 => 12,
+            Self::Vec4 | Self::IVec4 | Self::UVec4 => 16,
+            Self::Mat4 => 64,
+        }
+    }
+
+    /// Alignment in bytes
+    pub fn align(self) -> usize {
+        match self {
+            Self::Float | Self::Int | Self::UInt => 4,
+            Self::Vec2 | Self::IVec2 | Self::UVec2 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Byte size of `count` values in a uniform block. Array elements have a stride of 16 bytes
+    pub fn array_size(self, count: usize) -> usize {
+        if count == 1 {
+            self.size()
+        } else {
+            self::round_up(self.size(), 16) * count
+        }
+    }
+
+    /// Byte alignment of `count` values in a uniform block
+    pub fn array_align(self, count: usize) -> usize {
+        if count == 1 {
+            self.align()
+        } else {
+            16
+        }
+    }
+
+    /// Parses a GLSL type name, e.g. `vec4`
+    pub fn from_glsl(name: &str) -> Option<Self> {
+        Some(match name {
+            "float" => Self::Float,
+            "vec2" => Self::Vec2,
+            "vec3" => Self::Vec3,
+            "vec4" => Self::Vec4,
+            "int" => Self::Int,
+            "ivec2" => Self::IVec2,
+            "ivec3" => Self::IVec3,
+            "ivec4" => Self::IVec4,
+            "uint" => Self::UInt,
+            "uvec2" => Self::UVec2,
+            "uvec3" => Self::UVec3,
+            "uvec4" => Self::UVec4,
+            "mat4" => Self::Mat4,
+            _ => return None,
+        })
+    }
+}
+
+/// Field of a [`UniformBlock`] (see [`impl_uniform_block!`](crate::impl_uniform_block))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniformField {
+    pub name: &'static str,
+    pub ty: UniformType,
+    /// Array length. `1` for a single value
+    pub count: usize,
+    /// Byte offset in the Rust struct
+    pub offset: usize,
+    /// Byte size in the Rust struct
+    pub size: usize,
+}
+
+impl UniformField {
+    /// Byte size in the uniform block. Array elements have a stride of 16 bytes
+    pub fn block_size(&self) -> usize {
+        self.ty.array_size(self.count)
+    }
+
+    /// Byte alignment in the uniform block
+    pub fn block_align(&self) -> usize {
+        self.ty.array_align(self.count)
+    }
+}
+
+/// `#[repr(C)]` struct uploaded as a uniform block
+///
+/// Implement it with [`impl_uniform_block!`](crate::impl_uniform_block).
+pub trait UniformBlock: Copy + 'static {
+    /// Fields in declaration order
+    fn fields() -> Vec<UniformField>;
+}
+
+/// Validated layout of a [`UniformBlock`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformLayout {
+    size: usize,
+    fields: Vec<UniformField>,
+}
+
+impl UniformLayout {
+    /// Checks the Rust layout of `T` against the std140 rules
+    ///
+    /// Fields have to be aligned and sized as in the uniform block, must not overlap, and the
+    /// struct size has to be a multiple of 16 bytes. Padding between fields is fine.
+    pub fn of<T: UniformBlock>() -> Result<Self> {
+        let size = std::mem::size_of::<T>();
+        let name = std::any::type_name::<T>();
+        let fields = T::fields();
+        ensure!(!fields.is_empty(), "uniform block `{}` has no field", name);
+
+        let mut end = 0;
+        for f in &fields {
+            ensure!(f.count > 0, "`{}::{}` is an empty array", name, f.name);
+
+            let align = f.block_align();
+            ensure!(
+                f.offset >= end && f.offset / align * align == f.offset,
+                "`{}::{}` ({:?}) is at offset {}, expected a multiple of {} after {}",
+                name,
+                f.name,
+                f.ty,
+                f.offset,
+                align,
+                end
+            );
+            ensure!(
+                f.size == f.block_size(),
+                "`{}::{}` ({:?} x {}) is {} bytes in Rust, {} bytes in the uniform block",
+                name,
+                f.name,
+                f.ty,
+                f.count,
+                f.size,
+                f.block_size()
+            );
+            end = f.offset + f.size;
+        }
+
+        ensure!(
+            size == self::round_up(end, 16),
+            "uniform block `{}` is {} bytes, expected {} (pad it to a multiple of 16 bytes)",
+            name,
+            size,
+            self::round_up(end, 16)
+        );
+
+        Ok(Self { size, fields })
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn fields(&self) -> &[UniformField] {
+        &self.fields
+    }
+
+    /// Returns an error if the shader expects another size
+    pub fn check_size(&self, expected: usize) -> Result<()> {
+        ensure!(
+            self.size == expected,
+            "uniform block is {} bytes, but the shader expects {} bytes",
+            self.size,
+            expected
+        );
+        Ok(())
+    }
+}
+
+pub(crate) fn round_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+
+/// Size of a struct field (used by [`impl_uniform_block!`](crate::impl_uniform_block))
+#[doc(hidden)]
+pub fn field_size<T, F>(_field: fn(&T) -> &F) -> usize {
+    std::mem::size_of::<F>()
+}
+
+/// Implements [`UniformBlock`] for a `#[repr(C)]` struct
+///
+/// Fields are `name: Type` or `name: [Type; N]` for arrays, where `Type` is a [`UniformType`]
+/// variant. Padding fields are left out.
+#[macro_export]
+macro_rules! impl_uniform_block {
+    ($ty:ty { $($field:ident: $kind:tt),* $(,)? }) => {
+        impl $crate::uniform::UniformBlock for $ty {
+            fn fields() -> Vec<$crate::uniform::UniformField> {
+                vec![$($crate::__uniform_field!($ty, $field, $kind)),*]
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __uniform_field {
+    ($ty:ty, $field:ident, [$kind:ident; $n:expr]) => {
+        $crate::uniform::UniformField {
+            name: stringify!($field),
+            ty: $crate::uniform::UniformType::$kind,
+            count: $n,
+            offset: std::mem::offset_of!($ty, $field),
+            size: $crate::uniform::field_size(|x: &$ty| &x.$field),
+        }
+    };
+    ($ty:ty, $field:ident, $kind:ident) => {
+        $crate::uniform::UniformField {
+            name: stringify!($field),
+            ty: $crate::uniform::UniformType::$kind,
+            count: 1,
+            offset: std::mem::offset_of!($ty, $field),
+            size: $crate::uniform::field_size(|x: &$ty| &x.$field),
+        }
+    };
+}
+
+impl UniformBlock for vek::Mat4<f32> {
+    fn fields() -> Vec<UniformField> {
+        vec![UniformField {
+            name: "mat",
+            ty: UniformType::Mat4,
+            count: 1,
+            offset: 0,
+            size: 64,
+        }]
+    }
+}
+
+impl UniformBlock for [f32; 16] {
+    fn fields() -> Vec<UniformField> {
+        vek::Mat4::<f32>::fields()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    struct Light {
+        pos: [f32; 3],
+        radius: f32,
+        colors: [[f32; 4]; 2],
+    }
+
+    crate::impl_uniform_block!(Light {
+        pos: Vec3,
+        radius: Float,
+        colors: [Vec4; 2],
+    });
+
+    #[test]
+    fn macro_fields() {
+        let field = |name, ty, count, offset, size| UniformField {
+            name,
+            ty,
+            count,
+            offset,
+            size,
+        };
+        assert_eq!(
+            Light::fields(),
+            [
+                field("pos", UniformType::Vec3, 1, 0, 12),
+                field("radius", UniformType::Float, 1, 12, 4),
+                field("colors", UniformType::Vec4, 2, 16, 32),
+            ]
+        );
+    }
+
+    #[test]
+    fn vec3_then_float() {
+        // the float is packed into the last 4 bytes of the `vec3`
+        let layout = UniformLayout::of::<Light>().unwrap();
+        assert_eq!(layout.size(), 48);
+        assert_eq!(layout.fields().len(), 3);
+        layout.check_size(48).unwrap();
+        assert!(layout.check_size(64).is_err());
+
+        assert_eq!(UniformLayout::of::<[f32; 16]>().unwrap().size(), 64);
+    }
+
+    #[test]
+    fn misaligned_vec4() {
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Params {
+            scale: f32,
+            color: [f32; 4],
+            _pad: [f32; 3],
+        }
+        crate::impl_uniform_block!(Params {
+            scale: Float,
+            color: Vec4,
+        });
+
+        let err = UniformLayout::of::<Params>().unwrap_err().to_string();
+        assert!(err.contains("::color` (Vec4) is at offset 4"), "{}", err);
+    }
+
+    #[test]
+    fn array_stride() {
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Tight {
+            weights: [f32; 4],
+        }
+        crate::impl_uniform_block!(Tight {
+            weights: [Float; 4]
+        });
+
+        // each element takes 16 bytes in the uniform block
+        let err = UniformLayout::of::<Tight>().unwrap_err().to_string();
+        assert!(err.contains("16 bytes in Rust, 64 bytes"), "{}", err);
+
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Padded {
+            weights: [[f32; 4]; 4],
+        }
+        crate::impl_uniform_block!(Padded {
+            weights: [Float; 4]
+        });
+        assert_eq!(UniformLayout::of::<Padded>().unwrap().size(), 64);
+    }
+
+    #[test]
+    fn size_multiple_of_16() {
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Params {
+            color: [f32; 4],
+            time: f32,
+        }
+        crate::impl_uniform_block!(Params {
+            color: Vec4,
+            time: Float,
+        });
+
+        let err = UniformLayout::of::<Params>().unwrap_err().to_string();
+        assert!(err.contains("is 20 bytes, expected 32"), "{}", err);
+    }
+}
@@ -0,0 +1,2 @@
+#include "consts.wgsl"
+fn common() {}
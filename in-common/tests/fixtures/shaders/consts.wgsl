@@ -0,0 +1,2 @@
+#define SCALE 2.0
+let scale = SCALE;
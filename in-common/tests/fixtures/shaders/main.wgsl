@@ -0,0 +1,4 @@
+#include "common.wgsl"
+#include "consts.wgsl"
+#include "main.wgsl"
+fn main() {}
@@ -0,0 +1,10 @@
+//! `cube` shader example
+
+use anyhow::Result;
+
+use in_rokol::scenes::{self, CubeScene};
+
+fn main() -> Result<()> {
+    env_logger::init();
+    scenes::run::<CubeScene>("cube")
+}
@@ -0,0 +1,10 @@
+//! `cube_multi` shader example
+
+use anyhow::Result;
+
+use in_rokol::scenes::{self, CubeMultiScene};
+
+fn main() -> Result<()> {
+    env_logger::init();
+    scenes::run::<CubeMultiScene>("cube_multi")
+}
@@ -0,0 +1,10 @@
+//! `more_cubes` shader example
+
+use anyhow::Result;
+
+use in_rokol::scenes::{self, MoreCubesScene};
+
+fn main() -> Result<()> {
+    env_logger::init();
+    scenes::run::<MoreCubesScene>("more_cubes")
+}
@@ -0,0 +1,10 @@
+//! `quad` shader example
+
+use anyhow::Result;
+
+use in_rokol::scenes::{self, QuadScene};
+
+fn main() -> Result<()> {
+    env_logger::init();
+    scenes::run::<QuadScene>("quad")
+}
@@ -0,0 +1,10 @@
+//! `texture_multi` shader example
+
+use anyhow::Result;
+
+use in_rokol::scenes::{self, TextureMultiScene};
+
+fn main() -> Result<()> {
+    env_logger::init();
+    scenes::run::<TextureMultiScene>("texture_multi")
+}
@@ -2,10 +2,18 @@
 RAII graphics objects on [`rokol::gfx`]
 */
 
+mod assets;
 mod mesh;
+mod model;
 mod shader;
 mod tex;
+mod tilemap;
+mod uniform;
 
-pub use mesh::{DynamicMesh, StaticMesh};
+pub use assets::{Assets, ShaderFn};
+pub use mesh::{apply_scissor, DynamicMesh, StaticMesh};
+pub use model::{Model, ModelMesh};
 pub use shader::Shader;
-pub use tex::{RenderTexture2d, Texture2dDrop, TextureBuilder};
+pub use tex::{LayeredTexture, RenderTexture2d, Texture2dDrop, TextureBuilder};
+pub use tilemap::{TileMapRenderer, CHUNK_SIZE};
+pub use uniform::Uniform;
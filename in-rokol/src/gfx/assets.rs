@@ -0,0 +1,202 @@
+/*!
+Asset registry with hot reload
+*/
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use in_common::assets::{self, Cache, Font, Handle, Watcher};
+
+use crate::{
+    gfx::{Model, Shader, Texture2dDrop, TextureBuilder},
+    shaders::TexturedVertex,
+};
+
+/// Creates a [`Shader`] from null-terminated vertex and fragment shader sources
+/// (e.g. [`crate::shaders::triangle_from`])
+pub type ShaderFn = fn(&[String; 2]) -> Result<Shader>;
+
+/// Textures, GLSL shaders, models and fonts loaded by path
+///
+/// Repeated loads of the same file share one [`Handle`]. With hot reload enabled, call
+/// [`reload_changed`](Self::reload_changed) every frame.
+#[derive(Debug)]
+pub struct Assets {
+    /// Relative paths are resolved from here
+    root: PathBuf,
+    textures: Cache<Texture2dDrop>,
+    /// Keyed by the vertex shader path
+    shaders: Cache<Shader>,
+    /// Vertex shader path -> (fragment shader path, constructor)
+    shader_fns: HashMap<PathBuf, (PathBuf, ShaderFn)>,
+    models: Cache<Model<TexturedVertex>>,
+    fonts: Cache<Font>,
+    watcher: Option<Watcher>,
+    /// Premultiply alpha of textures on load
+    premultiply_alpha: bool,
+}
+
+impl Assets {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            textures: Cache::new(),
+            shaders: Cache::new(),
+            shader_fns: HashMap::new(),
+            models: Cache::new(),
+            fonts: Cache::new(),
+            watcher: None,
+            premultiply_alpha: false,
+        }
+    }
+
+    /// Premultiplies alpha of textures loaded (and reloaded) afterwards
+    pub fn premultiplied_alpha(mut self, premultiply: bool) -> Self {
+        self.premultiply_alpha = premultiply;
+        self
+    }
+
+    pub fn premultiplies_alpha(&self) -> bool {
+        self.premultiply_alpha
+    }
+
+    /// Watches loaded files and reloads them on [`reload_changed`](Self::reload_changed)
+    pub fn with_hot_reload(root: impl Into<PathBuf>) -> Result<Self> {
+        let mut assets = Self::new(root);
+        assets.watcher = Some(Watcher::new(std::time::Duration::from_millis(100))?);
+        Ok(assets)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn watch(&mut self, file: &Path, asset: Option<&Path>) -> Result<()> {
+        if let (Some(watcher), Some(asset)) = (&mut self.watcher, asset) {
+            watcher.watch(file, asset)?;
+        }
+        Ok(())
+    }
+
+    /// Bind it with `bind_texture` of the meshes, so that reloads are followed
+    pub fn texture(&mut self, path: impl AsRef<Path>) -> Result<Handle<Texture2dDrop>> {
+        let path = self.root.join(path);
+        let premul = self.premultiply_alpha;
+        let handle = self
+            .textures
+            .load_with(&path, |p| self::load_texture(p, premul))?;
+        self.watch(&path, handle.path())?;
+        Ok(handle)
+    }
+
+    /// Vertex + fragment shader. Both files are watched
+    pub fn shader(
+        &mut self,
+        vs: impl AsRef<Path>,
+        fs: impl AsRef<Path>,
+        f: ShaderFn,
+    ) -> Result<Handle<Shader>> {
+        let vs = self.root.join(vs);
+        let fs = assets::resolve(&self.root.join(fs))?;
+
+        let handle = self
+            .shaders
+            .load_with(&vs, |vs| self::load_shader(vs, &fs, f))?;
+
+        let key = handle.path().unwrap().to_path_buf();
+        self.watch(&vs, Some(&key))?;
+        self.watch(&fs, Some(&key))?;
+        self.shader_fns.insert(key, (fs, f));
+
+        Ok(handle)
+    }
+
+    /// `.obj`, `.gltf` or `.glb` model. Only the model file is watched
+    pub fn model(&mut self, path: impl AsRef<Path>) -> Result<Handle<Model<TexturedVertex>>> {
+        let path = self.root.join(path);
+        let handle = self.models.load_with(&path, |p| Model::load(p))?;
+        self.watch(&path, handle.path())?;
+        Ok(handle)
+    }
+
+    pub fn font(&mut self, path: impl AsRef<Path>) -> Result<Handle<Font>> {
+        let path = self.root.join(path);
+        let handle = self.fonts.load_with(&path, assets::load_font)?;
+        self.watch(&path, handle.path())?;
+        Ok(handle)
+    }
+
+    /// Reloads modified assets in place and returns their paths
+    ///
+    /// Assets that fail to reload are logged and kept as they were.
+    pub fn reload_changed(&mut self) -> Vec<PathBuf> {
+        let changed = match &mut self.watcher {
+            Some(w) => w.changed(),
+            None => return Vec::new(),
+        };
+
+        let mut reloaded = Vec::new();
+        for path in changed {
+            match self.reload(&path) {
+                Ok(true) => {
+                    log::info!("reloaded asset: {}", path.display());
+                    reloaded.push(path);
+                }
+                Ok(false) => {}
+                Err(err) => log::error!("{:#}", err),
+            }
+        }
+
+        reloaded
+    }
+
+    fn reload(&mut self, path: &Path) -> Result<bool> {
+        let premul = self.premultiply_alpha;
+        let mut any = self
+            .textures
+            .reload_with(path, |p| self::load_texture(p, premul))?;
+        if let Some((fs, f)) = self.shader_fns.get(path) {
+            any |= self
+                .shaders
+                .reload_with(path, |vs| self::load_shader(vs, fs, *f))?;
+        }
+        any |= self.models.reload_with(path, |p| Model::load(p))?;
+        any |= self.fonts.reload_with(path, assets::load_font)?;
+        Ok(any)
+    }
+
+    /// Forgets dropped assets
+    pub fn gc(&mut self) {
+        self.textures.gc();
+        self.shaders.gc();
+        self.models.gc();
+        self.fonts.gc();
+
+        let shaders = &self.shaders;
+        self.shader_fns.retain(|vs, _| shaders.get(vs).is_some());
+    }
+}
+
+fn load_texture(path: &Path, premultiply_alpha: bool) -> Result<Texture2dDrop> {
+    Ok(TextureBuilder::from_path(path)?
+        .premultiply_alpha(premultiply_alpha)
+        .build_texture())
+}
+
+fn load_shader(vs: &Path, fs: &Path, f: ShaderFn) -> Result<Shader> {
+    let load = |path: &Path| -> Result<String> {
+        let mut src = std::fs::read_to_string(path)
+            .with_context(|| format!("loading shader: {}", path.display()))?;
+        src.push('\0');
+        Ok(src)
+    };
+
+    // on failure, the old shader is kept
+    let shader = f(&[load(vs)?, load(fs)?])
+        .and_then(|shader| shader.check_state().map(|()| shader))
+        .with_context(|| format!("creating shader: {}", vs.display()))?;
+    Ok(shader)
+}
@@ -1,14 +1,46 @@
 use std::marker::PhantomData;
 
+use in_common::{assets::Handle, clip::ClipBatch, mesh::SubMesh};
 use rokol::gfx::{self as rg, BakedResource};
+use vek::{Extent2, Rect};
 
-use crate::utils::as_bytes;
+use crate::{gfx::Texture2dDrop, utils::as_bytes};
+
+/// Texture handles bound to image slots
+///
+/// The image is looked up on draw, so that hot-reloaded textures (which are new images) are
+/// bound instead of the destroyed ones.
+#[derive(Debug, Clone, Default)]
+struct TextureSlots {
+    slots: Vec<(usize, Handle<Texture2dDrop>)>,
+}
+
+impl TextureSlots {
+    fn set(&mut self, slot: usize, tex: Option<&Handle<Texture2dDrop>>) {
+        self.slots.retain(|(s, _)| *s != slot);
+        if let Some(tex) = tex {
+            self.slots.push((slot, tex.clone()));
+        }
+    }
+
+    /// Bindings with the current images of the handles
+    fn resolve(&self, bind: &rg::Bindings) -> rg::Bindings {
+        let mut bind = bind.clone();
+        for (slot, tex) in &self.slots {
+            bind.fs_images[*slot] = tex.get().img();
+        }
+        bind
+    }
+}
 
 /// Immutable buffers
 #[derive(Debug, Clone, Default)]
 pub struct StaticMesh<V> {
     bind: rg::Bindings,
+    textures: TextureSlots,
     n_indices: usize,
+    /// Named index ranges
+    submeshes: Vec<SubMesh>,
     _phantom: PhantomData<V>,
 }
 
@@ -31,7 +63,9 @@ impl<V> StaticMesh<V> {
                 index_buffer: rg::Buffer::create(&rg::ibuf_desc_immutable(as_bytes(indices), "")),
                 ..Default::default()
             },
+            textures: TextureSlots::default(),
             n_indices: indices.len(),
+            submeshes: Vec::new(),
             _phantom: PhantomData,
         }
     }
@@ -48,20 +82,82 @@ impl<V> StaticMesh<V> {
 
     /// slot: [0, 12)
     pub fn bind_img(&mut self, img: rg::Image, slot: usize) {
+        self.textures.set(slot, None);
         self.bind.fs_images[slot] = img;
     }
 
+    /// Binds the texture of an asset handle, following its reloads. slot: [0, 12)
+    pub fn bind_texture(&mut self, tex: &Handle<Texture2dDrop>, slot: usize) {
+        self.textures.set(slot, Some(tex));
+    }
+
+    /// Sets named index ranges. Fails if any of them is out of the index buffer
+    pub fn with_submeshes(mut self, submeshes: Vec<SubMesh>) -> anyhow::Result<Self> {
+        let n_indices = self.n_indices.min(u32::MAX as usize) as u32;
+        for sub in &submeshes {
+            sub.check(n_indices)?;
+        }
+        self.submeshes = submeshes;
+        Ok(self)
+    }
+
+    pub fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    /// Index of sub-mesh with the name
+    pub fn find_submesh(&self, name: &str) -> Option<usize> {
+        self.submeshes.iter().position(|sub| sub.name == name)
+    }
+
     /// Draws all the elements
     pub fn draw_all(&self) {
-        rg::apply_bindings(&self.bind);
+        rg::apply_bindings(&self.textures.resolve(&self.bind));
         rg::draw(0, self.n_indices as u32, 1);
     }
+
+    /// Draws `n_indices` from `base_elem`
+    ///
+    /// * `base_vertex`: added to each index before fetching a vertex. `sokol` has no base vertex,
+    /// so it's emulated with the vertex buffer offset.
+    pub fn draw_range(&self, base_elem: u32, n_indices: u32, base_vertex: i32) {
+        assert!(
+            matches!(base_elem.checked_add(n_indices), Some(end) if end as usize <= self.n_indices),
+            "index range {} + {} is out of the index buffer",
+            base_elem,
+            n_indices
+        );
+        assert!(base_vertex >= 0, "negative base vertex is not supported");
+
+        let mut bind = self.textures.resolve(&self.bind);
+        bind.vertex_buffer_offsets[0] = base_vertex * std::mem::size_of::<V>() as i32;
+        rg::apply_bindings(&bind);
+        rg::draw(base_elem, n_indices, 1);
+    }
+
+    /// Draws the `ix`-th sub-mesh. Be sure to bind its material before calling this
+    pub fn draw_submesh(&self, ix: usize) {
+        let sub = &self.submeshes[ix];
+        self.draw_range(sub.start, sub.count, sub.base_vertex);
+    }
+
+    /// Draws each batch with its scissor rect. Fully clipped batches are skipped
+    ///
+    /// The scissor rect is reset to the whole frame buffer (`fb_size`) afterwards.
+    pub fn draw_clipped(&self, batches: &[ClipBatch], fb_size: Extent2<u32>) {
+        for batch in batches.iter().filter(|b| !b.is_empty()) {
+            self::apply_scissor(batch.scissor);
+            self.draw_range(batch.start, batch.count, 0);
+        }
+        self::apply_scissor(Rect::new(0, 0, fb_size.w, fb_size.h));
+    }
 }
 
 /// Dynamic buffers
 #[derive(Debug, Clone, Default)]
 pub struct DynamicMesh<V> {
     bind: rg::Bindings,
+    textures: TextureSlots,
     n_indices: usize,
     pub verts: Vec<V>,
 }
@@ -88,6 +184,7 @@ impl<V> DynamicMesh<V> {
 
         Self {
             bind: b,
+            textures: TextureSlots::default(),
             n_indices: indices.len(),
             verts,
         }
@@ -105,9 +202,15 @@ impl<V> DynamicMesh<V> {
 
     /// slot: [0, 12)
     pub fn bind_img(&mut self, img: rg::Image, slot: usize) {
+        self.textures.set(slot, None);
         self.bind.fs_images[slot] = img;
     }
 
+    /// Binds the texture of an asset handle, following its reloads. slot: [0, 12)
+    pub fn bind_texture(&mut self, tex: &Handle<Texture2dDrop>, slot: usize) {
+        self.textures.set(slot, Some(tex));
+    }
+
     /// WARNING: can be called only once a frame
     pub unsafe fn upload_all_verts(&mut self) {
         rg::update_buffer(self.bind.vertex_buffers[0], as_bytes(&self.verts));
@@ -146,10 +249,21 @@ impl<V> DynamicMesh<V> {
     ///
     /// `base_elem` should be zero after calling `append_vert_slice`.
     pub fn draw(&self, base_elem: u32, n_indices: u32) {
-        rg::apply_bindings(&self.bind);
+        rg::apply_bindings(&self.textures.resolve(&self.bind));
         rg::draw(base_elem, n_indices, 1);
     }
 
+    /// Draws each batch with its scissor rect. Fully clipped batches are skipped
+    ///
+    /// The scissor rect is reset to the whole frame buffer (`fb_size`) afterwards.
+    pub fn draw_clipped(&self, batches: &[ClipBatch], fb_size: Extent2<u32>) {
+        for batch in batches.iter().filter(|b| !b.is_empty()) {
+            self::apply_scissor(batch.scissor);
+            self.draw(batch.start, batch.count);
+        }
+        self::apply_scissor(Rect::new(0, 0, fb_size.w, fb_size.h));
+    }
+
     /// FIXME: call upload_all_verts in this method?
     pub fn draw_all(&self) {
         self.draw(0, self.n_indices as u32);
@@ -164,3 +278,14 @@ impl<V> DynamicMesh<V> {
     //     &mut self.bind
     // }
 }
+
+/// Applies a scissor rect in pixels (top-left origin) to the current pass
+pub fn apply_scissor(rect: Rect<u32, u32>) {
+    rg::apply_scissor_rect(
+        rect.x as i32,
+        rect.y as i32,
+        rect.w as i32,
+        rect.h as i32,
+        true,
+    );
+}
@@ -0,0 +1,86 @@
+//! Imported models (OBJ, glTF) as [`StaticMesh`]es
+
+use std::path::Path;
+
+use anyhow::Result;
+use in_common::mesh::{MaterialData, MeshVertex, ModelData};
+
+use crate::gfx::{StaticMesh, Texture2dDrop, TextureBuilder};
+
+/// Mesh of a [`Model`] with material reference
+#[derive(Debug)]
+pub struct ModelMesh<V> {
+    pub name: String,
+    /// NOTE: Indices are `u32`; use `rg::IndexType::UInt32` in the pipeline
+    pub mesh: StaticMesh<V>,
+    /// Index of [`Model::materials`]
+    pub material: Option<usize>,
+}
+
+/// Meshes, materials and textures loaded from a model file
+#[derive(Debug)]
+pub struct Model<V> {
+    pub meshes: Vec<ModelMesh<V>>,
+    pub materials: Vec<MaterialData>,
+    /// Referred to by [`MaterialData::texture`]
+    pub textures: Vec<Texture2dDrop>,
+}
+
+impl<V: From<MeshVertex>> Model<V> {
+    /// Loads `.obj`, `.gltf` or `.glb` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let data = ModelData::load(path)?;
+        Ok(Self::from_data(&data))
+    }
+
+    pub fn from_data(data: &ModelData) -> Self {
+        let meshes = data
+            .meshes
+            .iter()
+            .map(|mesh| ModelMesh {
+                name: mesh.name.clone(),
+                mesh: StaticMesh::new_32(&mesh.vertices::<V>(), &mesh.indices),
+                material: mesh.material,
+            })
+            .collect();
+
+        // NOTE: Not flipped vertically; UVs have the top-left origin
+        let textures = data
+            .images
+            .iter()
+            .map(|img| {
+                TextureBuilder::from_pixels(img.as_raw(), img.width(), img.height()).build_texture()
+            })
+            .collect();
+
+        Self {
+            meshes,
+            materials: data.materials.clone(),
+            textures,
+        }
+    }
+
+    pub fn material(&self, mesh: &ModelMesh<V>) -> Option<&MaterialData> {
+        mesh.material.and_then(|i| self.materials.get(i))
+    }
+
+    /// Base color texture of the mesh
+    pub fn texture(&self, mesh: &ModelMesh<V>) -> Option<&Texture2dDrop> {
+        self.material(mesh)
+            .and_then(|m| m.texture)
+            .and_then(|i| self.textures.get(i))
+    }
+
+    /// Binds the textures and draws every mesh
+    pub fn draw_all(&mut self) {
+        for i in 0..self.meshes.len() {
+            let img = self
+                .texture(&self.meshes[i])
+                .map(|tex| tex.img())
+                .unwrap_or_default();
+            let mesh = &mut self.meshes[i].mesh;
+            mesh.bind_img(img, 0);
+            mesh.draw_all();
+        }
+    }
+}
@@ -2,6 +2,7 @@
 TODO: maybe recommend bytemuck for `as_bytes`
 */
 
+use in_common::reflect::StageReflection;
 use rokol::gfx::{self as rg, BakedResource};
 
 /// [`rg::Shader`] + [`rg::Pipeline`] with methods
@@ -9,6 +10,11 @@ use rokol::gfx::{self as rg, BakedResource};
 pub struct Shader {
     shd: rg::Shader,
     pip: rg::Pipeline,
+    /// Declared uniform block sizes of the vertex and fragment stage (`0`: no block). `None`
+    /// if unknown
+    ub_sizes: Option<[Vec<usize>; 2]>,
+    /// Uniforms and images of the vertex and fragment stage parsed from the sources
+    reflection: Option<[StageReflection; 2]>,
 }
 
 impl std::ops::Drop for Shader {
@@ -19,19 +25,106 @@ impl std::ops::Drop for Shader {
 }
 
 impl Shader {
+    /// Uniform sizes are not checked. Prefer [`Shader::with_desc`]
     pub fn new(shd: rg::Shader, pip: rg::Pipeline) -> Self {
-        Self { shd, pip }
+        Self {
+            shd,
+            pip,
+            ub_sizes: None,
+            reflection: None,
+        }
     }
 
+    /// Remembers the uniform block sizes declared in `desc` to validate uniforms
+    pub fn with_desc(shd: rg::Shader, pip: rg::Pipeline, desc: &rg::ShaderDesc) -> Self {
+        let sizes = |blocks: &[rg::ShaderUniformBlockDesc]| {
+            blocks.iter().map(|b| b.size as usize).collect::<Vec<_>>()
+        };
+
+        Self {
+            shd,
+            pip,
+            ub_sizes: Some([
+                sizes(&desc.vs.uniform_blocks),
+                sizes(&desc.fs.uniform_blocks),
+            ]),
+            reflection: None,
+        }
+    }
+
+    /// Remembers the reflection the `desc` was built from to validate uniforms field by field
+    pub fn with_reflection(mut self, reflection: [StageReflection; 2]) -> Self {
+        self.reflection = Some(reflection);
+        self
+    }
+
+    /// Vertex stage reflection. `None` if unknown
+    pub fn vs_reflection(&self) -> Option<&StageReflection> {
+        self.reflection.as_ref().map(|r| &r[0])
+    }
+
+    /// Fragment stage reflection. `None` if unknown
+    pub fn fs_reflection(&self) -> Option<&StageReflection> {
+        self.reflection.as_ref().map(|r| &r[1])
+    }
+
+    /// Image slot of a fragment shader sampler. `None` if unknown
+    pub fn fs_image_slot(&self, name: &str) -> Option<usize> {
+        self.fs_reflection()?.image_slot(name)
+    }
+
+    /// Declared size of a uniform block. `None` if unknown
+    pub fn vs_uniform_size(&self, ix: usize) -> Option<usize> {
+        self.ub_sizes
+            .as_ref()
+            .map(|s| s[0].get(ix).copied().unwrap_or(0))
+    }
+
+    /// Declared size of a uniform block. `None` if unknown
+    pub fn fs_uniform_size(&self, ix: usize) -> Option<usize> {
+        self.ub_sizes
+            .as_ref()
+            .map(|s| s[1].get(ix).copied().unwrap_or(0))
+    }
+
+    /// Panics if the size doesn't match the declared block. Prefer [`Uniform`](super::Uniform)
     pub fn set_vs_uniform(&self, ix: usize, bytes: &[u8]) {
+        self::check_size("vs", ix, self.vs_uniform_size(ix), bytes.len());
         rg::apply_uniforms(rg::ShaderStage::Vs, ix as u32, bytes);
     }
 
+    /// Panics if the size doesn't match the declared block. Prefer [`Uniform`](super::Uniform)
     pub fn set_fs_uniform(&self, ix: usize, bytes: &[u8]) {
+        self::check_size("fs", ix, self.fs_uniform_size(ix), bytes.len());
         rg::apply_uniforms(rg::ShaderStage::Fs, ix as u32, bytes);
     }
 
     pub fn apply_pip(&self) {
         rg::apply_pipeline(self.pip);
     }
+
+    /// Fails if the shader didn't compile or link, or the pipeline couldn't be created
+    ///
+    /// `sokol` only logs the errors and keeps the resources in the `FAILED` state.
+    pub fn check_state(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            rg::query_shader_state(self.shd) != rg::ResourceState::Failed,
+            "failed to compile or link shader"
+        );
+        anyhow::ensure!(
+            rg::query_pipeline_state(self.pip) != rg::ResourceState::Failed,
+            "failed to create pipeline"
+        );
+        Ok(())
+    }
+}
+
+fn check_size(stage: &str, ix: usize, expected: Option<usize>, size: usize) {
+    if let Some(expected) = expected {
+        assert_eq!(
+            size, expected,
+            "{} uniform block {}: given {} bytes, expected {} bytes",
+            stage, ix, size, expected
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use {
     image::GenericImageView,
+    in_common::texture::TextureLayers,
     rokol::gfx::{self as rg, BakedResource},
     std::{borrow::Cow, path::Path},
 };
@@ -9,10 +10,15 @@ pub type Result<T> = image::ImageResult<T>;
 
 #[derive(Debug)]
 pub struct TextureBuilder<'a> {
+    /// Layers (or cube faces) concatenated
     pixels: Cow<'a, [u8]>,
     size: [u32; 2],
+    image_type: rg::ImageType,
+    /// Array layers, depth or cube faces
+    layers: u32,
     filter: rg::Filter,
     wrap: rg::Wrap,
+    premultiply_alpha: bool,
 }
 
 impl TextureBuilder<'static> {
@@ -36,8 +42,56 @@ impl TextureBuilder<'static> {
         Self {
             pixels: Cow::from(img),
             size,
+            image_type: rg::ImageType::Dim2,
+            layers: 1,
             filter: rg::Filter::Linear,
             wrap: rg::Wrap::ClampToEdge,
+            premultiply_alpha: false,
+        }
+    }
+
+    /// 2D array texture (`sampler2DArray`)
+    pub fn array(layers: &TextureLayers) -> Self {
+        Self::from_layers(layers, rg::ImageType::Array, true)
+    }
+
+    /// 3D texture (`sampler3D`) with the layers as depth slices
+    pub fn volume(layers: &TextureLayers) -> Self {
+        Self::from_layers(layers, rg::ImageType::Dim3, true)
+    }
+
+    /// Cube map (`samplerCube`) from six faces (see [`TextureLayers::cube`])
+    pub fn cube(faces: &TextureLayers) -> Self {
+        let [w, h] = faces.size();
+        assert!(
+            faces.n_layers() == 6 && w == h,
+            "cube map needs 6 square faces, given {} of {}x{}",
+            faces.n_layers(),
+            w,
+            h
+        );
+        // cube map faces have the top-left origin even in OpenGL
+        Self::from_layers(faces, rg::ImageType::Cube, false)
+    }
+
+    fn from_layers(layers: &TextureLayers, image_type: rg::ImageType, flip: bool) -> Self {
+        let pixels = if flip {
+            // [OpenGL] invert vertically
+            let mut layers = layers.clone();
+            layers.flip_vertical();
+            layers.pixels()
+        } else {
+            layers.pixels()
+        };
+
+        Self {
+            pixels: Cow::from(pixels),
+            size: layers.size(),
+            image_type,
+            layers: layers.n_layers(),
+            filter: rg::Filter::Linear,
+            wrap: rg::Wrap::ClampToEdge,
+            premultiply_alpha: false,
         }
     }
 }
@@ -47,8 +101,11 @@ impl<'a> TextureBuilder<'a> {
         Self {
             pixels: Cow::from(pixels),
             size: [w, h],
+            image_type: rg::ImageType::Dim2,
+            layers: 1,
             filter: rg::Filter::Linear,
             wrap: rg::Wrap::ClampToEdge,
+            premultiply_alpha: false,
         }
     }
 
@@ -62,19 +119,67 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    /// Multiplies the color with alpha on build, for correct filtering at transparent edges.
+    /// Draw the texture with `BlendMode::Premultiplied`
+    pub fn premultiply_alpha(&mut self, premultiply: bool) -> &mut Self {
+        self.premultiply_alpha = premultiply;
+        self
+    }
+
+    /// Builds a 2D texture. Use [`build_layered`](Self::build_layered) for the others
     pub fn build_texture(&self) -> Texture2dDrop {
+        assert!(
+            matches!(self.image_type, rg::ImageType::Dim2),
+            "not a 2D texture: {:?}",
+            self.image_type
+        );
+
         Texture2dDrop {
-            img: rg::Image::create(&{
-                let mut desc = self::img_desc(self.size[0], self.size[1], self.filter, self.wrap);
-                desc.render_target = false;
-                desc.usage = rg::ResourceUsage::Immutable.to_ffi();
-                desc.data.subimage[0][0] = self.pixels.as_ref().into();
-                desc
-            }),
+            img: self.create_image(),
             w: self.size[0],
             h: self.size[1],
         }
     }
+
+    /// Builds an array, 3D or cube texture
+    pub fn build_layered(&self) -> LayeredTexture {
+        LayeredTexture {
+            img: self.create_image(),
+            size: self.size,
+            layers: self.layers,
+            image_type: self.image_type,
+        }
+    }
+
+    fn create_image(&self) -> rg::Image {
+        let pixels = if self.premultiply_alpha {
+            let mut pixels = self.pixels.to_vec();
+            in_common::blend::premultiply_alpha(&mut pixels);
+            Cow::Owned(pixels)
+        } else {
+            Cow::Borrowed(self.pixels.as_ref())
+        };
+
+        rg::Image::create(&{
+            let mut desc = self::img_desc(self.size[0], self.size[1], self.filter, self.wrap);
+            desc.type_ = self.image_type.to_ffi();
+            desc.render_target = false;
+            desc.usage = rg::ResourceUsage::Immutable.to_ffi();
+
+            if matches!(self.image_type, rg::ImageType::Cube) {
+                // one sub-image per face
+                let face = pixels.len() / 6;
+                for (i, bytes) in pixels.chunks_exact(face).enumerate() {
+                    desc.data.subimage[i][0] = bytes.into();
+                }
+            } else {
+                desc.num_slices = self.layers as i32;
+                desc.data.subimage[0][0] = pixels.as_ref().into();
+            }
+
+            desc
+        })
+    }
 }
 
 fn img_desc(w: u32, h: u32, filter: rg::Filter, wrap: rg::Wrap) -> rg::ImageDesc {
@@ -91,6 +196,41 @@ fn img_desc(w: u32, h: u32, filter: rg::Filter, wrap: rg::Wrap) -> rg::ImageDesc
     }
 }
 
+/// Owned array, 3D or cube texture
+#[derive(Debug)]
+pub struct LayeredTexture {
+    img: rg::Image,
+    size: [u32; 2],
+    layers: u32,
+    image_type: rg::ImageType,
+}
+
+impl Drop for LayeredTexture {
+    fn drop(&mut self) {
+        rg::Image::destroy(self.img);
+    }
+}
+
+impl LayeredTexture {
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Array layers, depth of 3D texture or 6 for cube map
+    pub fn n_layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// For the shader image description (`ShaderImageDesc::image_type`)
+    pub fn image_type(&self) -> rg::ImageType {
+        self.image_type
+    }
+
+    pub fn img(&self) -> rg::Image {
+        self.img
+    }
+}
+
 /// Owned 2D texture
 #[derive(Debug, Default)]
 pub struct Texture2dDrop {
@@ -128,6 +268,12 @@ impl Texture2dDrop {
     }
 }
 
+impl in_common::anim::TextureSize for Texture2dDrop {
+    fn texture_size(&self) -> [u32; 2] {
+        self.size()
+    }
+}
+
 /// Off-screen 2D rendering target
 #[derive(Debug, Default)]
 pub struct RenderTexture2d {
@@ -0,0 +1,161 @@
+/*!
+Tilemap rendering: chunked meshes with camera culling
+*/
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use in_common::{
+    camera::Camera2d,
+    mesh::MeshData,
+    tilemap::{ChunkMesh, TileMap},
+};
+use rokol::gfx as rg;
+use vek::{Mat4, Rect};
+
+use crate::{
+    gfx::{DynamicMesh, Shader, StaticMesh, Texture2dDrop, TextureBuilder, Uniform},
+    shaders::{self, TexturedVertex},
+};
+
+/// Default chunk size in tiles
+pub const CHUNK_SIZE: u32 = 16;
+
+/// Chunks without animated tiles are immutable
+#[derive(Debug)]
+enum GpuMesh {
+    Static(StaticMesh<TexturedVertex>),
+    Dynamic(DynamicMesh<TexturedVertex>),
+}
+
+#[derive(Debug)]
+struct Chunk {
+    data: ChunkMesh,
+    mesh: GpuMesh,
+}
+
+/// GPU meshes of a [`TileMap`]
+///
+/// Call [`update`](Self::update) every frame and then [`draw`](Self::draw). Chunks outside of
+/// the camera are skipped, and so are layers that are not visible.
+#[derive(Debug)]
+pub struct TileMapRenderer {
+    shd: Shader,
+    /// Column-major `mvp`
+    mvp_ub: Uniform<[f32; 16]>,
+    /// Texture per tileset, bound to the chunks
+    _textures: Vec<Texture2dDrop>,
+    /// In layer order
+    chunks: Vec<Chunk>,
+    /// Visibility per layer
+    layers: Vec<bool>,
+    mvp: Mat4<f32>,
+    /// World rect of the camera
+    view: Rect<f32, f32>,
+}
+
+impl TileMapRenderer {
+    /// Loads the tileset images (with nearest filter) and builds the chunk meshes
+    pub fn new(map: &TileMap, chunk_size: u32) -> Result<Self> {
+        let textures = map
+            .tilesets
+            .iter()
+            .map(|ts| {
+                let tex = TextureBuilder::from_path(&ts.image)
+                    .with_context(|| format!("loading tileset `{}`", ts.name))?
+                    .filter(rg::Filter::Nearest)
+                    .build_texture();
+                Ok(tex)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let chunks = (0..map.layers.len())
+            .flat_map(|layer| map.build_chunks(layer, chunk_size, Duration::ZERO))
+            .map(|data| {
+                let verts = self::vertices(&data.mesh);
+                // `build_chunks` keeps the vertices `u16`-addressable
+                let indices = data.mesh.indices_u16().unwrap();
+                let img = textures[data.tileset].img();
+
+                let mesh = if data.animated {
+                    let mut mesh = DynamicMesh::new_16(verts, &indices);
+                    mesh.bind_img(img, 0);
+                    GpuMesh::Dynamic(mesh)
+                } else {
+                    let mut mesh = StaticMesh::new_16(&verts, &indices);
+                    mesh.bind_img(img, 0);
+                    GpuMesh::Static(mesh)
+                };
+
+                Chunk { data, mesh }
+            })
+            .collect();
+
+        let shd = shaders::tilemap()?;
+        let mvp_ub = Uniform::vs(&shd, 0)?;
+
+        Ok(Self {
+            shd,
+            mvp_ub,
+            _textures: textures,
+            chunks,
+            layers: map.layers.iter().map(|l| l.visible).collect(),
+            mvp: Mat4::identity(),
+            view: Rect::new(0.0, 0.0, 0.0, 0.0),
+        })
+    }
+
+    /// Sets the camera and uploads the animated tiles of visible chunks
+    ///
+    /// * `time`: animation clock (animations loop from time zero)
+    ///
+    /// WARNING: can be called only once a frame
+    pub fn update(&mut self, map: &TileMap, camera: &Camera2d, time: Duration) {
+        self.mvp = camera.matrix();
+        self.view = camera.view_rect();
+
+        for (visible, layer) in self.layers.iter_mut().zip(&map.layers) {
+            *visible = layer.visible;
+        }
+
+        for chunk in &mut self.chunks {
+            let visible = self.layers[chunk.data.layer] && chunk.data.is_visible(self.view);
+            let mesh = match &mut chunk.mesh {
+                GpuMesh::Dynamic(mesh) if visible => mesh,
+                _ => continue,
+            };
+
+            map.rebuild_chunk(&mut chunk.data, time);
+            mesh.verts = self::vertices(&chunk.data.mesh);
+            unsafe {
+                mesh.upload_all_verts();
+            }
+        }
+    }
+
+    /// Draws the chunks in the camera, in layer order
+    pub fn draw(&self) {
+        self.shd.apply_pip();
+        self.mvp_ub.apply(&self.mvp.into_col_array());
+
+        for chunk in &self.chunks {
+            if !self.layers[chunk.data.layer] || !chunk.data.is_visible(self.view) {
+                continue;
+            }
+
+            match &chunk.mesh {
+                GpuMesh::Static(mesh) => mesh.draw_all(),
+                GpuMesh::Dynamic(mesh) => mesh.draw_all(),
+            }
+        }
+    }
+}
+
+/// [OpenGL] flips V since textures are uploaded upside down
+fn vertices(mesh: &MeshData) -> Vec<TexturedVertex> {
+    let mut verts = mesh.vertices::<TexturedVertex>();
+    for v in &mut verts {
+        v.uv[1] = 1.0 - v.uv[1];
+    }
+    verts
+}
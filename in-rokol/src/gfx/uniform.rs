@@ -0,0 +1,93 @@
+/*!
+Typed uniform blocks
+
+The layout of `T` is validated against the std140 rules and the block declared in the shader
+when the [`Uniform`] is created. Shaders from [`crate::shaders`] are checked field by field
+against the reflected `uniform` declarations.
+*/
+
+use std::marker::PhantomData;
+
+use anyhow::{bail, Context, Result};
+use in_common::{
+    reflect::StageReflection,
+    uniform::{UniformBlock, UniformLayout},
+};
+use rokol::gfx as rg;
+
+use crate::{gfx::Shader, utils::as_bytes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Vs,
+    Fs,
+}
+
+/// Uniform block slot of a [`Shader`] stage, applied with values of `T`
+#[derive(Debug)]
+pub struct Uniform<T> {
+    stage: Stage,
+    slot: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: UniformBlock> Uniform<T> {
+    /// Vertex shader uniform block
+    pub fn vs(shader: &Shader, slot: usize) -> Result<Self> {
+        Self::new(
+            Stage::Vs,
+            shader.vs_uniform_size(slot),
+            shader.vs_reflection(),
+            slot,
+        )
+    }
+
+    /// Fragment shader uniform block
+    pub fn fs(shader: &Shader, slot: usize) -> Result<Self> {
+        Self::new(
+            Stage::Fs,
+            shader.fs_uniform_size(slot),
+            shader.fs_reflection(),
+            slot,
+        )
+    }
+
+    fn new(
+        stage: Stage,
+        declared: Option<usize>,
+        reflection: Option<&StageReflection>,
+        slot: usize,
+    ) -> Result<Self> {
+        let layout = UniformLayout::of::<T>()?;
+        match declared {
+            Some(0) => bail!("no {:?} uniform block at slot {}", stage, slot),
+            Some(size) => layout.check_size(size)?,
+            None => {}
+        }
+
+        // reflected uniforms make up block 0
+        if let (0, Some(refl)) = (slot, reflection) {
+            refl.check_block::<T>()
+                .with_context(|| format!("{:?} uniform block {}", stage, slot))?;
+        }
+
+        Ok(Self {
+            stage,
+            slot,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Call after [`Shader::apply_pip`]
+    pub fn apply(&self, value: &T) {
+        let stage = match self.stage {
+            Stage::Vs => rg::ShaderStage::Vs,
+            Stage::Fs => rg::ShaderStage::Fs,
+        };
+        rg::apply_uniforms(
+            stage,
+            self.slot as u32,
+            as_bytes(std::slice::from_ref(value)),
+        );
+    }
+}
@@ -3,7 +3,7 @@
 uniform sampler2D tex1;
 uniform sampler2D tex2;
 
-in vec3 color;
+in vec4 color;
 in vec2 uv;
 
 out vec4 frag_color;
@@ -1,8 +1,8 @@
 #version 330
 
-uniform vs_params {
-    mat4 mvp;
-};
+// NOTE: sokol's GL backend sets uniforms with `glGetUniformLocation`, which doesn't find the
+// members of a uniform block. Loose uniforms like `cube.vs`
+uniform mat4 mvp;
 
 layout(location=0) in vec3 in_pos;
 layout(location=1) in vec4 in_color;
@@ -12,7 +12,7 @@ out vec4 color;
 out vec2 uv;
 
 void main() {
-    gl_Position = vec4(in_pos, 1.0);
+    gl_Position = mvp * vec4(in_pos, 1.0);
     color = in_color;
     uv = in_uv;
 }
@@ -2,5 +2,6 @@
 
 pub mod gfx;
 pub mod runner;
+pub mod scenes;
 pub mod shaders;
 pub mod utils;
@@ -2,31 +2,109 @@
 
 use std::time::Duration;
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Error, Result};
 use rokol::{
     gfx as rg,
     glue::sdl::{Init, WindowHandle},
 };
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 
+use in_common::{
+    assets::Handle,
+    clip::ClipStack,
+    input::{ActionMap, Controllers, Input},
+};
 use in_rokol::{
-    gfx::{Shader, StaticMesh},
-    runner, shaders,
+    gfx::{self, Assets, Shader, StaticMesh},
+    runner::{self, StateHasher, Viewport},
+    shaders,
 };
 
+/// Simulation state, updated from events, viewports and timesteps only
+///
+/// It doesn't touch the window, the GPU, devices or files, so replays run headless.
+#[derive(Debug)]
+pub struct Sim {
+    input: Input,
+    /// Scissor rect of the screen pass
+    clip: ClipStack,
+    /// Sum of timesteps
+    elapsed: Duration,
+}
+
+impl Sim {
+    /// The viewport is set by the first update
+    pub fn new(actions: ActionMap) -> Self {
+        Self {
+            input: Input::new(actions),
+            clip: ClipStack::new([1280, 720], [1.0, 1.0]),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn set_viewport(&mut self, vp: Viewport) {
+        self.input.set_dpi_scale(vp.dpi_scale);
+        self.clip.set_viewport(vp.drawable_size, vp.dpi_scale);
+    }
+
+    pub fn event(&mut self, ev: &Event) {
+        self.input.event(ev);
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.input.end_frame();
+    }
+
+    /// Hash of the simulation state for checking replays
+    pub fn state_hash(&self) -> u64 {
+        let mut h = StateHasher::new();
+        h.write_u64(self.elapsed.as_nanos() as u64);
+
+        // sets have no stable order
+        let mut keys = self
+            .input
+            .keys
+            .iter_down()
+            .map(|k| *k as i32 as u32)
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        h.write_u32(keys.len() as u32);
+        for key in keys {
+            h.write_u32(key);
+        }
+
+        let pos = self.input.mouse_pos();
+        h.write_f32(pos.x).write_f32(pos.y);
+
+        h.finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct App {
     window: WindowHandle,
     /// Clears the frame color buffer on starting screen rendering pass
     pa: rg::PassAction,
+    assets: Assets,
     /// Triangle shader
-    shd: Shader,
+    shd: Handle<Shader>,
     /// Buffer for the triangle shader
     mesh: StaticMesh<shaders::TriangleVertex>,
+    /// Opens game controllers on hot-plug. Their state is in the [`Input`] of `sim`
+    controllers: Controllers,
+    sim: Sim,
+    /// If the viewport is read from the window on the next update
+    resized: bool,
+    /// Viewport applied in the last update, taken by the recorder
+    new_viewport: Option<Viewport>,
 }
 
 impl App {
-    pub fn new(window: WindowHandle) -> Self {
+    pub fn new(window: WindowHandle) -> Result<Self> {
         // set up a triangle
         let verts: &[shaders::TriangleVertex] = &[
             // (vertex, color)
@@ -36,44 +114,162 @@ impl App {
         ];
         let indices: &[u16] = &[0, 1, 2];
 
-        Self {
+        let controllers = Controllers::new(&window.sdl)?;
+
+        let mut assets = Assets::with_hot_reload(env!("CARGO_MANIFEST_DIR"))?;
+        let shd = assets.shader(
+            "src/glsl/triangle.vs",
+            "src/glsl/triangle.fs",
+            shaders::triangle_from,
+        )?;
+
+        Ok(Self {
             window,
             pa: rg::PassAction::clear([100.0 / 255.0, 149.0 / 255.0, 237.0 / 255.0, 1.0]),
-            shd: shaders::triangle(),
+            assets,
+            shd,
             mesh: StaticMesh::new_16(verts, indices),
-        }
+            controllers,
+            sim: Sim::new(self::actions()?),
+            resized: true,
+            new_viewport: None,
+        })
     }
 }
 
 impl App {
     pub fn on_event(&mut self, ev: &Event) {
-        //
+        if let Event::Window {
+            win_event: WindowEvent::Resized(..),
+            ..
+        } = ev
+        {
+            // applied in the update, like the recorded viewport in replays
+            self.resized = true;
+        }
+
+        self.controllers.event(ev);
+        self.sim.event(ev);
     }
 
-    pub fn update(&mut self) {
-        //
+    pub fn update(&mut self, dt: Duration) {
+        if self.sim.input.action_pressed("quit") {
+            // the runner stops on the quit event (which is recorded, too)
+            let quit = Event::Quit { timestamp: 0 };
+            if let Err(err) = self.window.sdl.event().and_then(|ev| ev.push_event(quit)) {
+                log::warn!("failed to push quit event: {}", err);
+            }
+        }
+
+        if std::mem::take(&mut self.resized) {
+            let vp = self::viewport(&self.window);
+            self.sim.set_viewport(vp);
+            self.new_viewport = Some(vp);
+        }
+
+        self.sim.update(dt);
+        self.assets.reload_changed();
+    }
+
+    /// Viewport applied in the last update (for recording)
+    pub fn take_viewport(&mut self) -> Option<Viewport> {
+        self.new_viewport.take()
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        self.sim.state_hash()
     }
 
     pub fn render(&mut self) {
         rg::begin_default_pass(&self.pa, 1280, 720);
-        self.shd.apply_pip();
-        self.mesh.draw_all();
+        if !self.sim.clip.is_empty() {
+            gfx::apply_scissor(self.sim.clip.scissor());
+            self.shd.get().apply_pip();
+            self.mesh.draw_all();
+        }
         rg::end_pass();
     }
 
     pub fn end_frame(&mut self) {
         rg::commit();
         self.window.swap_window();
+        self.sim.end_frame();
+    }
+}
+
+fn actions() -> Result<ActionMap> {
+    ActionMap::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/actions.txt"))
+}
+
+/// Drawable size and window size to frame buffer size
+fn viewport(window: &WindowHandle) -> Viewport {
+    let (w, h) = window.win.size();
+    let (fb_w, fb_h) = window.win.drawable_size();
+    Viewport {
+        drawable_size: [fb_w, fb_h],
+        dpi_scale: [fb_w as f32 / w as f32, fb_h as f32 / h as f32],
     }
 }
 
 // boilerplate
 // -----------
 
+/// `--record <file>` or `--replay <file>`
+enum Mode {
+    Run,
+    Record(String),
+    Replay(String),
+}
+
 fn main() -> Result<()> {
     env_logger::init();
-    let (mut app, pump) = self::init()?;
-    runner::run(pump, &mut app, self::on_event, self::on_frame);
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mode = match args.as_slice() {
+        [] => Mode::Run,
+        [flag, path] if flag == "--record" => Mode::Record(path.clone()),
+        [flag, path] if flag == "--replay" => Mode::Replay(path.clone()),
+        _ => bail!("usage: in_rokol [--record <file> | --replay <file>]"),
+    };
+
+    match mode {
+        Mode::Run => {
+            let (mut app, pump) = self::init()?;
+            runner::run(pump, &mut app, self::on_event, self::on_frame);
+        }
+        Mode::Record(path) => {
+            let (mut app, pump) = self::init()?;
+            let mut recorder = runner::Recorder::create(&path)?;
+            runner::run_recording(
+                pump,
+                &mut app,
+                self::on_event,
+                self::on_frame,
+                App::take_viewport,
+                App::state_hash,
+                &mut recorder,
+            );
+            log::info!("recorded {} frames to {}", recorder.n_frames(), path);
+        }
+        Mode::Replay(path) => {
+            // headless: no window, GPU, game controller or hot reload
+            let replay = runner::Replay::load(&path)?;
+            let mut sim = Sim::new(self::actions()?);
+            runner::replay(
+                &replay,
+                &mut sim,
+                Sim::event,
+                |sim, dt| {
+                    sim.update(dt);
+                    sim.end_frame();
+                },
+                Sim::set_viewport,
+                Sim::state_hash,
+            )?;
+            log::info!("replayed {} frames from {}", replay.frames.len(), path);
+        }
+    }
+
     Ok(())
 }
 
@@ -88,7 +284,7 @@ fn init() -> Result<(App, sdl2::EventPump)> {
     .map_err(Error::msg)?;
 
     let pump = window.sdl.event_pump().map_err(Error::msg)?;
-    let app = App::new(window);
+    let app = App::new(window)?;
 
     Ok((app, pump))
 }
@@ -98,7 +294,7 @@ fn on_event(app: &mut App, ev: &Event) {
 }
 
 fn on_frame(app: &mut App, dt: Duration) {
-    app.update();
+    app.update(dt);
     app.render();
     app.end_frame();
 }
@@ -1,10 +1,15 @@
 //! Run game at 60 FPS
 
+mod replay;
+
+pub use self::replay::{Recorder, Replay, ReplayFrame, StateHasher, Viewport};
+
 use std::{
     thread,
     time::{Duration, Instant},
 };
 
+use anyhow::{bail, Result};
 use sdl2::event::{Event, WindowEvent};
 
 /// Runs your game at 60 FPS (trait-free)
@@ -14,17 +19,103 @@ use sdl2::event::{Event, WindowEvent};
 /// Details are in the source code.
 #[inline(always)]
 pub fn run<S>(
+    pump: sdl2::EventPump,
+    state: &mut S,
+    event: impl FnMut(&mut S, &sdl2::event::Event),
+    frame: impl FnMut(&mut S, std::time::Duration),
+) {
+    self::run_impl(pump, state, event, frame, |_, _| {});
+}
+
+/// [`run`] while recording the events and timesteps
+///
+/// After each update, `viewport` takes the viewport applied in the update (if any) and `hash`
+/// hashes the state. Both are recorded so that [`replay`] doesn't depend on the window and can
+/// detect divergence.
+pub fn run_recording<S>(
+    pump: sdl2::EventPump,
+    state: &mut S,
+    event: impl FnMut(&mut S, &sdl2::event::Event),
+    frame: impl FnMut(&mut S, std::time::Duration),
+    mut viewport: impl FnMut(&mut S) -> Option<Viewport>,
+    mut hash: impl FnMut(&S) -> u64,
+    recorder: &mut Recorder,
+) {
+    self::run_impl(pump, state, event, frame, |state, mut frame| {
+        if frame.dt.is_some() {
+            frame.viewport = viewport(state);
+            frame.hash = Some(hash(state));
+        }
+
+        if let Err(err) = recorder.record(&frame) {
+            log::error!("failed to record frame: {:#}", err);
+        }
+    });
+}
+
+/// Runs a recording as fast as possible, in place of the live `EventPump` and real clock
+///
+/// No window is needed if `event` and `frame` don't touch one. Recorded viewports are passed to
+/// `set_viewport` before the update. Returns an error on the first frame whose state hash
+/// differs from the recorded one.
+pub fn replay<S>(
+    replay: &Replay,
+    state: &mut S,
+    mut event: impl FnMut(&mut S, &sdl2::event::Event),
+    mut frame: impl FnMut(&mut S, std::time::Duration),
+    mut set_viewport: impl FnMut(&mut S, Viewport),
+    mut hash: impl FnMut(&S) -> u64,
+) -> Result<()> {
+    for (i, rec) in replay.frames.iter().enumerate() {
+        for ev in &rec.events {
+            if matches!(ev, sdl2::event::Event::Quit { .. }) {
+                return Ok(());
+            }
+            (event)(state, ev);
+        }
+
+        if let Some(dt) = rec.dt {
+            if let Some(vp) = rec.viewport {
+                (set_viewport)(state, vp);
+            }
+            (frame)(state, dt);
+
+            if let Some(expected) = rec.hash {
+                let h = (hash)(state);
+                if h != expected {
+                    bail!(
+                        "replay diverged at frame {}: state hash {:016x}, recorded {:016x}",
+                        i,
+                        h,
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The game loop. `on_frame_end` receives the events and the timestep of each iteration
+#[inline(always)]
+fn run_impl<S>(
     mut pump: sdl2::EventPump,
     state: &mut S,
     mut event: impl FnMut(&mut S, &sdl2::event::Event),
     mut frame: impl FnMut(&mut S, std::time::Duration),
+    mut on_frame_end: impl FnMut(&mut S, ReplayFrame),
 ) {
     let mut runner = self::GameRunner::new();
 
     'game_loop: loop {
+        let mut events = Vec::new();
+
         // 1. poll event
         for ev in pump.poll_iter() {
             if matches!(ev, sdl2::event::Event::Quit { .. }) {
+                events.push(ev);
+                (on_frame_end)(state, ReplayFrame::new(events));
                 break 'game_loop;
             }
 
@@ -32,23 +123,34 @@ pub fn run<S>(
 
             // TODO: filter events while not focused?
             (event)(state, &ev);
+            events.push(ev);
         }
 
         // 2. tick
         let tick = runner.update();
 
         if !tick {
+            (on_frame_end)(state, ReplayFrame::new(events));
             // not focused: wait polling events
             thread::sleep(Duration::from_secs_f32(0.2));
             continue;
         }
 
         // 3. update
-        if let Some(dt) = runner.consume_timestep() {
+        let dt = runner.consume_timestep();
+        if let Some(dt) = dt {
             // focused & update
             (frame)(state, dt);
         }
 
+        (on_frame_end)(
+            state,
+            ReplayFrame {
+                dt,
+                ..ReplayFrame::new(events)
+            },
+        );
+
         // 4. wait until next frame (don't poll events while waiting!)
         if let Some(dt) = runner.wait_duration() {
             self::accurate_sleep(dt);
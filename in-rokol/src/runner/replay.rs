@@ -0,0 +1,800 @@
+//! Recording of SDL events and timesteps for deterministic replay
+//!
+//! A recording is a text file with one line per event, each frame terminated by an `f` line with
+//! the timestep and the state hash. A `v` line records the [`Viewport`] applied in the frame:
+//!
+//! ```text
+//! e key-down 1520 1 32 26 0 0
+//! v 2560 1440 2 2
+//! f 16666666 9c2d0f8e1a4b7735
+//! f - -
+//! ```
+//!
+//! Only events that affect the simulation are recorded (quit, window, keyboard, text, mouse and
+//! game controller events). Others are dropped.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use sdl2::{
+    controller::{Axis, Button},
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod, Scancode},
+    mouse::{MouseButton, MouseState, MouseWheelDirection},
+};
+
+const HEADER: &str = "# in-rokol replay v2";
+
+/// Window metrics the simulation depends on. They differ between machines, so they're recorded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Framebuffer size in pixels
+    pub drawable_size: [u32; 2],
+    /// Framebuffer pixels per window point
+    pub dpi_scale: [f32; 2],
+}
+
+/// Events polled in one iteration of the game loop and the timestep that followed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayFrame {
+    pub events: Vec<Event>,
+    /// Viewport applied before the update
+    pub viewport: Option<Viewport>,
+    /// Timestep from `GameRunner::consume_timestep`
+    pub dt: Option<Duration>,
+    /// State hash after the update (if any)
+    pub hash: Option<u64>,
+}
+
+impl ReplayFrame {
+    /// Frame without update
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events,
+            viewport: None,
+            dt: None,
+            hash: None,
+        }
+    }
+}
+
+/// 64-bit FNV-1a, a state hash that is the same for every build and platform
+///
+/// Values are written as little-endian bytes. Sets have to be written in a stable order.
+#[derive(Debug, Clone)]
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self {
+            hash: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for b in bytes {
+            self.hash ^= *b as u64;
+            self.hash = self.hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        self
+    }
+
+    pub fn write_u32(&mut self, x: u32) -> &mut Self {
+        self.write(&x.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, x: u64) -> &mut Self {
+        self.write(&x.to_le_bytes())
+    }
+
+    /// Bit pattern of the float
+    pub fn write_f32(&mut self, x: f32) -> &mut Self {
+        self.write_u32(x.to_bits())
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Writes frames to a file as they come, so that crashes are recorded, too
+#[derive(Debug)]
+pub struct Recorder {
+    out: BufWriter<File>,
+    n_frames: usize,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("creating recording: {}", path.display()))?;
+
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}", HEADER)?;
+
+        Ok(Self { out, n_frames: 0 })
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.n_frames
+    }
+
+    pub fn record(&mut self, frame: &ReplayFrame) -> Result<()> {
+        for ev in &frame.events {
+            if let Some(line) = self::encode_event(ev) {
+                writeln!(self.out, "e {}", line)?;
+            }
+        }
+
+        if let Some(vp) = &frame.viewport {
+            let ([w, h], [sx, sy]) = (vp.drawable_size, vp.dpi_scale);
+            // `Display` of floats round-trips
+            writeln!(self.out, "v {} {} {} {}", w, h, sx, sy)?;
+        }
+
+        let dt = frame
+            .dt
+            .map_or_else(|| "-".to_string(), |dt| dt.as_nanos().to_string());
+        let hash = frame
+            .hash
+            .map_or_else(|| "-".to_string(), |h| format!("{:016x}", h));
+        writeln!(self.out, "f {} {}", dt, hash)?;
+        self.out.flush()?;
+
+        self.n_frames += 1;
+        Ok(())
+    }
+}
+
+/// Recorded frames loaded from a file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("opening recording: {}", path.display()))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("loading recording: {}", path.display()))
+    }
+
+    pub fn read(src: impl BufRead) -> Result<Self> {
+        let mut lines = src.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        ensure!(header == HEADER, "not a recording (header: {:?})", header);
+
+        let mut frames = Vec::new();
+        let mut events = Vec::new();
+        let mut viewport = None;
+
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            // the header is line 1
+            let ln = i + 2;
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => {}
+                Some("e") => {
+                    let ev = self::decode_event(&mut words)
+                        .with_context(|| format!("line {}: {}", ln, line))?;
+                    events.push(ev);
+                }
+                Some("v") => {
+                    let vp = self::decode_viewport(&mut words)
+                        .with_context(|| format!("line {}: {}", ln, line))?;
+                    viewport = Some(vp);
+                }
+                Some("f") => {
+                    let dt = self::opt(words.next(), |s| s.parse::<u64>().ok())
+                        .with_context(|| format!("line {}: bad timestep", ln))?
+                        .map(Duration::from_nanos);
+                    let hash = self::opt(words.next(), |s| u64::from_str_radix(s, 16).ok())
+                        .with_context(|| format!("line {}: bad hash", ln))?;
+
+                    frames.push(ReplayFrame {
+                        events: std::mem::take(&mut events),
+                        viewport: viewport.take(),
+                        dt,
+                        hash,
+                    });
+                }
+                Some(x) => bail!("line {}: unknown entry `{}`", ln, x),
+            }
+        }
+
+        if !events.is_empty() {
+            // events of a frame cut short by a crash
+            frames.push(ReplayFrame::new(events));
+        }
+
+        Ok(Self { frames })
+    }
+}
+
+/// Parses `-` as `None`
+fn opt<T>(word: Option<&str>, f: impl FnOnce(&str) -> Option<T>) -> Result<Option<T>> {
+    match word {
+        None => Err(anyhow!("missing field")),
+        Some("-") => Ok(None),
+        Some(s) => f(s).map(Some).ok_or_else(|| anyhow!("bad field `{}`", s)),
+    }
+}
+
+fn decode_viewport<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Viewport> {
+    let mut next = || words.next().context("missing field");
+    let size = |s: &str| s.parse::<u32>().map_err(|_| anyhow!("bad size `{}`", s));
+    let scale = |s: &str| s.parse::<f32>().map_err(|_| anyhow!("bad scale `{}`", s));
+
+    Ok(Viewport {
+        drawable_size: [size(next()?)?, size(next()?)?],
+        dpi_scale: [scale(next()?)?, scale(next()?)?],
+    })
+}
+
+fn encode_event(ev: &Event) -> Option<String> {
+    let key = |k: &Option<Keycode>| k.map_or(-1, |k| k as i32);
+    let scan = |s: &Option<Scancode>| s.map_or(-1, |s| s as i32);
+
+    Some(match ev {
+        Event::Quit { timestamp } => format!("quit {}", timestamp),
+        Event::Window {
+            timestamp,
+            window_id,
+            win_event,
+        } => {
+            let (name, d1, d2) = self::encode_window_event(win_event);
+            format!("window {} {} {} {} {}", timestamp, window_id, name, d1, d2)
+        }
+        Event::KeyDown {
+            timestamp,
+            window_id,
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+        } => format!(
+            "key-down {} {} {} {} {} {}",
+            timestamp,
+            window_id,
+            key(keycode),
+            scan(scancode),
+            keymod.bits(),
+            *repeat as u8
+        ),
+        Event::KeyUp {
+            timestamp,
+            window_id,
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+        } => format!(
+            "key-up {} {} {} {} {} {}",
+            timestamp,
+            window_id,
+            key(keycode),
+            scan(scancode),
+            keymod.bits(),
+            *repeat as u8
+        ),
+        Event::TextInput {
+            timestamp,
+            window_id,
+            text,
+        } => format!(
+            "text {} {} {}",
+            timestamp,
+            window_id,
+            self::hex(text.as_bytes())
+        ),
+        Event::MouseMotion {
+            timestamp,
+            window_id,
+            which,
+            mousestate,
+            x,
+            y,
+            xrel,
+            yrel,
+        } => format!(
+            "mouse-motion {} {} {} {} {} {} {} {}",
+            timestamp,
+            window_id,
+            which,
+            mousestate.to_sdl_state(),
+            x,
+            y,
+            xrel,
+            yrel
+        ),
+        Event::MouseButtonDown {
+            timestamp,
+            window_id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } => format!(
+            "mouse-down {} {} {} {} {} {} {}",
+            timestamp, window_id, which, *mouse_btn as u8, clicks, x, y
+        ),
+        Event::MouseButtonUp {
+            timestamp,
+            window_id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } => format!(
+            "mouse-up {} {} {} {} {} {} {}",
+            timestamp, window_id, which, *mouse_btn as u8, clicks, x, y
+        ),
+        Event::MouseWheel {
+            timestamp,
+            window_id,
+            which,
+            x,
+            y,
+            direction,
+        } => format!(
+            "mouse-wheel {} {} {} {} {} {}",
+            timestamp,
+            window_id,
+            which,
+            x,
+            y,
+            direction.to_ll()
+        ),
+        Event::ControllerAxisMotion {
+            timestamp,
+            which,
+            axis,
+            value,
+        } => format!(
+            "pad-axis {} {} {} {}",
+            timestamp,
+            which,
+            axis.string(),
+            value
+        ),
+        Event::ControllerButtonDown {
+            timestamp,
+            which,
+            button,
+        } => format!("pad-down {} {} {}", timestamp, which, button.string()),
+        Event::ControllerButtonUp {
+            timestamp,
+            which,
+            button,
+        } => format!("pad-up {} {} {}", timestamp, which, button.string()),
+        Event::ControllerDeviceAdded { timestamp, which } => {
+            format!("pad-added {} {}", timestamp, which)
+        }
+        Event::ControllerDeviceRemoved { timestamp, which } => {
+            format!("pad-removed {} {}", timestamp, which)
+        }
+        _ => return None,
+    })
+}
+
+fn decode_event<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Event> {
+    let kind = words.next().context("missing event kind")?;
+    let mut next = || words.next().context("missing field");
+
+    macro_rules! num {
+        () => {{
+            let s = next()?;
+            s.parse().map_err(|_| anyhow!("bad number `{}`", s))?
+        }};
+    }
+
+    Ok(match kind {
+        "quit" => Event::Quit { timestamp: num!() },
+        "window" => {
+            let timestamp = num!();
+            let window_id = num!();
+            let name = next()?;
+            let win_event = self::decode_window_event(name, num!(), num!())?;
+            Event::Window {
+                timestamp,
+                window_id,
+                win_event,
+            }
+        }
+        "key-down" | "key-up" => {
+            let timestamp = num!();
+            let window_id = num!();
+            let keycode = Keycode::from_i32(num!());
+            let scancode = Scancode::from_i32(num!());
+            let keymod = Mod::from_bits_truncate(num!());
+            let repeat: u8 = num!();
+            let repeat = repeat != 0;
+            if kind == "key-down" {
+                Event::KeyDown {
+                    timestamp,
+                    window_id,
+                    keycode,
+                    scancode,
+                    keymod,
+                    repeat,
+                }
+            } else {
+                Event::KeyUp {
+                    timestamp,
+                    window_id,
+                    keycode,
+                    scancode,
+                    keymod,
+                    repeat,
+                }
+            }
+        }
+        "text" => Event::TextInput {
+            timestamp: num!(),
+            window_id: num!(),
+            text: String::from_utf8(self::unhex(next()?)?)?,
+        },
+        "mouse-motion" => Event::MouseMotion {
+            timestamp: num!(),
+            window_id: num!(),
+            which: num!(),
+            mousestate: MouseState::from_sdl_state(num!()),
+            x: num!(),
+            y: num!(),
+            xrel: num!(),
+            yrel: num!(),
+        },
+        "mouse-down" | "mouse-up" => {
+            let timestamp = num!();
+            let window_id = num!();
+            let which = num!();
+            let mouse_btn = MouseButton::from_ll(num!());
+            let clicks = num!();
+            let x = num!();
+            let y = num!();
+            if kind == "mouse-down" {
+                Event::MouseButtonDown {
+                    timestamp,
+                    window_id,
+                    which,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            } else {
+                Event::MouseButtonUp {
+                    timestamp,
+                    window_id,
+                    which,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            }
+        }
+        "mouse-wheel" => Event::MouseWheel {
+            timestamp: num!(),
+            window_id: num!(),
+            which: num!(),
+            x: num!(),
+            y: num!(),
+            direction: MouseWheelDirection::from_ll(num!()),
+        },
+        "pad-axis" => {
+            let timestamp = num!();
+            let which = num!();
+            let name = next()?;
+            let axis = Axis::from_string(name).with_context(|| format!("bad axis `{}`", name))?;
+            Event::ControllerAxisMotion {
+                timestamp,
+                which,
+                axis,
+                value: num!(),
+            }
+        }
+        "pad-down" | "pad-up" => {
+            let timestamp = num!();
+            let which = num!();
+            let name = next()?;
+            let button =
+                Button::from_string(name).with_context(|| format!("bad button `{}`", name))?;
+            if kind == "pad-down" {
+                Event::ControllerButtonDown {
+                    timestamp,
+                    which,
+                    button,
+                }
+            } else {
+                Event::ControllerButtonUp {
+                    timestamp,
+                    which,
+                    button,
+                }
+            }
+        }
+        "pad-added" => Event::ControllerDeviceAdded {
+            timestamp: num!(),
+            which: num!(),
+        },
+        "pad-removed" => Event::ControllerDeviceRemoved {
+            timestamp: num!(),
+            which: num!(),
+        },
+        _ => bail!("unknown event `{}`", kind),
+    })
+}
+
+fn encode_window_event(ev: &WindowEvent) -> (&'static str, i32, i32) {
+    match *ev {
+        WindowEvent::None => ("none", 0, 0),
+        WindowEvent::Shown => ("shown", 0, 0),
+        WindowEvent::Hidden => ("hidden", 0, 0),
+        WindowEvent::Exposed => ("exposed", 0, 0),
+        WindowEvent::Moved(x, y) => ("moved", x, y),
+        WindowEvent::Resized(w, h) => ("resized", w, h),
+        WindowEvent::SizeChanged(w, h) => ("size-changed", w, h),
+        WindowEvent::Minimized => ("minimized", 0, 0),
+        WindowEvent::Maximized => ("maximized", 0, 0),
+        WindowEvent::Restored => ("restored", 0, 0),
+        WindowEvent::Enter => ("enter", 0, 0),
+        WindowEvent::Leave => ("leave", 0, 0),
+        WindowEvent::FocusGained => ("focus-gained", 0, 0),
+        WindowEvent::FocusLost => ("focus-lost", 0, 0),
+        WindowEvent::Close => ("close", 0, 0),
+        WindowEvent::TakeFocus => ("take-focus", 0, 0),
+        WindowEvent::HitTest => ("hit-test", 0, 0),
+    }
+}
+
+fn decode_window_event(name: &str, d1: i32, d2: i32) -> Result<WindowEvent> {
+    Ok(match name {
+        "none" => WindowEvent::None,
+        "shown" => WindowEvent::Shown,
+        "hidden" => WindowEvent::Hidden,
+        "exposed" => WindowEvent::Exposed,
+        "moved" => WindowEvent::Moved(d1, d2),
+        "resized" => WindowEvent::Resized(d1, d2),
+        "size-changed" => WindowEvent::SizeChanged(d1, d2),
+        "minimized" => WindowEvent::Minimized,
+        "maximized" => WindowEvent::Maximized,
+        "restored" => WindowEvent::Restored,
+        "enter" => WindowEvent::Enter,
+        "leave" => WindowEvent::Leave,
+        "focus-gained" => WindowEvent::FocusGained,
+        "focus-lost" => WindowEvent::FocusLost,
+        "close" => WindowEvent::Close,
+        "take-focus" => WindowEvent::TakeFocus,
+        "hit-test" => WindowEvent::HitTest,
+        _ => bail!("unknown window event `{}`", name),
+    })
+}
+
+/// Text is hex-encoded so that it can't break lines. `-` for empty text
+fn hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "-".to_string();
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Result<Vec<u8>> {
+    if s == "-" {
+        return Ok(Vec::new());
+    }
+
+    // bytes, so that non-ASCII input can't split a character
+    let bytes = s.as_bytes();
+    ensure!(bytes.len() & 1 == 0, "bad hex `{}`", s);
+    let digit = |b: u8| (b as char).to_digit(16);
+    bytes
+        .chunks_exact(2)
+        .map(|pair| match (digit(pair[0]), digit(pair[1])) {
+            (Some(hi), Some(lo)) => Ok((hi << 4 | lo) as u8),
+            _ => Err(anyhow!("bad hex `{}`", s)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(kind: &str, timestamp: u32, key: Keycode, sc: Scancode) -> Event {
+        let (keycode, scancode, keymod) = (Some(key), Some(sc), Mod::NOMOD);
+        match kind {
+            "down" => Event::KeyDown {
+                timestamp,
+                window_id: 1,
+                keycode,
+                scancode,
+                keymod,
+                repeat: false,
+            },
+            _ => Event::KeyUp {
+                timestamp,
+                window_id: 1,
+                keycode,
+                scancode,
+                keymod,
+                repeat: false,
+            },
+        }
+    }
+
+    /// Keys held and time elapsed
+    #[derive(Default)]
+    struct Toy {
+        down: Vec<i32>,
+        elapsed: Duration,
+        viewport: Option<Viewport>,
+    }
+
+    impl Toy {
+        fn event(&mut self, ev: &Event) {
+            match ev {
+                Event::KeyDown {
+                    scancode: Some(sc), ..
+                } => self.down.push(*sc as i32),
+                Event::KeyUp {
+                    scancode: Some(sc), ..
+                } => self.down.retain(|x| *x != *sc as i32),
+                _ => {}
+            }
+        }
+
+        fn hash(&self) -> u64 {
+            let mut h = StateHasher::new();
+            h.write_u64(self.elapsed.as_nanos() as u64);
+            h.write_u32(self.down.len() as u32);
+            for sc in &self.down {
+                h.write_u32(*sc as u32);
+            }
+            if let Some(vp) = &self.viewport {
+                h.write_u32(vp.drawable_size[0]).write_f32(vp.dpi_scale[0]);
+            }
+            h.finish()
+        }
+    }
+
+    /// Runs the frames on a fresh state, filling in the hashes like `run_recording`
+    fn simulate(frames: &mut [ReplayFrame]) {
+        let mut toy = Toy::default();
+        for frame in frames {
+            for ev in &frame.events {
+                toy.event(ev);
+            }
+            if let Some(dt) = frame.dt {
+                if let Some(vp) = frame.viewport {
+                    toy.viewport = Some(vp);
+                }
+                toy.elapsed += dt;
+                frame.hash = Some(toy.hash());
+            }
+        }
+    }
+
+    fn frames() -> Vec<ReplayFrame> {
+        let dt = Duration::from_nanos(1_000_000_000 / 60);
+        let mut frames = vec![
+            ReplayFrame {
+                dt: Some(dt),
+                viewport: Some(Viewport {
+                    drawable_size: [2560, 1440],
+                    dpi_scale: [2.0, 2.0],
+                }),
+                ..ReplayFrame::new(vec![key("down", 10, Keycode::A, Scancode::A)])
+            },
+            // unfocused
+            ReplayFrame::new(vec![Event::TextInput {
+                timestamp: 20,
+                window_id: 1,
+                text: "aé".to_string(),
+            }]),
+            ReplayFrame {
+                dt: Some(dt * 2),
+                ..ReplayFrame::new(vec![
+                    key("down", 30, Keycode::Z, Scancode::Z),
+                    key("up", 31, Keycode::A, Scancode::A),
+                ])
+            },
+            ReplayFrame {
+                dt: Some(dt),
+                ..ReplayFrame::new(vec![])
+            },
+        ];
+        self::simulate(&mut frames);
+        frames
+    }
+
+    #[test]
+    fn fnv1a() {
+        assert_eq!(StateHasher::new().finish(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(
+            StateHasher::new().write(b"a").finish(),
+            0xaf63_dc4c_8601_ec8c
+        );
+        assert_eq!(
+            StateHasher::new().write(b"foobar").finish(),
+            0x8594_4171_f739_67e8
+        );
+    }
+
+    #[test]
+    fn write_read_round_trip() -> Result<()> {
+        let frames = self::frames();
+
+        let path = std::env::temp_dir().join(format!("in-rokol-replay-{}.txt", std::process::id()));
+        let mut recorder = Recorder::create(&path)?;
+        for frame in &frames {
+            recorder.record(frame)?;
+        }
+        assert_eq!(recorder.n_frames(), frames.len());
+        drop(recorder);
+
+        let replay = Replay::load(&path);
+        std::fs::remove_file(&path)?;
+        assert_eq!(replay?.frames, frames);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_checks_state_hash() -> Result<()> {
+        let mut replay = Replay {
+            frames: self::frames(),
+        };
+
+        let run = |replay: &Replay| {
+            let mut toy = Toy::default();
+            crate::runner::replay(
+                replay,
+                &mut toy,
+                Toy::event,
+                |toy, dt| toy.elapsed += dt,
+                |toy, vp| toy.viewport = Some(vp),
+                Toy::hash,
+            )
+        };
+        run(&replay)?;
+
+        let last = replay.frames.last_mut().unwrap();
+        last.hash = last.hash.map(|h| h ^ 1);
+        let err = run(&replay).unwrap_err();
+        assert!(err.to_string().contains("frame 3"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unhex_rejects_bad_input() {
+        assert_eq!(unhex("-").unwrap(), b"");
+        assert_eq!(unhex("61c3a9").unwrap(), "aé".as_bytes());
+        assert!(unhex("6").is_err());
+        assert!(unhex("zz").is_err());
+        // two bytes, one character: must not panic on a char boundary
+        assert!(unhex("é").is_err());
+        assert!(unhex("aé").is_err());
+    }
+}
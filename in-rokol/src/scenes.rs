@@ -0,0 +1,220 @@
+/*!
+Example scenes for the GLSL shaders in `src/glsl`
+
+Run them with `cargo run --example <name>` (`quad`, `cube`, `cube_multi`, `more_cubes` or
+`texture_multi`).
+*/
+
+mod cube;
+mod cube_multi;
+mod more_cubes;
+mod quad;
+mod texture_multi;
+
+pub use self::{
+    cube::CubeScene, cube_multi::CubeMultiScene, more_cubes::MoreCubesScene, quad::QuadScene,
+    texture_multi::TextureMultiScene,
+};
+
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use rokol::{
+    gfx as rg,
+    glue::sdl::{Init, WindowHandle},
+};
+use sdl2::event::Event;
+use vek::{Mat4, Vec3};
+
+use crate::{
+    gfx::{Texture2dDrop, TextureBuilder},
+    runner,
+    shaders::TexturedVertex,
+};
+
+/// Screen size
+pub const W: u32 = 1280;
+pub const H: u32 = 720;
+
+/// Example scene driven by [`run`]
+pub trait Scene: Sized {
+    fn new() -> Result<Self>;
+
+    fn pass_action(&self) -> rg::PassAction {
+        rg::PassAction::clear([100.0 / 255.0, 149.0 / 255.0, 237.0 / 255.0, 1.0])
+    }
+
+    fn event(&mut self, _ev: &Event) {}
+
+    fn update(&mut self, _dt: Duration) {}
+
+    /// Called inside the default pass
+    fn render(&mut self);
+}
+
+/// Opens a window and runs the scene until quit
+pub fn run<S: Scene>(title: &str) -> Result<()> {
+    let window = Init {
+        title: title.to_string(),
+        w: W,
+        h: H,
+        ..Default::default()
+    }
+    .init(|_b| {})
+    .map_err(Error::msg)?;
+
+    let pump = window.sdl.event_pump().map_err(Error::msg)?;
+    let mut state: (WindowHandle, S) = (window, S::new()?);
+
+    runner::run(
+        pump,
+        &mut state,
+        |state, ev| state.1.event(ev),
+        |state, dt| {
+            let (window, scene) = state;
+            scene.update(dt);
+
+            rg::begin_default_pass(&scene.pass_action(), W, H);
+            scene.render();
+            rg::end_pass();
+
+            rg::commit();
+            window.swap_window();
+        },
+    );
+
+    Ok(())
+}
+
+/// Perspective camera looking at a point
+#[derive(Debug, Clone)]
+pub struct Camera3d {
+    pub eye: Vec3<f32>,
+    pub target: Vec3<f32>,
+    pub up: Vec3<f32>,
+    /// Vertical field of view in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Camera3d {
+    fn default() -> Self {
+        Self {
+            eye: Vec3::new(0.0, 1.5, 4.0),
+            target: Vec3::zero(),
+            up: Vec3::unit_y(),
+            fov_y: 60f32.to_radians(),
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+impl Camera3d {
+    /// Projection * view (OpenGL clip space)
+    pub fn proj_view(&self) -> Mat4<f32> {
+        let proj = Mat4::perspective_fov_rh_no(self.fov_y, W as f32, H as f32, self.near, self.far);
+        let view = Mat4::look_at_rh(self.eye, self.target, self.up);
+        proj * view
+    }
+}
+
+/// Unit cube centered at the origin with counter-clockwise front faces and per-face UVs
+pub fn cube_mesh() -> (Vec<TexturedVertex>, Vec<u16>) {
+    // (normal, u axis, v axis) where u x v = normal
+    let faces: [([f32; 3], [f32; 3], [f32; 3], [u8; 4]); 6] = [
+        (
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [0.0, 1.0, 0.0],
+            [255, 128, 128, 255],
+        ),
+        (
+            [-1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [128, 255, 255, 255],
+        ),
+        (
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [128, 255, 128, 255],
+        ),
+        (
+            [0.0, -1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [255, 128, 255, 255],
+        ),
+        (
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [128, 128, 255, 255],
+        ),
+        (
+            [0.0, 0.0, -1.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [255, 255, 128, 255],
+        ),
+    ];
+
+    let mut verts = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+
+    for (n, u, v, color) in faces.iter() {
+        let (n, u, v) = (Vec3::from(*n), Vec3::from(*u), Vec3::from(*v));
+        let c = n * 0.5;
+        let base = verts.len() as u16;
+
+        // (corner, uv) from the bottom-left in counter-clockwise order
+        let corners: [(Vec3<f32>, [f32; 2]); 4] = [
+            (c - u * 0.5 - v * 0.5, [0.0, 1.0]),
+            (c + u * 0.5 - v * 0.5, [1.0, 1.0]),
+            (c + u * 0.5 + v * 0.5, [1.0, 0.0]),
+            (c - u * 0.5 + v * 0.5, [0.0, 0.0]),
+        ];
+
+        for (p, uv) in corners.iter() {
+            verts.push(TexturedVertex::from((p.into_array(), *color, *uv)));
+        }
+
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    (verts, indices)
+}
+
+/// Checkerboard texture (no image assets are needed for the examples)
+pub fn checker_texture(size: u32, cell: u32, a: [u8; 4], b: [u8; 4]) -> Texture2dDrop {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let color = if (x / cell + y / cell) % 2 == 0 { a } else { b };
+            pixels.extend_from_slice(&color);
+        }
+    }
+
+    let mut builder = TextureBuilder::from_pixels(&pixels, size, size);
+    builder.filter(rg::Filter::Nearest);
+    builder.build_texture()
+}
+
+/// Radial gradient texture, opaque at the center and transparent at the edges
+pub fn radial_texture(size: u32, color: [u8; 3]) -> Texture2dDrop {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    let half = size as f32 / 2.0;
+    for y in 0..size {
+        for x in 0..size {
+            let dx = (x as f32 + 0.5 - half) / half;
+            let dy = (y as f32 + 0.5 - half) / half;
+            let t = (1.0 - (dx * dx + dy * dy).sqrt()).max(0.0);
+            pixels.extend_from_slice(&[color[0], color[1], color[2], (t * 255.0) as u8]);
+        }
+    }
+
+    TextureBuilder::from_pixels(&pixels, size, size).build_texture()
+}
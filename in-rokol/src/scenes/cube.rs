@@ -0,0 +1,54 @@
+//! `cube` shader: rotating textured cube
+
+use std::time::Duration;
+
+use anyhow::Result;
+use vek::Mat4;
+
+use crate::{
+    gfx::{Shader, StaticMesh, Texture2dDrop},
+    scenes::{self, Camera3d, Scene},
+    shaders::{self, TexturedVertex},
+    utils::as_bytes,
+};
+
+#[derive(Debug)]
+pub struct CubeScene {
+    shd: Shader,
+    mesh: StaticMesh<TexturedVertex>,
+    _tex: Texture2dDrop,
+    cam: Camera3d,
+    /// Rotation in radians
+    angle: f32,
+}
+
+impl Scene for CubeScene {
+    fn new() -> Result<Self> {
+        let (verts, indices) = scenes::cube_mesh();
+        let mut mesh = StaticMesh::new_16(&verts, &indices);
+
+        let tex = scenes::checker_texture(64, 8, [255, 255, 255, 255], [64, 64, 64, 255]);
+        mesh.bind_img(tex.img(), 0);
+
+        Ok(Self {
+            shd: shaders::cube()?,
+            mesh,
+            _tex: tex,
+            cam: Camera3d::default(),
+            angle: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.angle += dt.as_secs_f32();
+    }
+
+    fn render(&mut self) {
+        let model = Mat4::rotation_y(self.angle) * Mat4::rotation_x(self.angle * 0.5);
+        let mvp = self.cam.proj_view() * model;
+
+        self.shd.apply_pip();
+        self.shd.set_vs_uniform(0, as_bytes(&mvp.into_col_array()));
+        self.mesh.draw_all();
+    }
+}
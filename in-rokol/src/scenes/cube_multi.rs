@@ -0,0 +1,56 @@
+//! `cube_multi` shader: rotating cube blending two textures
+
+use std::time::Duration;
+
+use anyhow::Result;
+use vek::Mat4;
+
+use crate::{
+    gfx::{Shader, StaticMesh, Texture2dDrop},
+    scenes::{self, Camera3d, Scene},
+    shaders::{self, TexturedVertex},
+    utils::as_bytes,
+};
+
+#[derive(Debug)]
+pub struct CubeMultiScene {
+    shd: Shader,
+    mesh: StaticMesh<TexturedVertex>,
+    _texs: [Texture2dDrop; 2],
+    cam: Camera3d,
+    /// Rotation in radians
+    angle: f32,
+}
+
+impl Scene for CubeMultiScene {
+    fn new() -> Result<Self> {
+        let (verts, indices) = scenes::cube_mesh();
+        let mut mesh = StaticMesh::new_16(&verts, &indices);
+
+        let tex1 = scenes::checker_texture(64, 8, [255, 255, 255, 255], [64, 64, 64, 255]);
+        let tex2 = scenes::radial_texture(64, [255, 160, 0]);
+        mesh.bind_img(tex1.img(), 0);
+        mesh.bind_img(tex2.img(), 1);
+
+        Ok(Self {
+            shd: shaders::cube_multi()?,
+            mesh,
+            _texs: [tex1, tex2],
+            cam: Camera3d::default(),
+            angle: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.angle += dt.as_secs_f32();
+    }
+
+    fn render(&mut self) {
+        let model = Mat4::rotation_y(self.angle) * Mat4::rotation_z(self.angle * 0.3);
+        let mvp = self.cam.proj_view() * model;
+
+        self.shd.apply_pip();
+        self.shd.set_vs_uniform(0, as_bytes(&mvp.into_col_array()));
+        self.mesh.draw_all();
+    }
+}
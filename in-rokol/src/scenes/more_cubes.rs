@@ -0,0 +1,74 @@
+//! `more_cubes` shader: a grid of cubes sharing one mesh, drawn with per-cube `mvp`
+
+use std::time::Duration;
+
+use anyhow::Result;
+use vek::{Mat4, Vec3};
+
+use crate::{
+    gfx::{Shader, StaticMesh, Texture2dDrop},
+    scenes::{self, Camera3d, Scene},
+    shaders::{self, TexturedVertex},
+    utils::as_bytes,
+};
+
+/// Cubes per row and column
+const GRID: i32 = 5;
+
+#[derive(Debug)]
+pub struct MoreCubesScene {
+    shd: Shader,
+    mesh: StaticMesh<TexturedVertex>,
+    _texs: [Texture2dDrop; 2],
+    cam: Camera3d,
+    /// Elapsed time in seconds
+    time: f32,
+}
+
+impl Scene for MoreCubesScene {
+    fn new() -> Result<Self> {
+        let (verts, indices) = scenes::cube_mesh();
+        let mut mesh = StaticMesh::new_16(&verts, &indices);
+
+        let tex1 = scenes::checker_texture(64, 16, [200, 230, 255, 255], [40, 60, 120, 255]);
+        let tex2 = scenes::radial_texture(64, [255, 255, 255]);
+        mesh.bind_img(tex1.img(), 0);
+        mesh.bind_img(tex2.img(), 1);
+
+        Ok(Self {
+            shd: shaders::more_cubes()?,
+            mesh,
+            _texs: [tex1, tex2],
+            cam: Camera3d {
+                eye: Vec3::new(0.0, 6.0, 9.0),
+                ..Default::default()
+            },
+            time: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.time += dt.as_secs_f32();
+    }
+
+    fn render(&mut self) {
+        let proj_view = self.cam.proj_view();
+        self.shd.apply_pip();
+
+        for z in 0..GRID {
+            for x in 0..GRID {
+                let pos = Vec3::new(
+                    (x - GRID / 2) as f32 * 1.8,
+                    0.0,
+                    (z - GRID / 2) as f32 * 1.8,
+                );
+                let angle = self.time + (x + z * GRID) as f32 * 0.4;
+                let model = Mat4::<f32>::translation_3d(pos) * Mat4::rotation_y(angle);
+                let mvp = proj_view * model;
+
+                self.shd.set_vs_uniform(0, as_bytes(&mvp.into_col_array()));
+                self.mesh.draw_all();
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! `quad` shader: vertex colors only
+
+use anyhow::Result;
+
+use crate::{
+    gfx::{Shader, StaticMesh},
+    scenes::Scene,
+    shaders::{self, TriangleVertex},
+};
+
+#[derive(Debug)]
+pub struct QuadScene {
+    shd: Shader,
+    mesh: StaticMesh<TriangleVertex>,
+}
+
+impl Scene for QuadScene {
+    fn new() -> Result<Self> {
+        let verts: &[TriangleVertex] = &[
+            // (vertex, color)
+            ([-0.5, 0.5, 0.5], [1.0, 0.0, 0.0, 1.0]).into(), // top left
+            ([0.5, 0.5, 0.5], [0.0, 1.0, 0.0, 1.0]).into(),  // top right
+            ([0.5, -0.5, 0.5], [0.0, 0.0, 1.0, 1.0]).into(), // bottom right
+            ([-0.5, -0.5, 0.5], [1.0, 1.0, 0.0, 1.0]).into(), // bottom left
+        ];
+        let indices: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+        Ok(Self {
+            shd: shaders::quad()?,
+            mesh: StaticMesh::new_16(verts, indices),
+        })
+    }
+
+    fn render(&mut self) {
+        self.shd.apply_pip();
+        self.mesh.draw_all();
+    }
+}
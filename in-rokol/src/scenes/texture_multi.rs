@@ -0,0 +1,73 @@
+//! `texture_multi` shader: 2D quad blending two textures
+
+use std::time::Duration;
+
+use anyhow::Result;
+use vek::{Mat4, Vec3};
+
+use crate::{
+    gfx::{Shader, StaticMesh, Texture2dDrop},
+    scenes::{self, Scene, H, W},
+    shaders::{self, TexturedVertex},
+    utils::as_bytes,
+};
+
+#[derive(Debug)]
+pub struct TextureMultiScene {
+    shd: Shader,
+    mesh: StaticMesh<TexturedVertex>,
+    _texs: [Texture2dDrop; 2],
+    /// Elapsed time in seconds
+    time: f32,
+}
+
+impl Scene for TextureMultiScene {
+    fn new() -> Result<Self> {
+        // 256x256 pixels quad centered at the origin
+        let verts: &[TexturedVertex] = &[
+            // (pos, color, uv)
+            ([-128.0, -128.0, 0.0], [255, 255, 255, 255], [0.0, 1.0]).into(),
+            ([128.0, -128.0, 0.0], [255, 255, 255, 255], [1.0, 1.0]).into(),
+            ([128.0, 128.0, 0.0], [255, 255, 255, 255], [1.0, 0.0]).into(),
+            ([-128.0, 128.0, 0.0], [255, 255, 255, 255], [0.0, 0.0]).into(),
+        ];
+        let indices: &[u16] = &[0, 1, 2, 0, 2, 3];
+        let mut mesh = StaticMesh::new_16(verts, indices);
+
+        let tex1 = scenes::checker_texture(32, 4, [255, 255, 255, 255], [0, 0, 0, 255]);
+        let tex2 = scenes::radial_texture(64, [255, 64, 64]);
+        mesh.bind_img(tex1.img(), 0);
+        mesh.bind_img(tex2.img(), 1);
+
+        Ok(Self {
+            shd: shaders::texture_multi()?,
+            mesh,
+            _texs: [tex1, tex2],
+            time: 0.0,
+        })
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.time += dt.as_secs_f32();
+    }
+
+    fn render(&mut self) {
+        // pixel coordinates with the origin at the screen center
+        let (w, h) = (W as f32 / 2.0, H as f32 / 2.0);
+        let proj = Mat4::orthographic_rh_no(vek::FrustumPlanes {
+            left: -w,
+            right: w,
+            bottom: -h,
+            top: h,
+            near: -1.0,
+            far: 1.0,
+        });
+        let model = Mat4::rotation_z(self.time * 0.5)
+            * Mat4::<f32>::scaling_3d(Vec3::broadcast(1.0 + 0.25 * self.time.sin()));
+        let mvp = proj * model;
+
+        self.shd.apply_pip();
+        self.shd.set_vs_uniform(0, as_bytes(&mvp.into_col_array()));
+        self.mesh.draw_all();
+    }
+}
@@ -1,9 +1,21 @@
 //! GLSL shaders
-
-// TODO: consider supporting `sokol_shdc.h`
+//!
+//! Uniform blocks and images of the [`rg::ShaderDesc`] are reflected from the sources (see
+//! [`in_common::reflect`]): the loose `uniform`s of a stage make up its uniform block 0 and the
+//! samplers are the image slots in declaration order.
 
 #![allow(unused)]
 
+use std::ffi::CString;
+
+use anyhow::{ensure, Context, Result};
+use in_common::{
+    blend::{BlendFactor, BlendMode},
+    mesh::MeshVertex,
+    preprocess::{Defines, Preprocessor, ShaderSource},
+    reflect::{ImageKind, StageReflection},
+    uniform::UniformType,
+};
 use rokol::gfx::{self as rg, BakedResource, LayoutDesc};
 
 use crate::gfx::Shader;
@@ -40,48 +52,123 @@ macro_rules! embed_shd {
     };
 }
 
-/// Generates [`Shader`]
-fn gen(
-    vs_fs: &[impl AsRef<str>; 2],
-    mut_shd_desc: impl Fn(&mut rg::ShaderDesc),
-    pip_desc: &mut rg::PipelineDesc,
-) -> Shader {
+/// Preprocesses `<name>.vs` and `<name>.fs` under the root of `pre` (see
+/// [`in_common::preprocess`])
+///
+/// Pass the sources through [`nul_terminated`] to the `*_from` functions. Keep them to map the
+/// line numbers in GLSL compiler logs with [`ShaderSource::map_error`].
+pub fn preprocess_glsl(
+    pre: &mut Preprocessor,
+    name: &str,
+    defines: &Defines,
+) -> Result<[ShaderSource; 2]> {
+    let vs = pre.permutation(format!("{}.vs", name), defines)?.clone();
+    let fs = pre.permutation(format!("{}.fs", name), defines)?.clone();
+    Ok([vs, fs])
+}
+
+/// Null-terminated vertex and fragment shader code
+pub fn nul_terminated(vs_fs: &[ShaderSource; 2]) -> [String; 2] {
+    [&vs_fs[0], &vs_fs[1]].map(|src| {
+        let mut code = src.code().to_string();
+        code.push('\0');
+        code
+    })
+}
+
+/// Generates [`Shader`] with the uniform blocks and images reflected from the sources
+fn gen(vs_fs: &[impl AsRef<str>; 2], pip_desc: &mut rg::PipelineDesc) -> Result<Shader> {
+    let vs = StageReflection::parse(vs_fs[0].as_ref()).context("reflecting vertex shader")?;
+    let fs = StageReflection::parse(vs_fs[1].as_ref()).context("reflecting fragment shader")?;
+
     let mut shd_desc = unsafe { rokol::gfx::shader_desc(vs_fs[0].as_ref(), vs_fs[1].as_ref()) };
-    mut_shd_desc(&mut shd_desc);
+    // owns the names referred to by `shd_desc`
+    let mut names = Vec::new();
+    self::reflect_stage(&vs, &mut shd_desc.vs, &mut names).context("vertex shader")?;
+    self::reflect_stage(&fs, &mut shd_desc.fs, &mut names).context("fragment shader")?;
 
     let shd = rg::Shader::create(&shd_desc);
 
     pip_desc.shader = shd;
     let pip = rg::Pipeline::create(&pip_desc);
 
-    Shader::new(shd, pip)
+    // dropped (destroyed) on failure
+    let shader = Shader::with_desc(shd, pip, &shd_desc).with_reflection([vs, fs]);
+    shader.check_state()?;
+    Ok(shader)
 }
 
-/// Sets image type
-macro_rules! img_type {
-    ($name:expr,$ty:expr) => {
-        rg::ShaderImageDesc {
-            name: concat!($name, "\0").as_ptr() as *const _,
-            image_type: $ty.to_ffi(),
-            ..Default::default()
-        }
-    };
-}
+/// Fills uniform block 0 and the images of a stage
+fn reflect_stage(
+    refl: &StageReflection,
+    stage: &mut rg::ShaderStageDesc,
+    names: &mut Vec<CString>,
+) -> Result<()> {
+    let block = &mut stage.uniform_blocks[0];
+    ensure!(
+        refl.uniforms.len() <= block.uniforms.len(),
+        "{} uniforms, at most {} are supported",
+        refl.uniforms.len(),
+        block.uniforms.len()
+    );
 
-/// Single-value uniform block
-macro_rules! ub {
-    ($name:expr, $uniform_ty:expr, $size_ty:ty) => {{
-        let mut block = rg::ShaderUniformBlockDesc::default();
+    // sokol's GL backend reads the uniforms tightly packed, so the std140 layout of the Rust
+    // side must not have padding
+    refl.check_packed()?;
 
-        block.uniforms[0] = rg::ShaderUniformDesc {
-            name: concat!($name, "\0").as_ptr() as *const _,
-            type_: $uniform_ty.to_ffi(),
+    for (desc, u) in block.uniforms.iter_mut().zip(&refl.uniforms) {
+        *desc = rg::ShaderUniformDesc {
+            name: self::c_name(&u.name, names),
+            type_: self::uniform_type(u.ty)
+                .with_context(|| format!("unsupported type of uniform `{}`", u.name))?
+                .to_ffi(),
+            array_count: u.count as _,
             ..Default::default()
         };
-        block.size += std::mem::size_of::<$size_ty>().to_ffi();
+    }
+    block.size = refl.block_size() as _;
+
+    ensure!(
+        refl.images.len() <= stage.images.len(),
+        "{} images, at most {} are supported",
+        refl.images.len(),
+        stage.images.len()
+    );
+    for (desc, img) in stage.images.iter_mut().zip(&refl.images) {
+        *desc = rg::ShaderImageDesc {
+            name: self::c_name(&img.name, names),
+            image_type: self::image_type(img.kind).to_ffi(),
+            ..Default::default()
+        };
+    }
 
-        block
-    }};
+    Ok(())
+}
+
+/// Null-terminated copy of a (reflected, so nul-free) name, kept alive in `names`
+fn c_name(name: &str, names: &mut Vec<CString>) -> *const std::os::raw::c_char {
+    names.push(CString::new(name).unwrap());
+    names.last().unwrap().as_ptr()
+}
+
+fn uniform_type(ty: UniformType) -> Option<rg::UniformType> {
+    Some(match ty {
+        UniformType::Float => rg::UniformType::Float,
+        UniformType::Vec2 => rg::UniformType::Float2,
+        UniformType::Vec3 => rg::UniformType::Float3,
+        UniformType::Vec4 => rg::UniformType::Float4,
+        UniformType::Mat4 => rg::UniformType::Mat4,
+        _ => return None,
+    })
+}
+
+fn image_type(kind: ImageKind) -> rg::ImageType {
+    match kind {
+        ImageKind::Dim2 => rg::ImageType::Dim2,
+        ImageKind::Array => rg::ImageType::Array,
+        ImageKind::Dim3 => rg::ImageType::Dim3,
+        ImageKind::Cube => rg::ImageType::Cube,
+    }
 }
 
 /// (position, color) vertex
@@ -116,10 +203,14 @@ where
     }
 }
 
-pub fn triangle() -> Shader {
+pub fn triangle() -> Result<Shader> {
+    self::triangle_from(&def_shd!("triangle"))
+}
+
+/// [`triangle`] from null-terminated sources (see [`crate::gfx::Assets::shader`])
+pub fn triangle_from(vs_fs: &[String; 2]) -> Result<Shader> {
     gen(
-        &def_shd!("triangle"),
-        |_shd| {},
+        vs_fs,
         &mut rg::PipelineDesc {
             index_type: rg::IndexType::UInt16.to_ffi(),
             layout: TriangleVertex::layout_desc(),
@@ -166,33 +257,186 @@ where
     }
 }
 
-fn alpha_blend() -> rg::BlendState {
+impl From<MeshVertex> for TexturedVertex {
+    /// Drops the normal
+    fn from(v: MeshVertex) -> Self {
+        Self {
+            pos: v.pos.into_array(),
+            color: v
+                .color
+                .map(|x| (x.max(0.0).min(1.0) * 255.0).round() as u8)
+                .into_array(),
+            uv: v.uv.into_array(),
+        }
+    }
+}
+
+/// Blend state of a preset. Disabled for [`BlendMode::Opaque`]
+pub fn blend_state(mode: BlendMode) -> rg::BlendState {
+    let eq = match mode.equation() {
+        Some(eq) => eq,
+        None => return rg::BlendState::default(),
+    };
+
     rg::BlendState {
         enabled: true,
-        src_factor_rgb: rg::BlendFactor::SrcAlpha.to_ffi(),
-        dst_factor_rgb: rg::BlendFactor::OneMinusSrcAlpha.to_ffi(),
+        src_factor_rgb: self::blend_factor(eq.color.src).to_ffi(),
+        dst_factor_rgb: self::blend_factor(eq.color.dst).to_ffi(),
         op_rgb: rg::BlendOp::_Default.to_ffi(),
-        src_factor_alpha: rg::BlendFactor::One.to_ffi(),
-        dst_factor_alpha: rg::BlendFactor::Zero.to_ffi(),
+        src_factor_alpha: self::blend_factor(eq.alpha.src).to_ffi(),
+        dst_factor_alpha: self::blend_factor(eq.alpha.dst).to_ffi(),
         op_alpha: rg::BlendOp::_Default.to_ffi(),
     }
 }
 
-pub fn texture() -> Shader {
+fn blend_factor(f: BlendFactor) -> rg::BlendFactor {
+    match f {
+        BlendFactor::Zero => rg::BlendFactor::Zero,
+        BlendFactor::One => rg::BlendFactor::One,
+        BlendFactor::SrcColor => rg::BlendFactor::SrcColor,
+        BlendFactor::OneMinusSrcColor => rg::BlendFactor::OneMinusSrcColor,
+        BlendFactor::SrcAlpha => rg::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => rg::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstColor => rg::BlendFactor::DstColor,
+        BlendFactor::OneMinusDstColor => rg::BlendFactor::OneMinusDstColor,
+        BlendFactor::DstAlpha => rg::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => rg::BlendFactor::OneMinusDstAlpha,
+    }
+}
+
+/// Textured 2D vertices with alpha blending
+pub fn texture() -> Result<Shader> {
+    self::texture_from(&def_shd!("texture"))
+}
+
+/// [`texture`] from null-terminated sources (see [`crate::gfx::Assets::shader`])
+pub fn texture_from(vs_fs: &[String; 2]) -> Result<Shader> {
+    self::texture_with(vs_fs, BlendMode::Alpha)
+}
+
+/// [`texture`] with a blend mode, e.g. [`BlendMode::Premultiplied`] for textures built with
+/// [`TextureBuilder::premultiply_alpha`](crate::gfx::TextureBuilder::premultiply_alpha)
+pub fn texture_with(vs_fs: &[String; 2], blend: BlendMode) -> Result<Shader> {
+    gen(vs_fs, &mut {
+        let mut pip = rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TexturedVertex::layout_desc(),
+            cull_mode: rg::CullMode::None.to_ffi(),
+            ..Default::default()
+        };
+        pip.colors[0].blend = self::blend_state(blend);
+        pip
+    })
+}
+
+/// Depth test for 3D scenes (the default pass clears depth to `1.0`)
+fn depth_test() -> rg::DepthState {
+    rg::DepthState {
+        compare: rg::CompareFunc::LessEqual.to_ffi(),
+        write_enabled: true,
+        ..Default::default()
+    }
+}
+
+/// (position, color) vertices without any uniform
+///
+/// Use [`TriangleVertex`].
+pub fn quad() -> Result<Shader> {
     gen(
-        &def_shd!("texture"),
-        |shd| {
-            shd.fs.images[0] = img_type!("tex", rg::ImageType::Dim2);
+        &def_shd!("quad"),
+        &mut rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TriangleVertex::layout_desc(),
+            cull_mode: rg::CullMode::None.to_ffi(),
+            ..Default::default()
         },
-        &mut {
-            let mut pip = rg::PipelineDesc {
-                index_type: rg::IndexType::UInt16.to_ffi(),
-                layout: TexturedVertex::layout_desc(),
-                cull_mode: rg::CullMode::None.to_ffi(),
-                ..Default::default()
-            };
-            // pip.colors[0].blend = alpha_blend();
-            pip
+    )
+}
+
+/// Textured cube with `mvp` matrix
+///
+/// * vs uniform 0: `mvp` (`[f32; 16]`, column-major)
+/// * fs image 0: `tex`
+pub fn cube() -> Result<Shader> {
+    gen(
+        &def_shd!("cube"),
+        &mut rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TexturedVertex::layout_desc(),
+            cull_mode: rg::CullMode::Back.to_ffi(),
+            face_winding: rg::FaceWinding::Ccw.to_ffi(),
+            depth: self::depth_test(),
+            ..Default::default()
         },
     )
 }
+
+/// Cube blending two textures with `mvp` matrix
+///
+/// * vs uniform 0: `mvp` (`[f32; 16]`, column-major)
+/// * fs image 0: `tex1`
+/// * fs image 1: `tex2`
+pub fn cube_multi() -> Result<Shader> {
+    self::multi_texture_3d(def_shd!("cube_multi"))
+}
+
+/// Same as [`cube_multi`], but the vertex shader takes `vec4` positions (`w` is filled with `1.0`
+/// for [`TexturedVertex`])
+pub fn more_cubes() -> Result<Shader> {
+    self::multi_texture_3d(def_shd!("more_cubes"))
+}
+
+fn multi_texture_3d(vs_fs: [String; 2]) -> Result<Shader> {
+    gen(
+        &vs_fs,
+        &mut rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TexturedVertex::layout_desc(),
+            cull_mode: rg::CullMode::Back.to_ffi(),
+            face_winding: rg::FaceWinding::Ccw.to_ffi(),
+            depth: self::depth_test(),
+            ..Default::default()
+        },
+    )
+}
+
+/// 2D quad blending two textures
+///
+/// * vs uniform 0: `mvp` (`[f32; 16]`, column-major)
+/// * fs image 0: `tex1`
+/// * fs image 1: `tex2`
+pub fn texture_multi() -> Result<Shader> {
+    gen(&def_shd!("texture_multi"), &mut {
+        let mut pip = rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TexturedVertex::layout_desc(),
+            cull_mode: rg::CullMode::None.to_ffi(),
+            ..Default::default()
+        };
+        pip.colors[0].blend = self::blend_state(BlendMode::Alpha);
+        pip
+    })
+}
+
+/// Tilemap chunks in world pixels
+///
+/// * vs uniform 0: `mvp` (`[f32; 16]`, column-major)
+/// * fs image 0: `tex`
+pub fn tilemap() -> Result<Shader> {
+    self::tilemap_from(&def_shd!("tilemap"))
+}
+
+/// [`tilemap`] from null-terminated sources (see [`crate::gfx::Assets::shader`])
+pub fn tilemap_from(vs_fs: &[String; 2]) -> Result<Shader> {
+    gen(vs_fs, &mut {
+        let mut pip = rg::PipelineDesc {
+            index_type: rg::IndexType::UInt16.to_ffi(),
+            layout: TexturedVertex::layout_desc(),
+            // flipped tiles are mirrored quads
+            cull_mode: rg::CullMode::None.to_ffi(),
+            ..Default::default()
+        };
+        pip.colors[0].blend = self::blend_state(BlendMode::Alpha);
+        pip
+    })
+}
@@ -0,0 +1,340 @@
+//! `#[derive(Vertex)]` for `in_wgpu::gfx::Vertex`
+//!
+//! Field types are mapped to `wgpu::VertexFormat` and shader locations are assigned
+//! sequentially:
+//!
+//! ```ignore
+//! #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+//! #[repr(C)]
+//! pub struct MyVertex {
+//!     pub pos: Vec2<f32>,               // location(0), Float32x2
+//!     #[vertex(normalized)]
+//!     pub color: [u8; 4],               // location(1), Unorm8x4
+//!     #[vertex(location = 4)]
+//!     pub uv: Vec2<f32>,                // location(4), Float32x2
+//!     #[vertex(skip)]
+//!     pub _pad: [f32; 2],               // not visible to the shader
+//! }
+//! ```
+//!
+//! Field attributes (`#[vertex(..)]`):
+//!
+//! - `location = N`: explicit shader location. Following fields continue from `N + 1`
+//! - `format = "Float32x2"`: explicit `wgpu::VertexFormat`
+//! - `normalized`: maps integer fields to `Unorm*` / `Snorm*` formats
+//! - `skip`: no vertex attribute for the field (it still takes memory)
+//!
+//! The struct has to be `#[repr(C)]` so that the offsets can be computed at compile time.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Fields, Ident, Lit, Meta,
+    NestedMeta, Result, Type,
+};
+
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    self::impl_vertex(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// `#[vertex(..)]` on a field
+#[derive(Default)]
+struct FieldAttr {
+    location: Option<u32>,
+    format: Option<Ident>,
+    normalized: bool,
+    skip: bool,
+}
+
+impl FieldAttr {
+    fn parse(attrs: &[syn::Attribute]) -> Result<Self> {
+        let mut out = Self::default();
+
+        for attr in attrs.iter().filter(|a| a.path.is_ident("vertex")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(Error::new(meta.span(), "expected `#[vertex(..)]`")),
+            };
+
+            for nested in list.nested.iter() {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("normalized") => {
+                        out.normalized = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip") => {
+                        out.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("location") => {
+                        out.location = Some(match &nv.lit {
+                            Lit::Int(x) => x.base10_parse()?,
+                            lit => return Err(Error::new(lit.span(), "expected integer")),
+                        });
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("format") => {
+                        out.format = Some(match &nv.lit {
+                            Lit::Str(x) => Ident::new(&x.value(), x.span()),
+                            lit => return Err(Error::new(lit.span(), "expected string")),
+                        });
+                    }
+                    _ => return Err(Error::new(
+                        nested.span(),
+                        "expected one of `location = N`, `format = \"..\"`, `normalized` or `skip`",
+                    )),
+                }
+            }
+        }
+
+        if out.skip && (out.location.is_some() || out.format.is_some() || out.normalized) {
+            return Err(Error::new(
+                Span::call_site(),
+                "`skip` can't be combined with other `vertex` attributes",
+            ));
+        }
+
+        Ok(out)
+    }
+}
+
+fn impl_vertex(input: &DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "`Vertex` can't be derived for generic types",
+        ));
+    }
+
+    if !self::is_repr_c(&input.attrs)? {
+        return Err(Error::new(
+            ident.span(),
+            "`Vertex` requires `#[repr(C)]` to compute attribute offsets",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => {
+            return Err(Error::new(
+                ident.span(),
+                "`Vertex` can only be derived for structs",
+            ))
+        }
+    };
+
+    // compile-time offset computation (`#[repr(C)]` layout)
+    let mut consts = Vec::new();
+    // `wgpu::VertexAttribute`s
+    let mut attrs = Vec::new();
+    // for duplicate checks
+    let mut used_locations = Vec::new();
+
+    let mut next_location = 0;
+    let mut prev_end = quote!(0);
+
+    for (i, field) in fields.iter().enumerate() {
+        let ty = &field.ty;
+        let attr = FieldAttr::parse(&field.attrs)?;
+
+        let offset = format_ident!("OFFSET_{}", i);
+        let end = format_ident!("END_{}", i);
+        consts.push(quote! {
+            const #offset: u64 = align(#prev_end, ::std::mem::align_of::<#ty>());
+            const #end: u64 = #offset + ::std::mem::size_of::<#ty>() as u64;
+        });
+        prev_end = quote!(#end);
+
+        if attr.skip {
+            continue;
+        }
+
+        let format = match attr.format {
+            Some(format) => format,
+            None => match self::infer_format(ty, attr.normalized) {
+                Some(format) => Ident::new(format, ty.span()),
+                None => {
+                    return Err(Error::new(
+                        ty.span(),
+                        "unable to infer vertex format; specify `#[vertex(format = \"..\")]`",
+                    ))
+                }
+            },
+        };
+
+        let location = attr.location.unwrap_or(next_location);
+        if used_locations.contains(&location) {
+            return Err(Error::new(
+                field.span(),
+                format!("shader location {} is already used", location),
+            ));
+        }
+        used_locations.push(location);
+        next_location = location + 1;
+
+        let msg = format!(
+            "size of field `{}` doesn't match `wgpu::VertexFormat::{}`",
+            field
+                .ident
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| i.to_string()),
+            format
+        );
+        consts.push(quote! {
+            const _: () = assert!(
+                ::std::mem::size_of::<#ty>() as u64 == ::wgpu::VertexFormat::#format.size(),
+                #msg
+            );
+        });
+
+        attrs.push(quote! {
+            ::wgpu::VertexAttribute {
+                format: ::wgpu::VertexFormat::#format,
+                offset: #offset,
+                shader_location: #location,
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::in_wgpu::gfx::Vertex for #ident {
+            fn desc() -> ::wgpu::VertexBufferLayout<'static> {
+                // alignments are powers of two
+                const fn align(x: u64, align: usize) -> u64 {
+                    let mask = align as u64 - 1;
+                    (x + mask) & !mask
+                }
+
+                #(#consts)*
+
+                const ATTRS: &[::wgpu::VertexAttribute] = &[#(#attrs),*];
+
+                ::wgpu::VertexBufferLayout {
+                    array_stride: ::std::mem::size_of::<Self>() as ::wgpu::BufferAddress,
+                    step_mode: ::wgpu::VertexStepMode::Vertex,
+                    attributes: ATTRS,
+                }
+            }
+        }
+    })
+}
+
+fn is_repr_c(attrs: &[syn::Attribute]) -> Result<bool> {
+    for attr in attrs.iter().filter(|a| a.path.is_ident("repr")) {
+        if let Meta::List(list) = attr.parse_meta()? {
+            let is_c = list
+                .nested
+                .iter()
+                .any(|nested| matches!(nested, NestedMeta::Meta(Meta::Path(p)) if p.is_ident("C")));
+            if is_c {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Maps field type to `wgpu::VertexFormat` variant name
+fn infer_format(ty: &Type, normalized: bool) -> Option<&'static str> {
+    let (scalar, n) = self::scalar_and_len(ty)?;
+    self::format_name(&scalar, n, normalized)
+}
+
+/// `f32` -> `("f32", 1)`, `[u8; 4]` -> `("u8", 4)`, `Vec2<f32>` -> `("f32", 2)`
+fn scalar_and_len(ty: &Type) -> Option<(String, usize)> {
+    match ty {
+        Type::Array(arr) => {
+            let scalar = self::scalar_name(&arr.elem)?;
+            let n = match &arr.len {
+                Expr::Lit(lit) => match &lit.lit {
+                    Lit::Int(x) => x.base10_parse().ok()?,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some((scalar, n))
+        }
+        Type::Path(path) => {
+            let seg = path.path.segments.last()?;
+            if seg.arguments.is_empty() {
+                return Some((seg.ident.to_string(), 1));
+            }
+
+            // `vek` types
+            let n = match seg.ident.to_string().as_str() {
+                "Vec2" | "Extent2" => 2,
+                "Vec3" | "Rgb" => 3,
+                "Vec4" | "Rgba" => 4,
+                _ => return None,
+            };
+
+            let arg = match &seg.arguments {
+                syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match &args.args[0] {
+                        syn::GenericArgument::Type(ty) => ty,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            };
+
+            Some((self::scalar_name(arg)?, n))
+        }
+        _ => None,
+    }
+}
+
+fn scalar_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident().map(|x| x.to_string()),
+        _ => None,
+    }
+}
+
+fn format_name(scalar: &str, n: usize, normalized: bool) -> Option<&'static str> {
+    Some(match (scalar, n, normalized) {
+        ("f32", 1, false) => "Float32",
+        ("f32", 2, false) => "Float32x2",
+        ("f32", 3, false) => "Float32x3",
+        ("f32", 4, false) => "Float32x4",
+        ("f64", 1, false) => "Float64",
+        ("f64", 2, false) => "Float64x2",
+        ("f64", 3, false) => "Float64x3",
+        ("f64", 4, false) => "Float64x4",
+        ("u32", 1, false) => "Uint32",
+        ("u32", 2, false) => "Uint32x2",
+        ("u32", 3, false) => "Uint32x3",
+        ("u32", 4, false) => "Uint32x4",
+        ("i32", 1, false) => "Sint32",
+        ("i32", 2, false) => "Sint32x2",
+        ("i32", 3, false) => "Sint32x3",
+        ("i32", 4, false) => "Sint32x4",
+        // 8/16 bit formats only come in pairs and quads
+        ("u8", 2, false) => "Uint8x2",
+        ("u8", 4, false) => "Uint8x4",
+        ("u8", 2, true) => "Unorm8x2",
+        ("u8", 4, true) => "Unorm8x4",
+        ("i8", 2, false) => "Sint8x2",
+        ("i8", 4, false) => "Sint8x4",
+        ("i8", 2, true) => "Snorm8x2",
+        ("i8", 4, true) => "Snorm8x4",
+        ("u16", 2, false) => "Uint16x2",
+        ("u16", 4, false) => "Uint16x4",
+        ("u16", 2, true) => "Unorm16x2",
+        ("u16", 4, true) => "Unorm16x4",
+        ("i16", 2, false) => "Sint16x2",
+        ("i16", 4, false) => "Sint16x4",
+        ("i16", 2, true) => "Snorm16x2",
+        ("i16", 4, true) => "Snorm16x4",
+        _ => return None,
+    })
+}
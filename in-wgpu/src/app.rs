@@ -1,78 +1,123 @@
+use std::time::Duration;
+
 use anyhow::Result;
+use in_common::{blend::BlendMode, clip::ClipStack, input::Input, shape::Tessellator};
+use vek::Vec2;
 
-use crate::gfx::{Gpu, StaticMesh, Texture, TriVertex, Vertex, WindowWrapper};
+use crate::gfx::{
+    self, Assets, GlslShader, Gpu, Material, MaterialHandle, MaterialParams, Materials, StaticMesh,
+    Texture, TextureHandle, TextureOptions, TriVertex, WindowWrapper,
+};
 
 #[derive(Debug)]
 pub struct App {
     pub gpu: Gpu,
-    rpip: wgpu::RenderPipeline,
+    /// Scissor rect of the pass. Update it on resize
+    pub clip: ClipStack,
+    assets: Assets,
+    materials: Materials,
     mesh: StaticMesh<TriVertex, u16>,
-    texture: Texture,
-    bind_group: wgpu::BindGroup,
-}
-
-fn verts() -> [TriVertex; 5] {
-    [
-        // (pos, color, uv)
-        (
-            [-0.0868241, 0.49240386],
-            [1.0, 1.0, 1.0, 1.0],
-            [0.4131759, 0.00759614],
-        ),
-        (
-            [-0.49513406, 0.06958647],
-            [1.0, 1.0, 1.0, 1.0],
-            [0.0048659444, 0.43041354],
-        ),
-        (
-            [-0.21918549, -0.44939706],
-            [1.0, 1.0, 1.0, 1.0],
-            [0.28081453, 0.949397],
-        ),
-        (
-            [0.35966998, -0.3473291],
-            [1.0, 1.0, 1.0, 1.0],
-            [0.85967, 0.84732914],
-        ),
-        (
-            [0.44147372, 0.2347359],
-            [1.0, 1.0, 1.0, 1.0],
-            [0.9414737, 0.2652641],
-        ),
-    ]
-    .map(TriVertex::from)
+    material: MaterialHandle,
+    /// The texture shader of `in-rokol`, translated from GLSL
+    glsl_material: MaterialHandle,
+    /// If the pentagon is drawn with `glsl_material`
+    use_glsl: bool,
+    /// Scrolled with the movement actions
+    params: MaterialParams,
 }
 
-const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4, /* padding */ 0];
-
 impl App {
     pub async fn new(window: &WindowWrapper) -> Result<Self> {
         let gpu = Gpu::new(window).await;
 
-        let mesh = StaticMesh::new(&gpu.device, &verts(), INDICES);
-
-        let bytes = include_bytes!("../assets/happy-tree.png");
-        let texture = Texture::from_bytes(&gpu, bytes, "happy-tree")?;
-        let (group_layout, bind_group) = self::simple_bind_group(&gpu, &texture);
+        // the pentagon
+        let shape = Tessellator::new()
+            .polygon(Vec2::zero(), 0.5, 5, 100f32.to_radians())
+            .build();
+        let indices = shape.indices_u16().unwrap();
+        let mesh = StaticMesh::new(&gpu.device, &shape.vertices::<TriVertex>(), &indices);
+
+        let mut assets =
+            Assets::with_hot_reload(env!("CARGO_MANIFEST_DIR"))?.premultiplied_alpha(true);
+        let mut materials = Materials::new(&gpu);
+
+        let texture = self::load_texture(&gpu, &mut assets, &mut materials);
+        let shader = assets.shader("src/shader.wgsl")?;
+        let pipeline =
+            materials.add_pipeline_asset::<TriVertex>(&gpu, shader, BlendMode::Premultiplied);
+
+        let material = materials.add(
+            &gpu,
+            Material {
+                pipeline,
+                texture,
+                sampler: None,
+                params: MaterialParams::default(),
+            },
+        );
 
-        let rpip = self::simple_rpip::<TriVertex>(
+        // no material parameters: the texture doesn't scroll
+        let glsl = GlslShader::load(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../in-rokol/src/glsl"),
+            "texture",
+        )?;
+        let rpip = gfx::glsl_rpip::<TriVertex>(
             &gpu.device,
-            include_str!("shader.wgsl"),
+            &glsl,
             gpu.config.format,
-            &group_layout,
+            BlendMode::Premultiplied,
+            &materials.layouts(),
         );
+        let glsl_material = Material {
+            pipeline: materials.add_raw_pipeline(rpip),
+            texture,
+            sampler: None,
+            params: MaterialParams::default(),
+        };
+        let glsl_material = materials.add(&gpu, glsl_material);
 
         Ok(Self {
             gpu,
-            rpip,
+            clip: ClipStack::new(window.fb_size_u(), window.dpi_scale()),
+            assets,
+            materials,
             mesh,
-            texture,
-            bind_group,
+            material,
+            glsl_material,
+            use_glsl: false,
+            params: MaterialParams::default(),
         })
     }
 
+    /// Scrolls the texture with the movement actions and reloads modified assets
+    pub fn update(&mut self, input: &Input, dt: Duration) {
+        if input.action_pressed("shader") {
+            self.use_glsl = !self.use_glsl;
+        }
+
+        let dir = Vec2::new(
+            input.action_axis("left", "right"),
+            input.action_axis("up", "down"),
+        );
+
+        if input.action_pressed("reset") {
+            self.params.uv_offset = Vec2::zero();
+            self.materials
+                .set_params(&self.gpu, self.material, self.params);
+        } else if dir != Vec2::zero() {
+            // half the texture per second
+            self.params.uv_offset += dir * 0.5 * dt.as_secs_f32();
+            self.materials
+                .set_params(&self.gpu, self.material, self.params);
+        }
+
+        if !self.assets.reload_changed(&self.gpu).is_empty() {
+            self.materials.refresh(&self.gpu);
+        }
+    }
+
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.gpu.surface.get_current_texture()?;
+        let output = self.gpu.surface().get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -103,9 +148,17 @@ impl App {
                 depth_stencil_attachment: None,
             });
 
-            rpass.set_pipeline(&self.rpip);
-            rpass.set_bind_group(0, &self.bind_group, &[]);
-            self.mesh.draw_all(&mut rpass);
+            // wgpu rejects empty scissor rects
+            if !self.clip.is_empty() {
+                let r = self.clip.scissor();
+                rpass.set_scissor_rect(r.x, r.y, r.w, r.h);
+                let material = if self.use_glsl {
+                    self.glsl_material
+                } else {
+                    self.material
+                };
+                self.materials.draw_mesh(&mut rpass, &self.mesh, material);
+            }
         }
 
         // submit will accept anything that implements IntoIter
@@ -116,102 +169,29 @@ impl App {
     }
 }
 
-fn simple_rpip<V: Vertex>(
-    device: &wgpu::Device,
-    src: &str,
-    tex_fmt: wgpu::TextureFormat,
-    layout: &wgpu::BindGroupLayout,
-) -> wgpu::RenderPipeline {
-    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-        label: Some("shader"),
-        source: wgpu::ShaderSource::Wgsl(src.into()),
-    });
-
-    let rpip_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("render-pipeline-layout"),
-        bind_group_layouts: &[layout],
-        push_constant_ranges: &[],
-    });
-
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("render-pipeline"),
-        layout: Some(&rpip_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[V::desc()],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[wgpu::ColorTargetState {
-                format: tex_fmt,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            }],
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            // cull the back face
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            // other than this requires`Features::NON_FILL_POLYGON_MODE`
-            polygon_mode: wgpu::PolygonMode::Fill,
-            // requires `Features::DEPTH_CLIP_CONTROL`
-            unclipped_depth: false,
-            // requires `Features::CONSERVATIVE_RASTERIZATION`
-            conservative: false,
-        },
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: 1, // single sampling
-            mask: !0, // all samples are active
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-    })
-}
-
-fn simple_bind_group(gpu: &Gpu, texture: &Texture) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
-    let layout = gpu
-        .device
-        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-            label: Some("texture-bind-group-layout"),
-        });
-
-    let group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&texture.view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&texture.sampler),
-            },
-        ],
-        label: Some("diffuse-bind-group"),
-    });
-
-    (layout, group)
+/// Loads the happy tree or falls back to a checkerboard
+fn load_texture(gpu: &Gpu, assets: &mut Assets, materials: &mut Materials) -> TextureHandle {
+    match assets.texture(gpu, "assets/happy-tree.png") {
+        Ok(tex) => materials.add_texture_asset(tex),
+        Err(err) => {
+            log::warn!("{:#}", err);
+            let img = image::RgbaImage::from_fn(64, 64, |x, y| {
+                if (x / 8 + y / 8) & 1 == 0 {
+                    image::Rgba([255, 255, 255, 255])
+                } else {
+                    image::Rgba([255, 0, 255, 255])
+                }
+            });
+            let img = image::DynamicImage::ImageRgba8(img);
+            let tex = Texture::from_image_with(
+                &gpu.device,
+                &gpu.queue,
+                &img,
+                Some("placeholder"),
+                TextureOptions::premultiplied(),
+            )
+            .unwrap();
+            materials.add_texture(tex)
+        }
+    }
 }
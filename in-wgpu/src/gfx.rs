@@ -1,28 +1,51 @@
 //! Immediate-mode 2D rendering
 
+mod assets;
+mod glsl;
 mod gpu;
+mod material;
 mod mesh;
+mod model;
+mod particles;
+pub mod readback;
+mod tilemap;
+mod uniform;
 mod window;
 
+pub use assets::Assets;
+pub use glsl::{glsl_rpip, GlslShader};
 pub use gpu::Gpu;
-pub use mesh::StaticMesh;
+pub use material::{
+    blend_state, Material, MaterialHandle, MaterialParams, Materials, PipelineHandle,
+    SamplerHandle, TextureHandle,
+};
+pub use mesh::{Index, StaticMesh};
+pub use model::{Model, ModelMesh};
+pub use particles::{Emitter, Particle, ParticleSystem, MAX_EMITTERS};
+pub use tilemap::{TileMapRenderer, CHUNK_SIZE};
+pub use uniform::{Uniform, UniformRing};
 pub use window::WindowWrapper;
 
-use std::mem;
+/// `#[derive(Vertex)]`
+pub use in_wgpu_derive::Vertex;
+
+use std::borrow::Cow;
 
 use anyhow::*;
 use image::GenericImageView;
-use vek::{Vec2, Vec4};
+use in_common::{mesh::MeshVertex, texture::TextureLayers};
+use vek::{Vec2, Vec3, Vec4};
 
 // TODO: add color struct
 
+/// Vertex type. Prefer `#[derive(Vertex)]` to implementing by hand
 pub trait Vertex {
     /// Declares memory layout of vertex buffer
     fn desc() -> wgpu::VertexBufferLayout<'static>;
 }
 
 /// Triangle vertex
-#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
 #[repr(C)]
 pub struct TriVertex {
     /// XY
@@ -43,54 +66,274 @@ impl From<([f32; 2], [f32; 4], [f32; 2])> for TriVertex {
     }
 }
 
-impl Vertex for TriVertex {
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        const ATTRS: &'static [wgpu::VertexAttribute] =
-            &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4, 2 => Float32x2];
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: ATTRS,
+impl From<MeshVertex> for TriVertex {
+    /// Drops Z and the normal
+    fn from(v: MeshVertex) -> Self {
+        Self {
+            pos: v.pos.xy(),
+            color: v.color.into(),
+            uv: v.uv,
         }
     }
 }
 
-/// `wgpu` texture
+/// 3D vertex for imported models
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+#[repr(C)]
+pub struct ModelVertex {
+    /// XYZ
+    pub pos: Vec3<f32>,
+    /// XYZ
+    pub normal: Vec3<f32>,
+    /// RGBA
+    pub color: Vec4<f32>,
+    /// UV
+    pub uv: Vec2<f32>,
+}
+
+impl From<MeshVertex> for ModelVertex {
+    fn from(v: MeshVertex) -> Self {
+        Self {
+            pos: v.pos,
+            normal: v.normal,
+            color: v.color.into(),
+            uv: v.uv,
+        }
+    }
+}
+
+/// Options of [`Texture`] constructors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextureOptions {
+    /// Multiplies color with alpha (in linear space) for correct filtering at transparent edges.
+    /// Draw the texture with `BlendMode::Premultiplied`
+    pub premultiply_alpha: bool,
+}
+
+impl TextureOptions {
+    pub fn premultiplied() -> Self {
+        Self {
+            premultiply_alpha: true,
+        }
+    }
+}
+
+/// `wgpu` texture: 2D, 2D array, 3D or cube map
 #[derive(Debug)]
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    size: [u32; 2],
+    /// Array layers, depth or cube faces
+    layers: u32,
+    view_dimension: wgpu::TextureViewDimension,
+    premultiplied: bool,
+}
+
+impl in_common::anim::TextureSize for Texture {
+    fn texture_size(&self) -> [u32; 2] {
+        self.size
+    }
 }
 
 impl Texture {
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Array layers, depth of 3D texture or 6 for cube map
+    pub fn n_layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        self.view_dimension
+    }
+
+    /// Binding type for a bind group layout entry
+    pub fn binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: self.view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        }
+    }
+
+    /// Matching WGSL type, e.g. `texture_2d_array<f32>`
+    pub fn wgsl_type(&self) -> &'static str {
+        match self.view_dimension {
+            wgpu::TextureViewDimension::D1 => "texture_1d<f32>",
+            wgpu::TextureViewDimension::D2 => "texture_2d<f32>",
+            wgpu::TextureViewDimension::D2Array => "texture_2d_array<f32>",
+            wgpu::TextureViewDimension::Cube => "texture_cube<f32>",
+            wgpu::TextureViewDimension::CubeArray => "texture_cube_array<f32>",
+            wgpu::TextureViewDimension::D3 => "texture_3d<f32>",
+        }
+    }
+
+    /// If the color is multiplied with alpha (draw with `BlendMode::Premultiplied`)
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
     pub fn from_bytes(gpu: &Gpu, bytes: &[u8], label: &str) -> Result<Self> {
         let img = image::load_from_memory(bytes)?;
         Self::from_image(&gpu.device, &gpu.queue, &img, Some(label))
     }
 
+    pub fn from_path(gpu: &Gpu, path: &std::path::Path, opts: TextureOptions) -> Result<Self> {
+        let img = image::open(path)?.into_rgba8();
+        let img = image::DynamicImage::ImageRgba8(img);
+        Self::from_image_with(&gpu.device, &gpu.queue, &img, path.to_str(), opts)
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.as_rgba8().unwrap();
+        Self::from_image_with(device, queue, img, label, TextureOptions::default())
+    }
+
+    pub fn from_image_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        opts: TextureOptions,
+    ) -> Result<Self> {
         let dimensions = img.dimensions();
+        let rgba = if opts.premultiply_alpha {
+            let mut rgba = img.to_rgba8();
+            in_common::blend::premultiply_alpha_srgb(&mut rgba);
+            Cow::Owned(rgba)
+        } else {
+            match img.as_rgba8() {
+                Some(rgba) => Cow::Borrowed(rgba),
+                None => Cow::Owned(img.to_rgba8()),
+            }
+        };
+
+        Ok(Self::create(
+            device,
+            queue,
+            &rgba,
+            [dimensions.0, dimensions.1, 1],
+            wgpu::TextureViewDimension::D2,
+            label,
+            opts.premultiply_alpha,
+        ))
+    }
+
+    /// 2D array texture (`texture_2d_array<f32>`), sampled with a layer index
+    pub fn array(
+        gpu: &Gpu,
+        layers: &TextureLayers,
+        label: Option<&str>,
+        opts: TextureOptions,
+    ) -> Self {
+        Self::from_layers(
+            gpu,
+            layers,
+            wgpu::TextureViewDimension::D2Array,
+            label,
+            opts,
+        )
+    }
+
+    /// 3D texture (`texture_3d<f32>`) with the layers as depth slices
+    pub fn volume(
+        gpu: &Gpu,
+        layers: &TextureLayers,
+        label: Option<&str>,
+        opts: TextureOptions,
+    ) -> Self {
+        Self::from_layers(gpu, layers, wgpu::TextureViewDimension::D3, label, opts)
+    }
+
+    /// Cube map (`texture_cube<f32>`) from six faces (see [`TextureLayers::cube`])
+    pub fn cube(
+        gpu: &Gpu,
+        faces: &TextureLayers,
+        label: Option<&str>,
+        opts: TextureOptions,
+    ) -> Result<Self> {
+        let [w, h] = faces.size();
+        ensure!(
+            faces.n_layers() == 6 && w == h,
+            "cube map needs 6 square faces, given {} of {}x{}",
+            faces.n_layers(),
+            w,
+            h
+        );
+        Ok(Self::from_layers(
+            gpu,
+            faces,
+            wgpu::TextureViewDimension::Cube,
+            label,
+            opts,
+        ))
+    }
+
+    fn from_layers(
+        gpu: &Gpu,
+        layers: &TextureLayers,
+        view_dimension: wgpu::TextureViewDimension,
+        label: Option<&str>,
+        opts: TextureOptions,
+    ) -> Self {
+        let pixels = if opts.premultiply_alpha {
+            let mut layers = layers.clone();
+            layers.premultiply_alpha_srgb();
+            layers.pixels()
+        } else {
+            layers.pixels()
+        };
 
+        let [w, h] = layers.size();
+        Self::create(
+            &gpu.device,
+            &gpu.queue,
+            &pixels,
+            [w, h, layers.n_layers()],
+            view_dimension,
+            label,
+            opts.premultiply_alpha,
+        )
+    }
+
+    /// Creates an RGBA8 (sRGB) texture and uploads the layers
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pixels: &[u8],
+        [w, h, layers]: [u32; 3],
+        view_dimension: wgpu::TextureViewDimension,
+        label: Option<&str>,
+        premultiplied: bool,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
+            width: w,
+            height: h,
+            depth_or_array_layers: layers,
+        };
+        let dimension = match view_dimension {
+            wgpu::TextureViewDimension::D3 => wgpu::TextureDimension::D3,
+            _ => wgpu::TextureDimension::D2,
         };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
             mip_level_count: 1,
             sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
+            dimension,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            // `COPY_SRC` for `readback::read_texture`
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
         });
 
         queue.write_texture(
@@ -100,16 +343,19 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            rgba,
+            pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+                bytes_per_row: std::num::NonZeroU32::new(4 * w),
+                rows_per_image: std::num::NonZeroU32::new(h),
             },
             size,
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -120,10 +366,66 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+            size: [w, h],
+            layers,
+            view_dimension,
+            premultiplied,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec2, Vec3};
+
+    use super::*;
+
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+    #[repr(C)]
+    struct AttrVertex {
+        pos: Vec3<f32>,
+        #[vertex(normalized)]
+        color: [u8; 4],
+        #[vertex(location = 4)]
+        uv: Vec2<f32>,
+        #[vertex(skip)]
+        _pad: [f32; 2],
+        #[vertex(format = "Sint16x2")]
+        id: [i16; 2],
+    }
+
+    #[test]
+    fn derive_attributes() {
+        let desc = AttrVertex::desc();
+        assert_eq!(desc.array_stride, std::mem::size_of::<AttrVertex>() as u64);
+        assert_eq!(desc.step_mode, wgpu::VertexStepMode::Vertex);
+
+        let expected = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Unorm8x4,
+            4 => Float32x2,
+        ];
+        assert_eq!(desc.attributes[..3], expected);
+        // after the skipped field
+        assert_eq!(
+            desc.attributes[3],
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Sint16x2,
+                offset: 32,
+                shader_location: 5,
+            }
+        );
+        assert_eq!(desc.attributes.len(), 4);
+    }
+
+    #[test]
+    fn derive_matches_hand_written() {
+        let expected = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4, 2 => Float32x2];
+        assert_eq!(TriVertex::desc().attributes, expected);
+        assert_eq!(TriVertex::desc().array_stride, 32);
     }
 }
@@ -0,0 +1,164 @@
+//! Asset registry with hot reload
+
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use in_common::assets::{self, Cache, Font, Handle, Watcher};
+
+use crate::gfx::{Gpu, Model, ModelVertex, Texture, TextureOptions};
+
+/// Textures, WGSL shaders, models and fonts loaded by path
+///
+/// Repeated loads of the same file share one [`Handle`]. With hot reload enabled, call
+/// [`reload_changed`](Self::reload_changed) every frame and then `Materials::refresh`.
+#[derive(Debug)]
+pub struct Assets {
+    /// Relative paths are resolved from here
+    root: PathBuf,
+    textures: Cache<Texture>,
+    shaders: Cache<String>,
+    models: Cache<Model<ModelVertex>>,
+    fonts: Cache<Font>,
+    watcher: Option<Watcher>,
+    /// Premultiply alpha of textures on load
+    premultiply_alpha: bool,
+}
+
+impl Assets {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            textures: Cache::new(),
+            shaders: Cache::new(),
+            models: Cache::new(),
+            fonts: Cache::new(),
+            watcher: None,
+            premultiply_alpha: false,
+        }
+    }
+
+    /// Premultiplies alpha of textures loaded (and reloaded) afterwards
+    pub fn premultiplied_alpha(mut self, premultiply: bool) -> Self {
+        self.premultiply_alpha = premultiply;
+        self
+    }
+
+    pub fn premultiplies_alpha(&self) -> bool {
+        self.premultiply_alpha
+    }
+
+    /// Watches loaded files and reloads them on [`reload_changed`](Self::reload_changed)
+    pub fn with_hot_reload(root: impl Into<PathBuf>) -> Result<Self> {
+        let mut assets = Self::new(root);
+        assets.watcher = Some(Watcher::new(std::time::Duration::from_millis(100))?);
+        Ok(assets)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn watch(&mut self, handle_path: Option<&Path>) -> Result<()> {
+        if let (Some(watcher), Some(path)) = (&mut self.watcher, handle_path) {
+            watcher.watch(path, path)?;
+        }
+        Ok(())
+    }
+
+    pub fn texture(&mut self, gpu: &Gpu, path: impl AsRef<Path>) -> Result<Handle<Texture>> {
+        let path = self.root.join(path);
+        let premul = self.premultiply_alpha;
+        let handle = self.textures.load_with(&path, |path| {
+            Texture::from_path(
+                gpu,
+                path,
+                TextureOptions {
+                    premultiply_alpha: premul,
+                },
+            )
+        })?;
+        self.watch(handle.path())?;
+        Ok(handle)
+    }
+
+    /// WGSL source. Create pipelines with `Materials::add_pipeline_asset`
+    pub fn shader(&mut self, path: impl AsRef<Path>) -> Result<Handle<String>> {
+        let path = self.root.join(path);
+        let handle = self.shaders.load_with(&path, self::load_text)?;
+        self.watch(handle.path())?;
+        Ok(handle)
+    }
+
+    /// `.obj`, `.gltf` or `.glb` model. Only the model file is watched
+    pub fn model(
+        &mut self,
+        gpu: &Gpu,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<Model<ModelVertex>>> {
+        let path = self.root.join(path);
+        let handle = self
+            .models
+            .load_with(&path, |path| Model::load(gpu, path))?;
+        self.watch(handle.path())?;
+        Ok(handle)
+    }
+
+    pub fn font(&mut self, path: impl AsRef<Path>) -> Result<Handle<Font>> {
+        let path = self.root.join(path);
+        let handle = self.fonts.load_with(&path, assets::load_font)?;
+        self.watch(handle.path())?;
+        Ok(handle)
+    }
+
+    /// Reloads modified assets in place and returns their paths
+    ///
+    /// Assets that fail to reload are logged and kept as they were.
+    pub fn reload_changed(&mut self, gpu: &Gpu) -> Vec<PathBuf> {
+        let changed = match &mut self.watcher {
+            Some(w) => w.changed(),
+            None => return Vec::new(),
+        };
+
+        let mut reloaded = Vec::new();
+        for path in changed {
+            match self.reload(gpu, &path) {
+                Result::Ok(true) => {
+                    log::info!("reloaded asset: {}", path.display());
+                    reloaded.push(path);
+                }
+                Result::Ok(false) => {}
+                Err(err) => log::error!("{:#}", err),
+            }
+        }
+
+        reloaded
+    }
+
+    fn reload(&mut self, gpu: &Gpu, path: &Path) -> Result<bool> {
+        let mut any = self.textures.reload_with(path, |p| {
+            Texture::from_path(
+                gpu,
+                p,
+                TextureOptions {
+                    premultiply_alpha: self.premultiply_alpha,
+                },
+            )
+        })?;
+        any |= self.shaders.reload_with(path, self::load_text)?;
+        any |= self.models.reload_with(path, |p| Model::load(gpu, p))?;
+        any |= self.fonts.reload_with(path, assets::load_font)?;
+        Ok(any)
+    }
+
+    /// Forgets dropped assets
+    pub fn gc(&mut self) {
+        self.textures.gc();
+        self.shaders.gc();
+        self.models.gc();
+        self.fonts.gc();
+    }
+}
+
+fn load_text(path: &Path) -> Result<String> {
+    Ok(std::fs::read_to_string(path)?)
+}
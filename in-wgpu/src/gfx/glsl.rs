@@ -0,0 +1,443 @@
+//! GLSL 330 to WGSL translation via naga, for the shaders of `in-rokol/src/glsl`
+//!
+//! The sources are rewritten to Vulkan GLSL 450 line by line (so naga errors keep the source
+//! line numbers) with these bindings:
+//!
+//! - vertex inputs: `layout(location = ..)` as declared, otherwise in declaration order
+//! - varyings: numbered in the order of the vertex shader outputs, matched by name
+//! - fragment outputs: as declared, otherwise in declaration order
+//! - `uniform sampler2D tex`: texture `tex_texture` and sampler `tex_sampler` in group 0. The
+//!   first sampler is bindings 0 and 1 ([`Materials`] texture group), the next is 2 and 3, ..
+//! - other loose uniforms: one std140 block per stage in group 2, binding 0 (vertex) and 1
+//!   (fragment), e.g. a [`Uniform`] camera as in [`TileMapRenderer`]
+//!
+//! Matrices are used as they are: clip-space depth is `[0, 1]` in `wgpu`, not `[-1, 1]`.
+//!
+//! [`Materials`]: crate::gfx::Materials
+//! [`Uniform`]: crate::gfx::Uniform
+//! [`TileMapRenderer`]: crate::gfx::TileMapRenderer
+
+use std::{fmt::Write, path::Path};
+
+use anyhow::*;
+use in_common::blend::BlendMode;
+
+use crate::gfx::{blend_state, Vertex};
+
+/// WGSL translated from a GLSL vertex/fragment pair
+///
+/// Both modules have the entry point `main`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlslShader {
+    /// Vertex shader (WGSL)
+    pub vs: String,
+    /// Fragment shader (WGSL)
+    pub fs: String,
+    /// Loose uniforms of the vertex stage, in block order
+    pub vs_uniforms: Vec<String>,
+    /// Loose uniforms of the fragment stage, in block order
+    pub fs_uniforms: Vec<String>,
+    /// `sampler*` uniforms of the fragment stage in binding order
+    pub samplers: Vec<String>,
+}
+
+impl GlslShader {
+    /// Loads `<name>.vs` and `<name>.fs` in a directory, e.g. `in-rokol/src/glsl`
+    pub fn load(dir: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let dir = dir.as_ref();
+        let read = |ext: &str| {
+            let path = dir.join(format!("{}.{}", name, ext));
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("reading shader {}", path.display()))
+        };
+        Self::translate(name, &read("vs")?, &read("fs")?)
+    }
+
+    /// Translates GLSL 330 sources
+    ///
+    /// * `name`: shader name in error messages (`<name>.vs:<line>`)
+    pub fn translate(name: &str, vs: &str, fs: &str) -> Result<Self> {
+        let mut varyings = Vec::new();
+
+        let vs_450 = self::rewrite(vs, naga::ShaderStage::Vertex, &mut varyings)
+            .with_context(|| format!("{}.vs", name))?;
+        let fs_450 = self::rewrite(fs, naga::ShaderStage::Fragment, &mut varyings)
+            .with_context(|| format!("{}.fs", name))?;
+
+        Ok(Self {
+            vs: self::to_wgsl(
+                &format!("{}.vs", name),
+                &vs_450.code,
+                naga::ShaderStage::Vertex,
+            )?,
+            fs: self::to_wgsl(
+                &format!("{}.fs", name),
+                &fs_450.code,
+                naga::ShaderStage::Fragment,
+            )?,
+            vs_uniforms: vs_450.uniforms,
+            fs_uniforms: fs_450.uniforms,
+            samplers: fs_450.samplers,
+        })
+    }
+}
+
+/// Vulkan GLSL 450 with the same lines as the source
+struct Rewritten {
+    code: String,
+    uniforms: Vec<String>,
+    samplers: Vec<String>,
+}
+
+/// Global `[layout(..)] [interpolation] qualifier type name[array];` declaration
+#[derive(Debug)]
+struct Decl<'a> {
+    layout: Option<&'a str>,
+    interpolation: Option<&'a str>,
+    qualifier: &'a str,
+    ty: &'a str,
+    name: &'a str,
+    /// Array suffix, e.g. `[4]`
+    array: &'a str,
+}
+
+impl<'a> Decl<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line.trim().strip_suffix(';')?.trim_end();
+
+        let layout = match rest.strip_prefix("layout") {
+            Some(l) => {
+                let l = l.trim_start().strip_prefix('(')?;
+                let end = l.find(')')?;
+                rest = l[end + 1..].trim_start();
+                Some(l[..end].trim())
+            }
+            None => None,
+        };
+
+        let mut words = rest.split_whitespace().collect::<Vec<_>>();
+        let interpolation = match words.first() {
+            Some(&w) if matches!(w, "flat" | "smooth" | "noperspective") => {
+                words.remove(0);
+                Some(w)
+            }
+            _ => None,
+        };
+
+        let (qualifier, ty, name) = match words[..] {
+            [q, ty, name] if matches!(q, "in" | "out" | "uniform") => (q, ty, name),
+            _ => return None,
+        };
+        let (name, array) = match name.find('[') {
+            Some(ix) => (&name[..ix], &name[ix..]),
+            None => (name, ""),
+        };
+
+        Some(Self {
+            layout,
+            interpolation,
+            qualifier,
+            ty,
+            name,
+            array,
+        })
+    }
+
+    /// `layout(location = N) [interpolation] qualifier type name;`
+    fn with_location(&self, location: usize) -> String {
+        let mut layout = format!("location = {}", location);
+        if let Some(l) = self.layout.filter(|l| !l.contains("location")) {
+            layout = format!("{}, {}", l, layout);
+        }
+
+        format!(
+            "layout({}) {}{} {} {}{};",
+            layout,
+            self.interpolation
+                .map(|i| format!("{} ", i))
+                .unwrap_or_default(),
+            self.qualifier,
+            self.ty,
+            self.name,
+            self.array
+        )
+    }
+
+    /// Explicit location in the `layout(..)` qualifier
+    fn location(&self) -> Option<usize> {
+        let layout = self.layout?;
+        let ix = layout.find("location")?;
+        let value = layout[ix + "location".len()..]
+            .trim_start()
+            .strip_prefix('=')?;
+        let digits = value.trim_start();
+        let n = digits.bytes().take_while(u8::is_ascii_digit).count();
+        digits[..n].parse().ok()
+    }
+}
+
+/// Group of the loose-uniform blocks
+const UNIFORM_GROUP: u32 = 2;
+
+fn rewrite(src: &str, stage: naga::ShaderStage, varyings: &mut Vec<String>) -> Result<Rewritten> {
+    let mut lines = Vec::new();
+    let mut uniforms = Vec::new();
+    // (name, type)
+    let mut samplers: Vec<(String, &str)> = Vec::new();
+    let mut block: Option<usize> = None;
+    let mut members = String::new();
+    let (mut n_in, mut n_out) = (0, 0);
+    let mut depth = 0i32;
+
+    for (i, src_line) in src.lines().enumerate() {
+        lines.push(src_line.to_string());
+        let line = lines.last_mut().unwrap();
+
+        let at_global = depth == 0;
+        depth += src_line.matches('{').count() as i32 - src_line.matches('}').count() as i32;
+        if !at_global {
+            continue;
+        }
+
+        if src_line.trim_start().starts_with("#version") {
+            *line = "#version 450".to_string();
+            continue;
+        }
+
+        let decl = match Decl::parse(src_line) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        match (decl.qualifier, stage) {
+            ("uniform", _) if decl.ty.starts_with("sampler") => {
+                let texture_ty = match decl.ty {
+                    "sampler2D" => "texture2D",
+                    "sampler2DArray" => "texture2DArray",
+                    "sampler3D" => "texture3D",
+                    "samplerCube" => "textureCube",
+                    ty => bail!("line {}: unsupported sampler type `{}`", i + 1, ty),
+                };
+                let binding = 2 * samplers.len();
+                *line = format!(
+                    "layout(set = 0, binding = {}) uniform {} {}_texture; \
+                     layout(set = 0, binding = {}) uniform sampler {}_sampler;",
+                    binding,
+                    texture_ty,
+                    decl.name,
+                    binding + 1,
+                    decl.name
+                );
+                samplers.push((decl.name.to_string(), decl.ty));
+            }
+            ("uniform", _) => {
+                write!(members, " {} {}{};", decl.ty, decl.name, decl.array).unwrap();
+                uniforms.push(decl.name.to_string());
+                block.get_or_insert(i);
+                line.clear();
+            }
+            ("in", naga::ShaderStage::Vertex) => {
+                let loc = decl.location().unwrap_or(n_in);
+                n_in = loc + 1;
+                *line = decl.with_location(loc);
+            }
+            ("out", naga::ShaderStage::Vertex) => {
+                *line = decl.with_location(varyings.len());
+                varyings.push(decl.name.to_string());
+            }
+            ("in", _) => {
+                let loc = varyings
+                    .iter()
+                    .position(|v| v == decl.name)
+                    .with_context(|| {
+                        format!(
+                            "line {}: `{}` is not written by the vertex shader",
+                            i + 1,
+                            decl.name
+                        )
+                    })?;
+                *line = decl.with_location(loc);
+            }
+            _ => {
+                let loc = decl.location().unwrap_or(n_out);
+                n_out = loc + 1;
+                *line = decl.with_location(loc);
+            }
+        }
+    }
+
+    if let Some(ix) = block {
+        let (binding, name) = match stage {
+            naga::ShaderStage::Vertex => (0, "VsUniforms"),
+            _ => (1, "FsUniforms"),
+        };
+        lines[ix] = format!(
+            "layout(set = {}, binding = {}, std140) uniform {} {{{} }};",
+            UNIFORM_GROUP, binding, name, members
+        );
+    }
+
+    // combine the texture and the sampler where the sampler uniform is used
+    for line in &mut lines {
+        for (name, ty) in &samplers {
+            let combined = format!("{}({}_texture, {}_sampler)", ty, name, name);
+            *line = self::replace_word(line, name, &combined);
+        }
+    }
+
+    let mut code = lines.join("\n");
+    code.push('\n');
+    Ok(Rewritten {
+        code,
+        uniforms,
+        samplers: samplers.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+/// Replaces whole identifiers
+fn replace_word(line: &str, word: &str, with: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(ix) = rest.find(word) {
+        let before = rest[..ix].chars().last().or_else(|| out.chars().last());
+        let after = rest[ix + word.len()..].chars().next();
+        out.push_str(&rest[..ix]);
+        if matches!(before, Some(c) if is_ident(c)) || matches!(after, Some(c) if is_ident(c)) {
+            out.push_str(word);
+        } else {
+            out.push_str(with);
+        }
+        rest = &rest[ix + word.len()..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Parses, validates and writes WGSL
+fn to_wgsl(name: &str, src: &str, stage: naga::ShaderStage) -> Result<String> {
+    let module = naga::front::glsl::Parser::default()
+        .parse(&stage.into(), src)
+        .map_err(|errors| {
+            let msgs = errors
+                .iter()
+                .map(|e| {
+                    let line = e
+                        .meta
+                        .to_range()
+                        .map(|r| src[..r.start.min(src.len())].matches('\n').count() + 1)
+                        .unwrap_or(0);
+                    format!("{}:{}: {}", name, line, e.kind)
+                })
+                .collect::<Vec<_>>();
+            anyhow!("{}", msgs.join("\n"))
+        })?;
+
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|e| anyhow!("{}: {}", name, e))?;
+
+    naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+        .with_context(|| format!("writing {} as WGSL", name))
+}
+
+/// Creates a render pipeline from a translated shader, like `Materials::add_pipeline`
+///
+/// Add it with `Materials::add_raw_pipeline`.
+pub fn glsl_rpip<V: Vertex>(
+    device: &wgpu::Device,
+    shader: &GlslShader,
+    tex_fmt: wgpu::TextureFormat,
+    blend: BlendMode,
+    layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline {
+    let vs = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("glsl-vs"),
+        source: wgpu::ShaderSource::Wgsl(shader.vs.as_str().into()),
+    });
+    let fs = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("glsl-fs"),
+        source: wgpu::ShaderSource::Wgsl(shader.fs.as_str().into()),
+    });
+
+    let rpip_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("glsl-pipeline-layout"),
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("glsl-pipeline"),
+        layout: Some(&rpip_layout),
+        vertex: wgpu::VertexState {
+            module: &vs,
+            entry_point: "main",
+            buffers: &[V::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fs,
+            entry_point: "main",
+            targets: &[wgpu::ColorTargetState {
+                format: tex_fmt,
+                blend: self::blend_state(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // the 2D shaders draw both faces
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rokol_shaders() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../in-rokol/src/glsl");
+        let shaders: &[(&str, &[&str], &[&str])] = &[
+            ("cube", &["mvp"], &["tex"]),
+            ("cube_multi", &["mvp"], &["tex1", "tex2"]),
+            ("more_cubes", &["mvp"], &["tex1", "tex2"]),
+            ("quad", &[], &[]),
+            ("texture", &[], &["tex"]),
+            ("texture_multi", &["mvp"], &["tex1", "tex2"]),
+            ("tilemap", &["mvp"], &["tex"]),
+            ("triangle", &[], &[]),
+        ];
+
+        for &(name, vs_uniforms, samplers) in shaders {
+            let shader = GlslShader::load(dir, name).unwrap();
+            assert_eq!(shader.vs_uniforms, vs_uniforms, "{}", name);
+            assert!(shader.fs_uniforms.is_empty(), "{}", name);
+            assert_eq!(shader.samplers, samplers, "{}", name);
+            assert_eq!(
+                shader.vs.contains("[[group(2), binding(0)]]"),
+                !vs_uniforms.is_empty(),
+                "{}:\n{}",
+                name,
+                shader.vs
+            );
+        }
+    }
+}
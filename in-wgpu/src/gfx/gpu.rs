@@ -1,3 +1,5 @@
+use anyhow::*;
+use in_common::preprocess::ShaderSource;
 use vek::Extent2;
 
 use crate::gfx::window::WindowWrapper;
@@ -5,8 +7,8 @@ use crate::gfx::window::WindowWrapper;
 /// `wgpu` handles
 #[derive(Debug)]
 pub struct Gpu {
-    /// Frame buffer
-    pub(crate) surface: wgpu::Surface,
+    /// Frame buffer. `None` if headless
+    pub(crate) surface: Option<wgpu::Surface>,
     /// Connection to a graphics device
     pub(crate) device: wgpu::Device,
     /// Command queue on the device
@@ -63,7 +65,7 @@ impl Gpu {
         surface.configure(&device, &config);
 
         Self {
-            surface,
+            surface: Some(surface),
             device,
             queue,
             config,
@@ -71,6 +73,53 @@ impl Gpu {
         }
     }
 
+    /// Without a window, e.g. for tests with readback. Fails if there's no adapter
+    ///
+    /// `config` is kept for pipelines targeting the frame buffer format (`Rgba8UnormSrgb`).
+    pub async fn headless(size: Extent2<u32>) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .context("no graphics adapter")?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                    label: Some("headless"),
+                },
+                None,
+            )
+            .await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: size.w,
+            height: size.h,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+
+        Ok(Self {
+            surface: None,
+            device,
+            queue,
+            config,
+            fb_size: size,
+        })
+    }
+
+    /// Panics if headless
+    pub(crate) fn surface(&self) -> &wgpu::Surface {
+        self.surface.as_ref().expect("headless GPU has no surface")
+    }
+
     /// Updates the frame buffer
     ///
     /// - `new_size`: size not mulitplied by DPI scaling factor
@@ -91,6 +140,71 @@ impl Gpu {
         self.fb_size = new_size;
         self.config.width = new_size.w;
         self.config.height = new_size.h;
-        self.surface.configure(&self.device, &self.config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Creates a shader module from preprocessed WGSL
+    ///
+    /// Validation errors are returned with locations in the source files instead of panicking.
+    pub fn create_shader_module(
+        &self,
+        label: &str,
+        src: &ShaderSource,
+    ) -> Result<wgpu::ShaderModule> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(src.code().into()),
+            });
+
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(err) => Err(anyhow!(
+                "invalid shader `{}`: {}",
+                label,
+                src.map_error(&err.to_string())
+            )),
+            None => Ok(module),
+        }
+    }
+
+    /// Creates a compute pipeline from WGSL source
+    pub fn create_compute_pipeline(
+        &self,
+        label: &str,
+        src: &str,
+        entry_point: &str,
+        layouts: &[&wgpu::BindGroupLayout],
+    ) -> wgpu::ComputePipeline {
+        let shader = self
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(src.into()),
+            });
+
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+
+        self.device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point,
+            })
+    }
+
+    /// Number of workgroups to cover `n` invocations
+    pub fn n_workgroups(n: u32, workgroup_size: u32) -> u32 {
+        n.div_ceil(workgroup_size)
     }
 }
@@ -0,0 +1,524 @@
+//! Materials: pipeline + texture + sampler + uniform parameters
+//!
+//! Bind group layout:
+//!
+//! - group 0: texture (binding 0) + sampler (binding 1), cached by `(texture, sampler)` handles
+//! - group 1: [`MaterialParams`] uniform (binding 0), one per material
+//!
+//! Pipelines are created with a [`BlendMode`] preset. Textures premultiplied on load (see
+//! [`Assets::premultiplied_alpha`](crate::gfx::Assets::premultiplied_alpha)) are drawn with
+//! [`BlendMode::Premultiplied`].
+//!
+//! Textures and shaders can be [`Handle`]s from [`Assets`](crate::gfx::Assets). Call
+//! [`Materials::refresh`] after reloading assets to rebuild the bind groups and pipelines
+//! derived from them.
+
+use std::{cell::Ref, collections::HashMap};
+
+use in_common::{
+    assets::Handle,
+    blend::{BlendComponent, BlendFactor, BlendMode},
+};
+use vek::{Vec2, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::gfx::{Gpu, Index, StaticMesh, Texture, Vertex};
+
+/// Handle to a pipeline in [`Materials`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineHandle(usize);
+
+/// Handle to a texture in [`Materials`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+/// Handle to a sampler in [`Materials`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerHandle(usize);
+
+/// Handle to a material in [`Materials`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(usize);
+
+/// Uniform parameters of a material (`MaterialParams` in WGSL)
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct MaterialParams {
+    /// Multiplied with texture color and vertex color
+    pub tint: Vec4<f32>,
+    /// `uv * uv_scale + uv_offset`
+    pub uv_offset: Vec2<f32>,
+    pub uv_scale: Vec2<f32>,
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        Self {
+            tint: Vec4::one(),
+            uv_offset: Vec2::zero(),
+            uv_scale: Vec2::one(),
+        }
+    }
+}
+
+/// Description of a material
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    pub pipeline: PipelineHandle,
+    pub texture: TextureHandle,
+    /// `None`: use the sampler of the texture
+    pub sampler: Option<SamplerHandle>,
+    pub params: MaterialParams,
+}
+
+/// (texture, sampler) bind group cache key
+type TextureKey = (TextureHandle, Option<SamplerHandle>);
+
+/// Creates a pipeline from WGSL source (`material_rpip::<V>`)
+type RpipFn = fn(
+    &wgpu::Device,
+    &str,
+    wgpu::TextureFormat,
+    BlendMode,
+    &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline;
+
+#[derive(Debug)]
+struct PipelineEntry {
+    rpip: wgpu::RenderPipeline,
+    blend: BlendMode,
+    /// Shader asset, generation of the shader and pipeline constructor
+    source: Option<(Handle<String>, u32, RpipFn)>,
+}
+
+#[derive(Debug)]
+struct MaterialEntry {
+    desc: Material,
+    params_buf: wgpu::Buffer,
+    params_group: wgpu::BindGroup,
+}
+
+/// Storage of pipelines, textures, samplers and materials
+///
+/// Bind groups are created on [`add`](Self::add) or [`set_texture`](Self::set_texture) and
+/// reused by every material referring to the same texture and sampler.
+#[derive(Debug)]
+pub struct Materials {
+    texture_layout: wgpu::BindGroupLayout,
+    params_layout: wgpu::BindGroupLayout,
+    pipelines: Vec<PipelineEntry>,
+    textures: Vec<Handle<Texture>>,
+    samplers: Vec<wgpu::Sampler>,
+    materials: Vec<MaterialEntry>,
+    /// Bind groups with the generation of the texture
+    texture_groups: HashMap<TextureKey, (u32, wgpu::BindGroup)>,
+}
+
+impl Materials {
+    pub fn new(gpu: &Gpu) -> Self {
+        let texture_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                    label: Some("texture-bind-group-layout"),
+                });
+
+        let params_layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<MaterialParams>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+                label: Some("material-bind-group-layout"),
+            });
+
+        Self {
+            texture_layout,
+            params_layout,
+            pipelines: Vec::new(),
+            textures: Vec::new(),
+            samplers: Vec::new(),
+            materials: Vec::new(),
+            texture_groups: HashMap::new(),
+        }
+    }
+
+    /// Bind group layouts in group order
+    pub fn layouts(&self) -> [&wgpu::BindGroupLayout; 2] {
+        [&self.texture_layout, &self.params_layout]
+    }
+
+    /// Creates a render pipeline for WGSL source with `vs_main` and `fs_main`
+    pub fn add_pipeline<V: Vertex>(
+        &mut self,
+        gpu: &Gpu,
+        src: &str,
+        blend: BlendMode,
+    ) -> PipelineHandle {
+        let rpip =
+            self::material_rpip::<V>(&gpu.device, src, gpu.config.format, blend, &self.layouts());
+        self.pipelines.push(PipelineEntry {
+            rpip,
+            blend,
+            source: None,
+        });
+        PipelineHandle(self.pipelines.len() - 1)
+    }
+
+    /// Creates a render pipeline for a WGSL shader asset. It's rebuilt by
+    /// [`refresh`](Self::refresh) when the shader is reloaded
+    pub fn add_pipeline_asset<V: Vertex>(
+        &mut self,
+        gpu: &Gpu,
+        src: Handle<String>,
+        blend: BlendMode,
+    ) -> PipelineHandle {
+        let rpip = self::material_rpip::<V>(
+            &gpu.device,
+            &src.get(),
+            gpu.config.format,
+            blend,
+            &self.layouts(),
+        );
+        let generation = src.generation();
+        self.pipelines.push(PipelineEntry {
+            rpip,
+            blend,
+            source: Some((src, generation, self::material_rpip::<V>)),
+        });
+        PipelineHandle(self.pipelines.len() - 1)
+    }
+
+    /// Adds a pipeline created with [`layouts`](Self::layouts)
+    pub fn add_raw_pipeline(&mut self, rpip: wgpu::RenderPipeline) -> PipelineHandle {
+        self.pipelines.push(PipelineEntry {
+            rpip,
+            // only for rebuilding from source
+            blend: BlendMode::Opaque,
+            source: None,
+        });
+        PipelineHandle(self.pipelines.len() - 1)
+    }
+
+    pub fn add_texture(&mut self, texture: Texture) -> TextureHandle {
+        self.add_texture_asset(Handle::new(texture))
+    }
+
+    /// Adds a texture asset. Bind groups are rebuilt by [`refresh`](Self::refresh) when it's
+    /// reloaded
+    pub fn add_texture_asset(&mut self, texture: Handle<Texture>) -> TextureHandle {
+        assert_eq!(
+            texture.get().view_dimension(),
+            wgpu::TextureViewDimension::D2,
+            "materials take 2D textures only"
+        );
+        self.textures.push(texture);
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// Swaps the texture and rebuilds bind groups referring to the old one
+    pub fn replace_texture(&mut self, gpu: &Gpu, handle: TextureHandle, texture: Texture) {
+        self.textures[handle.0].replace(texture);
+        self.refresh(gpu);
+    }
+
+    pub fn texture(&self, handle: TextureHandle) -> Ref<'_, Texture> {
+        self.textures[handle.0].get()
+    }
+
+    /// Rebuilds bind groups and pipelines of reloaded assets
+    ///
+    /// Pipelines with invalid shaders are kept as they were.
+    pub fn refresh(&mut self, gpu: &Gpu) {
+        let stale = self
+            .texture_groups
+            .iter()
+            .filter(|(key, (gen, _))| self.textures[key.0 .0].generation() != *gen)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in stale {
+            self.texture_groups.remove(&key);
+            self.cache_texture_group(gpu, key);
+        }
+
+        let layouts = [&self.texture_layout, &self.params_layout];
+        for entry in &mut self.pipelines {
+            let (src, gen, f) = match &mut entry.source {
+                Some((src, gen, f)) if src.generation() != *gen => (src, gen, f),
+                _ => continue,
+            };
+            *gen = src.generation();
+
+            gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let rpip = f(
+                &gpu.device,
+                &src.get(),
+                gpu.config.format,
+                entry.blend,
+                &layouts,
+            );
+            match pollster::block_on(gpu.device.pop_error_scope()) {
+                None => entry.rpip = rpip,
+                Some(err) => log::error!("failed to rebuild pipeline ({:?}): {}", src.path(), err),
+            }
+        }
+    }
+
+    pub fn add_sampler(&mut self, sampler: wgpu::Sampler) -> SamplerHandle {
+        self.samplers.push(sampler);
+        SamplerHandle(self.samplers.len() - 1)
+    }
+
+    pub fn add(&mut self, gpu: &Gpu, desc: Material) -> MaterialHandle {
+        self.cache_texture_group(gpu, (desc.texture, desc.sampler));
+
+        let params_buf = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("material-params"),
+                contents: bytemuck::bytes_of(&desc.params),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let params_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buf.as_entire_binding(),
+            }],
+            label: Some("material-bind-group"),
+        });
+
+        self.materials.push(MaterialEntry {
+            desc,
+            params_buf,
+            params_group,
+        });
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    pub fn get(&self, handle: MaterialHandle) -> &Material {
+        &self.materials[handle.0].desc
+    }
+
+    /// Uploads new uniform parameters
+    pub fn set_params(&mut self, gpu: &Gpu, handle: MaterialHandle, params: MaterialParams) {
+        let entry = &mut self.materials[handle.0];
+        entry.desc.params = params;
+        gpu.queue
+            .write_buffer(&entry.params_buf, 0, bytemuck::bytes_of(&params));
+    }
+
+    pub fn set_texture(
+        &mut self,
+        gpu: &Gpu,
+        handle: MaterialHandle,
+        texture: TextureHandle,
+        sampler: Option<SamplerHandle>,
+    ) {
+        self.cache_texture_group(gpu, (texture, sampler));
+        let desc = &mut self.materials[handle.0].desc;
+        desc.texture = texture;
+        desc.sampler = sampler;
+    }
+
+    /// Sets the pipeline and the bind groups of the material
+    pub fn apply<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, handle: MaterialHandle) {
+        let entry = &self.materials[handle.0];
+        let key = (entry.desc.texture, entry.desc.sampler);
+
+        rpass.set_pipeline(&self.pipelines[entry.desc.pipeline.0].rpip);
+        rpass.set_bind_group(0, &self.texture_groups[&key].1, &[]);
+        rpass.set_bind_group(1, &entry.params_group, &[]);
+    }
+
+    /// Applies the material and draws the whole mesh
+    pub fn draw_mesh<'a, V, I>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        mesh: &'a StaticMesh<V, I>,
+        handle: MaterialHandle,
+    ) where
+        V: bytemuck::Pod + Vertex,
+        I: Index,
+    {
+        self.apply(rpass, handle);
+        mesh.draw_range(rpass, 0..mesh.n_indices(), 0);
+    }
+
+    /// Draws every sub-mesh with the material in its slot
+    ///
+    /// * `slots`: `SubMesh::material` -> material. Sub-meshes without a material or with one out
+    ///   of `slots` use `slots[0]`. Nothing is drawn if `slots` is empty.
+    pub fn draw_submeshes<'a, V, I>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        mesh: &'a StaticMesh<V, I>,
+        slots: &[MaterialHandle],
+    ) where
+        V: bytemuck::Pod + Vertex,
+        I: Index,
+    {
+        let fallback = match slots.first() {
+            Some(h) => *h,
+            None => return,
+        };
+
+        for (i, sub) in mesh.submeshes().iter().enumerate() {
+            let handle = sub
+                .material
+                .and_then(|m| slots.get(m))
+                .copied()
+                .unwrap_or(fallback);
+            self.apply(rpass, handle);
+            mesh.draw_submesh(rpass, i);
+        }
+    }
+
+    fn cache_texture_group(&mut self, gpu: &Gpu, key: TextureKey) {
+        if self.texture_groups.contains_key(&key) {
+            return;
+        }
+
+        let handle = &self.textures[key.0 .0];
+        let texture = handle.get();
+        let sampler = match key.1 {
+            Some(s) => &self.samplers[s.0],
+            None => &texture.sampler,
+        };
+
+        let group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("texture-bind-group"),
+        });
+
+        self.texture_groups
+            .insert(key, (handle.generation(), group));
+    }
+}
+
+fn material_rpip<V: Vertex>(
+    device: &wgpu::Device,
+    src: &str,
+    tex_fmt: wgpu::TextureFormat,
+    blend: BlendMode,
+    layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("shader"),
+        source: wgpu::ShaderSource::Wgsl(src.into()),
+    });
+
+    let rpip_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("render-pipeline-layout"),
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("render-pipeline"),
+        layout: Some(&rpip_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[V::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: tex_fmt,
+                blend: self::blend_state(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            // cull the back face
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // other than this requires`Features::NON_FILL_POLYGON_MODE`
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // requires `Features::DEPTH_CLIP_CONTROL`
+            unclipped_depth: false,
+            // requires `Features::CONSERVATIVE_RASTERIZATION`
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1, // single sampling
+            mask: !0, // all samples are active
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// `None` for [`BlendMode::Opaque`]
+pub fn blend_state(mode: BlendMode) -> Option<wgpu::BlendState> {
+    let eq = mode.equation()?;
+    Some(wgpu::BlendState {
+        color: self::blend_component(eq.color),
+        alpha: self::blend_component(eq.alpha),
+    })
+}
+
+fn blend_component(c: BlendComponent) -> wgpu::BlendComponent {
+    wgpu::BlendComponent {
+        src_factor: self::blend_factor(c.src),
+        dst_factor: self::blend_factor(c.dst),
+        operation: wgpu::BlendOperation::Add,
+    }
+}
+
+fn blend_factor(f: BlendFactor) -> wgpu::BlendFactor {
+    match f {
+        BlendFactor::Zero => wgpu::BlendFactor::Zero,
+        BlendFactor::One => wgpu::BlendFactor::One,
+        BlendFactor::SrcColor => wgpu::BlendFactor::Src,
+        BlendFactor::OneMinusSrcColor => wgpu::BlendFactor::OneMinusSrc,
+        BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => wgpu::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstColor => wgpu::BlendFactor::Dst,
+        BlendFactor::OneMinusDstColor => wgpu::BlendFactor::OneMinusDst,
+        BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
+    }
+}
@@ -1,7 +1,10 @@
 //! Vertex/index buffer in handy API
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, mem, ops::Range};
 
+use anyhow::*;
+use in_common::{clip::ClipBatch, mesh::SubMesh};
+use vek::Extent2;
 use wgpu::util::DeviceExt;
 
 use crate::gfx::Vertex;
@@ -28,7 +31,7 @@ impl Index for u32 {
 pub struct StaticMesh<V, I> {
     /// CPU vertices (no need to keep the memory)
     _verts: PhantomData<V>,
-    _n_verts: u32,
+    n_verts: u32,
     /// GPU vertices
     vbuf: wgpu::Buffer,
     /// GPU indices
@@ -36,33 +39,122 @@ pub struct StaticMesh<V, I> {
     n_indices: u32,
     /// GPU indices
     ibuf: wgpu::Buffer,
+    /// Named index ranges
+    submeshes: Vec<SubMesh>,
+    /// If the buffers have `COPY_DST` usage
+    updatable: bool,
 }
 
 impl<V: bytemuck::Pod + Vertex, I: Index> StaticMesh<V, I> {
     pub fn new(device: &wgpu::Device, verts: &[V], indices: &[I]) -> Self {
+        Self::with_usage(device, verts, indices, wgpu::BufferUsages::empty())
+    }
+
+    /// Creates buffers with `COPY_DST` usage so that they can be edited with
+    /// [`update_vertices`](Self::update_vertices) and [`update_indices`](Self::update_indices)
+    pub fn new_updatable(device: &wgpu::Device, verts: &[V], indices: &[I]) -> Self {
+        Self::with_usage(device, verts, indices, wgpu::BufferUsages::COPY_DST)
+    }
+
+    fn with_usage(
+        device: &wgpu::Device,
+        verts: &[V],
+        indices: &[I],
+        extra: wgpu::BufferUsages,
+    ) -> Self {
         assert!(verts.len() <= std::u32::MAX as usize);
 
+        // NOTE: `create_buffer_init` pads the buffers to `wgpu::COPY_BUFFER_ALIGNMENT`
         let vbuf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("static-mesh-vbuf"),
             contents: bytemuck::cast_slice(verts),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | extra,
         });
 
         let ibuf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: bytemuck::cast_slice(indices),
-            usage: wgpu::BufferUsages::INDEX,
+            usage: wgpu::BufferUsages::INDEX | extra,
         });
         let n_indices = indices.len() as u32;
 
         Self {
             _verts: PhantomData,
-            _n_verts: verts.len() as u32,
+            n_verts: verts.len() as u32,
             vbuf,
             _indices: PhantomData,
             ibuf,
             n_indices,
+            submeshes: Vec::new(),
+            updatable: extra.contains(wgpu::BufferUsages::COPY_DST),
+        }
+    }
+
+    pub fn n_verts(&self) -> u32 {
+        self.n_verts
+    }
+
+    pub fn n_indices(&self) -> u32 {
+        self.n_indices
+    }
+
+    /// Overwrites vertices in `range` (via `queue.write_buffer`)
+    ///
+    /// The byte offset and size have to be multiples of `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn update_vertices(
+        &self,
+        queue: &wgpu::Queue,
+        range: Range<u32>,
+        verts: &[V],
+    ) -> Result<()> {
+        self::write_range(
+            queue,
+            &self.vbuf,
+            self.updatable,
+            self.n_verts,
+            range,
+            verts,
+        )
+        .context("unable to update vertices")
+    }
+
+    /// Overwrites indices in `range` (via `queue.write_buffer`)
+    ///
+    /// The byte offset has to be a multiple of `wgpu::COPY_BUFFER_ALIGNMENT`. So does the size,
+    /// except for a range that reaches the end of the buffer (the padding is written then).
+    pub fn update_indices(
+        &self,
+        queue: &wgpu::Queue,
+        range: Range<u32>,
+        indices: &[I],
+    ) -> Result<()> {
+        self::write_range(
+            queue,
+            &self.ibuf,
+            self.updatable,
+            self.n_indices,
+            range,
+            indices,
+        )
+        .context("unable to update indices")
+    }
+
+    /// Sets named index ranges. Fails if any of them is out of the index buffer
+    pub fn with_submeshes(mut self, submeshes: Vec<SubMesh>) -> Result<Self> {
+        for sub in &submeshes {
+            sub.check(self.n_indices)?;
         }
+        self.submeshes = submeshes;
+        Ok(self)
+    }
+
+    pub fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    /// Index of sub-mesh with the name
+    pub fn find_submesh(&self, name: &str) -> Option<usize> {
+        self.submeshes.iter().position(|sub| sub.name == name)
     }
 
     pub fn draw_all<'v, 'p>(&'v mut self, rpass: &'p mut wgpu::RenderPass<'v>) {
@@ -70,4 +162,103 @@ impl<V: bytemuck::Pod + Vertex, I: Index> StaticMesh<V, I> {
         rpass.set_index_buffer(self.ibuf.slice(..), I::format());
         rpass.draw_indexed(0..self.n_indices, 0, 0..1);
     }
+
+    /// Draws a range of the index buffer
+    ///
+    /// * `base_vertex`: added to each index before fetching a vertex
+    pub fn draw_range<'v>(
+        &'v self,
+        rpass: &mut wgpu::RenderPass<'v>,
+        indices: Range<u32>,
+        base_vertex: i32,
+    ) {
+        assert!(
+            indices.start <= indices.end && indices.end <= self.n_indices,
+            "index range {:?} is out of the index buffer ({} indices)",
+            indices,
+            self.n_indices
+        );
+        rpass.set_vertex_buffer(0, self.vbuf.slice(..));
+        rpass.set_index_buffer(self.ibuf.slice(..), I::format());
+        rpass.draw_indexed(indices, base_vertex, 0..1);
+    }
+
+    /// Draws the `ix`-th sub-mesh. Be sure to bind its material before calling this
+    pub fn draw_submesh<'v>(&'v self, rpass: &mut wgpu::RenderPass<'v>, ix: usize) {
+        let sub = &self.submeshes[ix];
+        self.draw_range(rpass, sub.indices(), sub.base_vertex);
+    }
+
+    /// Draws each batch with its scissor rect. Fully clipped batches are skipped
+    ///
+    /// The scissor rect is reset to the whole frame buffer (`fb_size`) afterwards.
+    pub fn draw_clipped<'v>(
+        &'v self,
+        rpass: &mut wgpu::RenderPass<'v>,
+        batches: &[ClipBatch],
+        fb_size: Extent2<u32>,
+    ) {
+        for batch in batches.iter().filter(|b| !b.is_empty()) {
+            let r = batch.scissor;
+            rpass.set_scissor_rect(r.x, r.y, r.w, r.h);
+            self.draw_range(rpass, batch.indices(), 0);
+        }
+        rpass.set_scissor_rect(0, 0, fb_size.w, fb_size.h);
+    }
+}
+
+/// Validates and writes `data` to `buf[range]` (in elements)
+fn write_range<T: bytemuck::Pod>(
+    queue: &wgpu::Queue,
+    buf: &wgpu::Buffer,
+    updatable: bool,
+    len: u32,
+    range: Range<u32>,
+    data: &[T],
+) -> Result<()> {
+    // power of two
+    const ALIGN: u64 = wgpu::COPY_BUFFER_ALIGNMENT;
+    const MASK: u64 = ALIGN - 1;
+
+    ensure!(updatable, "the mesh was not created with `new_updatable`");
+    ensure!(
+        range.start <= range.end && range.end <= len,
+        "range {:?} is out of bounds (length {})",
+        range,
+        len
+    );
+    ensure!(
+        (range.end - range.start) as usize == data.len(),
+        "range {:?} doesn't match data length {}",
+        range,
+        data.len()
+    );
+
+    let stride = mem::size_of::<T>() as u64;
+    let offset = range.start as u64 * stride;
+    ensure!(
+        offset & MASK == 0,
+        "byte offset {} is not a multiple of {}",
+        offset,
+        ALIGN
+    );
+
+    let bytes: &[u8] = bytemuck::cast_slice(data);
+    if bytes.len() as u64 & MASK == 0 {
+        queue.write_buffer(buf, offset, bytes);
+        return Ok(());
+    }
+
+    // the buffer is padded at the end, so the tail can be written with padding
+    ensure!(
+        range.end == len,
+        "byte size {} is not a multiple of {} (only the tail of the buffer can be unaligned)",
+        bytes.len(),
+        ALIGN
+    );
+    let mut padded = bytes.to_vec();
+    padded.resize(((bytes.len() as u64 + MASK) & !MASK) as usize, 0);
+    queue.write_buffer(buf, offset, &padded);
+
+    Ok(())
 }
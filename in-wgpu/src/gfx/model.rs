@@ -0,0 +1,72 @@
+//! Imported models (OBJ, glTF) as [`StaticMesh`]es
+
+use std::path::Path;
+
+use anyhow::*;
+use in_common::mesh::{MaterialData, MeshVertex, ModelData};
+
+use crate::gfx::{Gpu, StaticMesh, Texture, Vertex};
+
+/// Mesh of a [`Model`] with material reference
+#[derive(Debug)]
+pub struct ModelMesh<V> {
+    pub name: String,
+    pub mesh: StaticMesh<V, u32>,
+    /// Index of [`Model::materials`]
+    pub material: Option<usize>,
+}
+
+/// Meshes, materials and textures loaded from a model file
+#[derive(Debug)]
+pub struct Model<V> {
+    pub meshes: Vec<ModelMesh<V>>,
+    pub materials: Vec<MaterialData>,
+    /// Referred to by [`MaterialData::texture`]
+    pub textures: Vec<Texture>,
+}
+
+impl<V: bytemuck::Pod + Vertex + From<MeshVertex>> Model<V> {
+    /// Loads `.obj`, `.gltf` or `.glb` file
+    pub fn load(gpu: &Gpu, path: impl AsRef<Path>) -> Result<Self> {
+        let data = ModelData::load(path.as_ref())?;
+        Self::from_data(gpu, &data, path.as_ref().to_str())
+    }
+
+    pub fn from_data(gpu: &Gpu, data: &ModelData, label: Option<&str>) -> Result<Self> {
+        let meshes = data
+            .meshes
+            .iter()
+            .map(|mesh| ModelMesh {
+                name: mesh.name.clone(),
+                mesh: StaticMesh::new(&gpu.device, &mesh.vertices::<V>(), &mesh.indices),
+                material: mesh.material,
+            })
+            .collect();
+
+        let textures = data
+            .images
+            .iter()
+            .map(|img| {
+                let img = image::DynamicImage::ImageRgba8(img.clone());
+                Texture::from_image(&gpu.device, &gpu.queue, &img, label)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            meshes,
+            materials: data.materials.clone(),
+            textures,
+        })
+    }
+
+    pub fn material(&self, mesh: &ModelMesh<V>) -> Option<&MaterialData> {
+        mesh.material.and_then(|i| self.materials.get(i))
+    }
+
+    /// Base color texture of the mesh
+    pub fn texture(&self, mesh: &ModelMesh<V>) -> Option<&Texture> {
+        self.material(mesh)
+            .and_then(|m| m.texture)
+            .and_then(|i| self.textures.get(i))
+    }
+}
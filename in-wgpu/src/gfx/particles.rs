@@ -0,0 +1,661 @@
+//! GPU particles: simulated by a compute shader, drawn as instanced quads
+//!
+//! Particles live in one storage buffer that doubles as the instance vertex buffer. Each frame
+//! the CPU only uploads the emitters and the number of particles they spawn; dead slots are
+//! handed out to emitters with an atomic counter.
+//!
+//! Bind group layouts: simulation (group 0: particles, emitters, parameters, counter) and
+//! drawing (group 0: camera uniform).
+
+use std::time::Duration;
+
+use anyhow::*;
+use in_common::{blend::BlendMode, camera::Camera2d};
+use vek::{Mat4, Rgba, Vec2, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::gfx::{blend_state, readback, Gpu, Uniform};
+
+/// Maximum number of emitters per [`ParticleSystem`]
+pub const MAX_EMITTERS: usize = 16;
+
+/// Invocations per workgroup of `particles_sim.wgsl`
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Particle in the storage buffer (std430 layout)
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Particle {
+    /// World position
+    pub pos: Vec2<f32>,
+    /// World units per second
+    pub vel: Vec2<f32>,
+    pub color: Vec4<f32>,
+    /// Diameter in world units
+    pub size: f32,
+    /// Seconds since spawn
+    pub age: f32,
+    /// Lifetime in seconds
+    pub life: f32,
+    /// Index of the emitter that spawned it
+    pub emitter: u32,
+}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.age < self.life
+    }
+}
+
+/// Particle source
+///
+/// Directions are in Y-down world coordinates: angles are clockwise on screen from +X.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub pos: Vec2<f32>,
+    /// Particles per second (continuous emission)
+    pub rate: f32,
+    /// Direction in radians
+    pub angle: f32,
+    /// Cone angle in radians around `angle` (`TAU` for every direction)
+    pub spread: f32,
+    /// Initial speed range in world units per second
+    pub speed: [f32; 2],
+    /// Lifetime range in seconds
+    pub life: [f32; 2],
+    /// Size at spawn and at death (interpolated linearly)
+    pub size: [f32; 2],
+    /// Color at spawn and at death (interpolated linearly)
+    pub color: [Rgba<f32>; 2],
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            pos: Vec2::zero(),
+            rate: 0.0,
+            angle: 0.0,
+            spread: std::f32::consts::TAU,
+            speed: [32.0, 64.0],
+            life: [1.0, 1.0],
+            size: [4.0, 4.0],
+            color: [Rgba::white(), Rgba::new(1.0, 1.0, 1.0, 0.0)],
+        }
+    }
+}
+
+impl Emitter {
+    pub fn new(pos: impl Into<Vec2<f32>>, rate: f32) -> Self {
+        Self {
+            pos: pos.into(),
+            rate,
+            ..Default::default()
+        }
+    }
+
+    /// Emits towards `angle` within a cone of `spread` radians
+    pub fn cone(mut self, angle: f32, spread: f32) -> Self {
+        self.angle = angle;
+        self.spread = spread;
+        self
+    }
+
+    pub fn speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = [min, max];
+        self
+    }
+
+    pub fn life(mut self, min: f32, max: f32) -> Self {
+        self.life = [min, max];
+        self
+    }
+
+    pub fn size(mut self, start: f32, end: f32) -> Self {
+        self.size = [start, end];
+        self
+    }
+
+    pub fn color(mut self, start: impl Into<Rgba<f32>>, end: impl Into<Rgba<f32>>) -> Self {
+        self.color = [start.into(), end.into()];
+        self
+    }
+}
+
+/// [`Emitter`] in the storage buffer
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GpuEmitter {
+    pos: Vec2<f32>,
+    angle: f32,
+    spread: f32,
+    speed: Vec2<f32>,
+    life: Vec2<f32>,
+    color_start: Vec4<f32>,
+    color_end: Vec4<f32>,
+    size: Vec2<f32>,
+    /// Particles to spawn this frame
+    count: u32,
+    _pad: u32,
+}
+
+/// Uniform of `particles_sim.wgsl`
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct SimParams {
+    gravity: Vec2<f32>,
+    dt: f32,
+    drag: f32,
+    seed: u32,
+    n_emitters: u32,
+    n_spawn: u32,
+    _pad: u32,
+}
+
+in_common::impl_uniform_block!(SimParams {
+    gravity: Vec2,
+    dt: Float,
+    drag: Float,
+    seed: UInt,
+    n_emitters: UInt,
+    n_spawn: UInt,
+});
+
+/// Fixed-capacity pool of GPU-simulated particles
+///
+/// Call [`update`](Self::update) every frame and then [`draw`](Self::draw) in a render pass.
+/// [`read_particles`](Self::read_particles) copies the simulation state back to the CPU.
+#[derive(Debug)]
+pub struct ParticleSystem {
+    capacity: u32,
+    /// Storage + instance buffer of [`Particle`]s
+    particles: wgpu::Buffer,
+    emitters_buf: wgpu::Buffer,
+    params: Uniform<SimParams>,
+    counter_buf: wgpu::Buffer,
+    sim_group: wgpu::BindGroup,
+    sim_pip: wgpu::ComputePipeline,
+    /// Column-major view-projection matrix
+    camera: Uniform<[f32; 16]>,
+    draw_pip: wgpu::RenderPipeline,
+    emitters: Vec<Emitter>,
+    /// Fractional particles carried over to the next frame, per emitter
+    carry: Vec<f32>,
+    /// One-shot spawns for the next frame, per emitter
+    bursts: Vec<u32>,
+    /// World units per second squared
+    pub gravity: Vec2<f32>,
+    /// Velocity damping per second
+    pub drag: f32,
+    frame: u32,
+}
+
+impl ParticleSystem {
+    /// * `capacity`: maximum number of live particles. Spawns are dropped while it's full
+    /// * `blend`: `BlendMode::Additive` for glowing effects, `BlendMode::Premultiplied` otherwise
+    pub fn new(gpu: &Gpu, capacity: u32, blend: BlendMode) -> Result<Self> {
+        assert!(capacity > 0, "zero-capacity particle system");
+        let device = &gpu.device;
+
+        let particles = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("particles"),
+            // zeroed particles are dead
+            contents: bytemuck::cast_slice(&vec![Particle::default(); capacity as usize]),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let emitters_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle-emitters"),
+            size: (std::mem::size_of::<GpuEmitter>() * MAX_EMITTERS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params = Uniform::new(
+            gpu,
+            &SimParams::default(),
+            wgpu::ShaderStages::COMPUTE,
+            "particle-params",
+        )?;
+        let counter_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle-counter"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sim_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage(0, false),
+                storage(1, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<SimParams>() as u64
+                        ),
+                    },
+                    count: None,
+                },
+                storage(3, false),
+            ],
+            label: Some("particle-sim-bind-group-layout"),
+        });
+        let sim_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sim_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particles.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: emitters_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: counter_buf.as_entire_binding(),
+                },
+            ],
+            label: Some("particle-sim-bind-group"),
+        });
+        let sim_pip = gpu.create_compute_pipeline(
+            "particle-sim",
+            include_str!("../particles_sim.wgsl"),
+            "cs_main",
+            &[&sim_layout],
+        );
+
+        let camera = Uniform::new(
+            gpu,
+            &Mat4::<f32>::identity().into_col_array(),
+            wgpu::ShaderStages::VERTEX,
+            "particle-camera",
+        )?;
+        let draw_pip =
+            self::particle_rpip(device, gpu.config.format, blend, camera.bind_group_layout());
+
+        Ok(Self {
+            capacity,
+            particles,
+            emitters_buf,
+            params,
+            counter_buf,
+            sim_group,
+            sim_pip,
+            camera,
+            draw_pip,
+            emitters: Vec::new(),
+            carry: Vec::new(),
+            bursts: Vec::new(),
+            gravity: Vec2::zero(),
+            drag: 0.0,
+            frame: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the emitter index
+    pub fn add_emitter(&mut self, emitter: Emitter) -> Result<usize> {
+        ensure!(
+            self.emitters.len() < MAX_EMITTERS,
+            "too many particle emitters (max {})",
+            MAX_EMITTERS
+        );
+        self.emitters.push(emitter);
+        self.carry.push(0.0);
+        self.bursts.push(0);
+        Ok(self.emitters.len() - 1)
+    }
+
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    /// Moving an emitter doesn't move the particles it has spawned
+    pub fn emitter_mut(&mut self, ix: usize) -> &mut Emitter {
+        &mut self.emitters[ix]
+    }
+
+    /// Spawns `n` particles from an emitter on the next update
+    pub fn burst(&mut self, emitter: usize, n: u32) {
+        self.bursts[emitter] += n;
+    }
+
+    /// Kills every particle
+    pub fn clear(&mut self, gpu: &Gpu) {
+        let dead = vec![Particle::default(); self.capacity as usize];
+        gpu.queue
+            .write_buffer(&self.particles, 0, bytemuck::cast_slice(&dead));
+    }
+
+    /// Advances the simulation and uploads the camera
+    pub fn update(&mut self, gpu: &Gpu, camera: &Camera2d, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        let mut n_spawn = 0;
+        let emitters = self
+            .emitters
+            .iter()
+            .zip(self.carry.iter_mut().zip(&mut self.bursts))
+            .map(|(e, (carry, burst))| {
+                *carry += e.rate.max(0.0) * dt;
+                let count = carry.floor();
+                *carry -= count;
+                let count = count as u32 + std::mem::take(burst);
+                n_spawn += count;
+
+                GpuEmitter {
+                    pos: e.pos,
+                    angle: e.angle,
+                    spread: e.spread,
+                    speed: e.speed.into(),
+                    life: e.life.into(),
+                    color_start: Vec4::from(e.color[0]),
+                    color_end: Vec4::from(e.color[1]),
+                    size: e.size.into(),
+                    count,
+                    _pad: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let params = SimParams {
+            gravity: self.gravity,
+            dt,
+            drag: self.drag,
+            seed: self.frame,
+            n_emitters: emitters.len() as u32,
+            // nothing to spawn from
+            n_spawn: if emitters.is_empty() { 0 } else { n_spawn },
+            _pad: 0,
+        };
+        self.frame = self.frame.wrapping_add(1);
+
+        if !emitters.is_empty() {
+            gpu.queue
+                .write_buffer(&self.emitters_buf, 0, bytemuck::cast_slice(&emitters));
+        }
+        self.params.write(gpu, &params);
+        gpu.queue
+            .write_buffer(&self.counter_buf, 0, bytemuck::bytes_of(&0u32));
+        self.camera.write(gpu, &camera.matrix().into_col_array());
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("particle-sim-encoder"),
+            });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("particle-sim-pass"),
+            });
+            cpass.set_pipeline(&self.sim_pip);
+            cpass.set_bind_group(0, &self.sim_group, &[]);
+            cpass.dispatch(Gpu::n_workgroups(self.capacity, WORKGROUP_SIZE), 1, 1);
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Draws every particle slot; dead ones are degenerate quads
+    pub fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        rpass.set_pipeline(&self.draw_pip);
+        rpass.set_bind_group(0, self.camera.bind_group(), &[]);
+        rpass.set_vertex_buffer(0, self.particles.slice(..));
+        rpass.draw(0..6, 0..self.capacity);
+    }
+
+    /// Copies every particle slot back to the CPU (blocking)
+    pub fn read_particles(&self, gpu: &Gpu) -> Result<Vec<Particle>> {
+        readback::read_buffer_blocking(gpu, &self.particles, 0, self.capacity as usize)
+    }
+
+    /// Live particles, read back from the GPU (blocking)
+    pub fn read_alive(&self, gpu: &Gpu) -> Result<Vec<Particle>> {
+        let mut particles = self.read_particles(gpu)?;
+        particles.retain(Particle::is_alive);
+        Ok(particles)
+    }
+}
+
+/// Per-instance layout of [`Particle`]
+fn instance_desc() -> wgpu::VertexBufferLayout<'static> {
+    // `vel` is skipped
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = [
+        self::attr(0, 0, wgpu::VertexFormat::Float32x2),
+        self::attr(1, 16, wgpu::VertexFormat::Float32x4),
+        self::attr(2, 32, wgpu::VertexFormat::Float32),
+        self::attr(3, 36, wgpu::VertexFormat::Float32),
+        self::attr(4, 40, wgpu::VertexFormat::Float32),
+    ];
+
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+const fn attr(
+    shader_location: u32,
+    offset: wgpu::BufferAddress,
+    format: wgpu::VertexFormat,
+) -> wgpu::VertexAttribute {
+    wgpu::VertexAttribute {
+        format,
+        offset,
+        shader_location,
+    }
+}
+
+fn particle_rpip(
+    device: &wgpu::Device,
+    tex_fmt: wgpu::TextureFormat,
+    blend: BlendMode,
+    camera_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("particle-shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../particles.wgsl").into()),
+    });
+
+    let rpip_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("particle-pipeline-layout"),
+        bind_group_layouts: &[camera_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("particle-pipeline"),
+        layout: Some(&rpip_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[self::instance_desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: tex_fmt,
+                blend: self::blend_state(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::Extent2;
+
+    use super::*;
+
+    /// Member names and offsets, and the size of a WGSL struct
+    fn wgsl_layout(module: &naga::Module, name: &str) -> (Vec<(String, u32)>, u32) {
+        let ty = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no struct `{}`", name))
+            .1;
+
+        match &ty.inner {
+            naga::TypeInner::Struct { members, span } => {
+                let members = members
+                    .iter()
+                    .map(|m| (m.name.clone().unwrap_or_default(), m.offset))
+                    .collect();
+                (members, *span)
+            }
+            inner => panic!("`{}` is not a struct: {:?}", name, inner),
+        }
+    }
+
+    /// Rust side of [`wgsl_layout`]. Leading underscores are stripped from the field names
+    macro_rules! layout {
+        ($T:ty { $($field:ident),* $(,)? }) => {
+            (
+                vec![$((
+                    stringify!($field).trim_start_matches('_').to_string(),
+                    std::mem::offset_of!($T, $field) as u32,
+                )),*],
+                std::mem::size_of::<$T>() as u32,
+            )
+        };
+    }
+
+    /// Buffers are uploaded and read back (`read_particles`) as bytes, so the Rust structs must
+    /// match the WGSL ones
+    #[test]
+    fn layouts_match_wgsl() {
+        let module = naga::front::wgsl::parse_str(include_str!("../particles_sim.wgsl")).unwrap();
+
+        assert_eq!(
+            wgsl_layout(&module, "Particle"),
+            layout!(Particle {
+                pos,
+                vel,
+                color,
+                size,
+                age,
+                life,
+                emitter,
+            })
+        );
+        assert_eq!(
+            wgsl_layout(&module, "Emitter"),
+            layout!(GpuEmitter {
+                pos,
+                angle,
+                spread,
+                speed,
+                life,
+                color_start,
+                color_end,
+                size,
+                count,
+                _pad,
+            })
+        );
+        assert_eq!(
+            wgsl_layout(&module, "SimParams"),
+            layout!(SimParams {
+                gravity,
+                dt,
+                drag,
+                seed,
+                n_emitters,
+                n_spawn,
+                _pad,
+            })
+        );
+    }
+
+    #[test]
+    fn simulation() {
+        let gpu = match pollster::block_on(Gpu::headless(Extent2::new(64, 64))) {
+            std::result::Result::Ok(gpu) => gpu,
+            Err(err) => {
+                eprintln!("skipping the particle simulation test: {:#}", err);
+                return;
+            }
+        };
+        let camera = Camera2d::new([64.0, 64.0]);
+        let secs = Duration::from_secs_f32;
+
+        let mut sys = ParticleSystem::new(&gpu, 64, BlendMode::Premultiplied).unwrap();
+        let emitter = Emitter::new([10.0, 20.0], 0.0)
+            .cone(0.0, 0.0)
+            .speed(100.0, 100.0)
+            .life(1.0, 1.0);
+        let ix = sys.add_emitter(emitter).unwrap();
+
+        // spawned particles start at the emitter
+        sys.burst(ix, 5);
+        sys.update(&gpu, &camera, secs(0.5));
+        let alive = sys.read_alive(&gpu).unwrap();
+        assert_eq!(alive.len(), 5);
+        for p in &alive {
+            assert_eq!(
+                (p.pos, p.vel, p.age),
+                (Vec2::new(10.0, 20.0), Vec2::new(100.0, 0.0), 0.0)
+            );
+        }
+
+        // velocity integration and color interpolation
+        sys.update(&gpu, &camera, secs(0.25));
+        for p in sys.read_alive(&gpu).unwrap() {
+            assert_eq!((p.pos, p.age), (Vec2::new(35.0, 20.0), 0.25));
+            assert_eq!(p.color.w, 0.75);
+        }
+
+        // spawns beyond the capacity are dropped
+        sys.burst(ix, 100);
+        sys.update(&gpu, &camera, secs(0.25));
+        assert_eq!(sys.read_alive(&gpu).unwrap().len(), 64);
+
+        // the first ones die
+        sys.update(&gpu, &camera, secs(0.5));
+        assert_eq!(sys.read_alive(&gpu).unwrap().len(), 59);
+
+        sys.clear(&gpu);
+        assert!(sys.read_alive(&gpu).unwrap().is_empty());
+    }
+}
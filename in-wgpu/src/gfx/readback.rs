@@ -0,0 +1,206 @@
+//! GPU to CPU readback of buffers and textures
+//!
+//! Each function records a copy into a staging buffer, submits it and requests the mapping
+//! before returning. The returned future resolves once the device is polled (`device.poll` or
+//! a later `queue.submit`), so it can be awaited across frames without stalling. The
+//! `_blocking` variants wait for the GPU instead.
+//!
+//! Source buffers need `COPY_SRC` usage, and so do source textures.
+
+use std::future::Future;
+
+use anyhow::*;
+use image::RgbaImage;
+
+use crate::gfx::Gpu;
+
+/// Reads `len` elements of `T` starting at byte `offset`
+///
+/// Byte offset and size have to be multiples of `wgpu::COPY_BUFFER_ALIGNMENT` (4).
+pub fn read_buffer<T: bytemuck::Pod>(
+    gpu: &Gpu,
+    buf: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    len: usize,
+) -> impl Future<Output = Result<Vec<T>>> {
+    let size = (std::mem::size_of::<T>() * len) as wgpu::BufferAddress;
+    let staging = self::check_buffer_copy(offset, size).map(|()| {
+        let staging = self::staging_buffer(gpu, size);
+        let mut encoder = self::encoder(gpu);
+        encoder.copy_buffer_to_buffer(buf, offset, &staging, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+        self::map_read(staging)
+    });
+
+    async move {
+        let bytes = staging?.await?;
+        Ok(bytemuck::pod_collect_to_vec(&bytes))
+    }
+}
+
+/// [`read_buffer`] waiting for the GPU
+pub fn read_buffer_blocking<T: bytemuck::Pod>(
+    gpu: &Gpu,
+    buf: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    len: usize,
+) -> Result<Vec<T>> {
+    let data = self::read_buffer(gpu, buf, offset, len);
+    gpu.device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(data)
+}
+
+/// Reads one layer (or depth slice) of an 8-bit RGBA or BGRA texture, e.g. a render target
+///
+/// * `size`: size of the texture in pixels
+/// * `format`: BGRA is swizzled into RGBA. sRGB data is returned as stored (not linearized)
+pub fn read_texture(
+    gpu: &Gpu,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+    layer: u32,
+) -> impl Future<Output = Result<RgbaImage>> {
+    let [w, h] = size;
+    let bgra = match format {
+        _ if w == 0 || h == 0 => Err(anyhow!("empty texture readback ({}x{})", w, h)),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        _ => Err(anyhow!("can't read back texture format {:?}", format)),
+    };
+
+    let padded_row = self::padded_bytes_per_row(w);
+    let staging = bgra.map(|bgra| {
+        let staging = self::staging_buffer(gpu, (padded_row * h) as wgpu::BufferAddress);
+        let mut encoder = self::encoder(gpu);
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                    rows_per_image: std::num::NonZeroU32::new(h),
+                },
+            },
+            wgpu::Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+        (self::map_read(staging), bgra)
+    });
+
+    async move {
+        let (bytes, bgra) = staging?;
+        let bytes = bytes.await?;
+
+        // strip the row padding
+        let row = (4 * w) as usize;
+        let mut pixels = Vec::with_capacity(row * h as usize);
+        for padded in bytes.chunks_exact(padded_row as usize) {
+            pixels.extend_from_slice(&padded[..row]);
+        }
+        if bgra {
+            for px in pixels.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(w, h, pixels).context("texture readback size mismatch")
+    }
+}
+
+/// [`read_texture`] waiting for the GPU
+pub fn read_texture_blocking(
+    gpu: &Gpu,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+    layer: u32,
+) -> Result<RgbaImage> {
+    let img = self::read_texture(gpu, texture, format, size, layer);
+    gpu.device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(img)
+}
+
+/// Row pitch of a texture copy: `4 * width` rounded up to `COPY_BYTES_PER_ROW_ALIGNMENT`
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (4 * width).div_ceil(align) * align
+}
+
+fn check_buffer_copy(offset: wgpu::BufferAddress, size: wgpu::BufferAddress) -> Result<()> {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT;
+    ensure!(size > 0, "empty buffer readback");
+    ensure!(
+        (offset / align) * align == offset && (size / align) * align == size,
+        "buffer readback offset {} and size {} have to be multiples of {}",
+        offset,
+        size,
+        align
+    );
+    Ok(())
+}
+
+fn staging_buffer(gpu: &Gpu, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback-staging"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn encoder(gpu: &Gpu) -> wgpu::CommandEncoder {
+    gpu.device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback-encoder"),
+        })
+}
+
+/// Requests the mapping of the whole staging buffer now and copies it out when it's mapped
+fn map_read(staging: wgpu::Buffer) -> impl Future<Output = Result<Vec<u8>>> {
+    let map = staging.slice(..).map_async(wgpu::MapMode::Read);
+
+    async move {
+        map.await.context("mapping readback buffer")?;
+        let bytes = staging.slice(..).get_mapped_range().to_vec();
+        staging.unmap();
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_padding() {
+        assert_eq!(padded_bytes_per_row(0), 0);
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(100), 512);
+    }
+
+    #[test]
+    fn buffer_copy_alignment() {
+        assert!(check_buffer_copy(0, 4).is_ok());
+        assert!(check_buffer_copy(8, 16).is_ok());
+        assert!(check_buffer_copy(0, 0).is_err());
+        assert!(check_buffer_copy(2, 4).is_err());
+        assert!(check_buffer_copy(0, 6).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Tilemap rendering: chunked [`StaticMesh`]es with camera culling
+//!
+//! Bind group layout: [`Materials::layouts`] + the camera uniform (group 2, binding 0).
+
+use std::time::Duration;
+
+use anyhow::*;
+use in_common::{
+    blend::BlendMode,
+    camera::Camera2d,
+    tilemap::{ChunkMesh, TileMap},
+};
+use vek::{Mat4, Rect, Vec4};
+
+use crate::gfx::{
+    blend_state, Assets, Gpu, Material, MaterialHandle, MaterialParams, Materials, StaticMesh,
+    TriVertex, Uniform, Vertex,
+};
+
+/// Default chunk size in tiles
+pub const CHUNK_SIZE: u32 = 16;
+
+#[derive(Debug)]
+struct Chunk {
+    data: ChunkMesh,
+    mesh: StaticMesh<TriVertex, u16>,
+}
+
+/// GPU meshes of a [`TileMap`]
+///
+/// Call [`update`](Self::update) every frame and then [`draw`](Self::draw). Chunks outside of
+/// the camera are skipped, and so are layers that are not visible.
+#[derive(Debug)]
+pub struct TileMapRenderer {
+    /// Column-major view-projection matrix
+    camera: Uniform<[f32; 16]>,
+    /// Material per tileset
+    materials: Vec<MaterialHandle>,
+    /// In layer order
+    chunks: Vec<Chunk>,
+    /// Visibility per layer
+    layers: Vec<bool>,
+    /// World rect of the camera
+    view: Rect<f32, f32>,
+    /// If vertex colors are premultiplied for `BlendMode::Premultiplied`
+    premultiplied: bool,
+}
+
+impl TileMapRenderer {
+    /// Loads the tileset images and builds the chunk meshes
+    ///
+    /// Tileset images are loaded via `assets` (paths under its root are made relative to it).
+    pub fn new(
+        gpu: &Gpu,
+        assets: &mut Assets,
+        materials: &mut Materials,
+        map: &TileMap,
+        chunk_size: u32,
+    ) -> Result<Self> {
+        let camera = Uniform::new(
+            gpu,
+            &Mat4::<f32>::identity().into_col_array(),
+            wgpu::ShaderStages::VERTEX,
+            "tilemap-camera",
+        )?;
+
+        let premultiplied = assets.premultiplies_alpha();
+        let blend = if premultiplied {
+            BlendMode::Premultiplied
+        } else {
+            BlendMode::Alpha
+        };
+        let rpip = {
+            let [texture_layout, params_layout] = materials.layouts();
+            self::tilemap_rpip(
+                &gpu.device,
+                gpu.config.format,
+                blend,
+                &[texture_layout, params_layout, camera.bind_group_layout()],
+            )
+        };
+        let pipeline = materials.add_raw_pipeline(rpip);
+
+        // pixel art: no filtering across tiles
+        let sampler = materials.add_sampler(gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("tilemap-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }));
+
+        let mut tileset_materials = Vec::with_capacity(map.tilesets.len());
+        for ts in &map.tilesets {
+            let path = ts.image.strip_prefix(assets.root()).unwrap_or(&ts.image);
+            let texture = assets
+                .texture(gpu, path)
+                .with_context(|| format!("loading tileset `{}`", ts.name))?;
+            let texture = materials.add_texture_asset(texture);
+
+            tileset_materials.push(materials.add(
+                gpu,
+                Material {
+                    pipeline,
+                    texture,
+                    sampler: Some(sampler),
+                    params: MaterialParams::default(),
+                },
+            ));
+        }
+
+        let chunks = (0..map.layers.len())
+            .flat_map(|layer| map.build_chunks(layer, chunk_size, Duration::ZERO))
+            .map(|data| {
+                let verts = self::vertices(&data, premultiplied);
+                // `build_chunks` keeps the vertices `u16`-addressable
+                let indices = data.mesh.indices_u16().unwrap();
+                let mesh = StaticMesh::new_updatable(&gpu.device, &verts, &indices);
+                Chunk { data, mesh }
+            })
+            .collect();
+
+        Ok(Self {
+            camera,
+            materials: tileset_materials,
+            chunks,
+            layers: map.layers.iter().map(|l| l.visible).collect(),
+            view: Rect::new(0.0, 0.0, 0.0, 0.0),
+            premultiplied,
+        })
+    }
+
+    /// Material of a tileset, e.g. for tinting with `Materials::set_params`
+    pub fn material(&self, tileset: usize) -> MaterialHandle {
+        self.materials[tileset]
+    }
+
+    /// Uploads the camera and the animated tiles of visible chunks
+    ///
+    /// * `time`: animation clock (animations loop from time zero)
+    pub fn update(
+        &mut self,
+        gpu: &Gpu,
+        map: &TileMap,
+        camera: &Camera2d,
+        time: Duration,
+    ) -> Result<()> {
+        self.camera.write(gpu, &camera.matrix().into_col_array());
+        self.view = camera.view_rect();
+
+        for (visible, layer) in self.layers.iter_mut().zip(&map.layers) {
+            *visible = layer.visible;
+        }
+
+        for chunk in &mut self.chunks {
+            let visible = self.layers[chunk.data.layer] && chunk.data.is_visible(self.view);
+            if !chunk.data.animated || !visible {
+                continue;
+            }
+
+            map.rebuild_chunk(&mut chunk.data, time);
+            let verts = self::vertices(&chunk.data, self.premultiplied);
+            chunk
+                .mesh
+                .update_vertices(&gpu.queue, 0..verts.len() as u32, &verts)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_visible(&self, chunk: &ChunkMesh) -> bool {
+        self.layers[chunk.layer] && chunk.is_visible(self.view)
+    }
+
+    /// Draws the chunks in the camera, in layer order
+    pub fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, materials: &'a Materials) {
+        for chunk in &self.chunks {
+            if !self.is_visible(&chunk.data) {
+                continue;
+            }
+
+            materials.apply(rpass, self.materials[chunk.data.tileset]);
+            rpass.set_bind_group(2, self.camera.bind_group(), &[]);
+            chunk.mesh.draw_range(rpass, 0..chunk.mesh.n_indices(), 0);
+        }
+    }
+}
+
+/// Vertices of a chunk. The layer opacity is multiplied into the color for premultiplied blending
+fn vertices(data: &ChunkMesh, premultiplied: bool) -> Vec<TriVertex> {
+    let mut verts = data.mesh.vertices::<TriVertex>();
+    if premultiplied {
+        for v in &mut verts {
+            let a = v.color.w;
+            v.color = Vec4::new(v.color.x * a, v.color.y * a, v.color.z * a, a);
+        }
+    }
+    verts
+}
+
+fn tilemap_rpip(
+    device: &wgpu::Device,
+    tex_fmt: wgpu::TextureFormat,
+    blend: BlendMode,
+    layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("tilemap-shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../tilemap.wgsl").into()),
+    });
+
+    let rpip_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("tilemap-pipeline-layout"),
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("tilemap-pipeline"),
+        layout: Some(&rpip_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[TriVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: tex_fmt,
+                blend: self::blend_state(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // flipped tiles are mirrored quads
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
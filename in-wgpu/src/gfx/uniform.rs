@@ -0,0 +1,205 @@
+//! Typed uniform buffers
+//!
+//! [`Uniform`] is one value bound at a fixed offset. [`UniformRing`] holds many values of the
+//! same type in one buffer and binds them with dynamic offsets, e.g. for per-draw transforms.
+//! Both validate the layout of `T` on creation (see [`UniformLayout::of`]).
+
+use std::marker::PhantomData;
+
+use anyhow::*;
+use in_common::uniform::{UniformBlock, UniformLayout};
+use wgpu::util::DeviceExt;
+
+use crate::gfx::Gpu;
+
+/// Uniform buffer with its bind group (binding 0)
+#[derive(Debug)]
+pub struct Uniform<T> {
+    buf: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    group: wgpu::BindGroup,
+    _marker: PhantomData<T>,
+}
+
+impl<T: UniformBlock + bytemuck::Pod> Uniform<T> {
+    pub fn new(gpu: &Gpu, value: &T, visibility: wgpu::ShaderStages, label: &str) -> Result<Self> {
+        let block = UniformLayout::of::<T>()?;
+
+        let layout = self::bind_group_layout(gpu, &block, visibility, false, label);
+        let buf = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::bytes_of(value),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buf.as_entire_binding(),
+            }],
+            label: Some(label),
+        });
+
+        Ok(Self {
+            buf,
+            layout,
+            group,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn write(&self, gpu: &Gpu, value: &T) {
+        gpu.queue
+            .write_buffer(&self.buf, 0, bytemuck::bytes_of(value));
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.group
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buf
+    }
+}
+
+/// Per-draw uniform values bound with dynamic offsets (binding 0)
+///
+/// [`push`](Self::push) values while recording draws, [`flush`](Self::flush) them before
+/// submitting the commands, and [`clear`](Self::clear) the ring at the start of the next frame.
+#[derive(Debug)]
+pub struct UniformRing<T> {
+    buf: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    group: wgpu::BindGroup,
+    /// Element size rounded up to `min_uniform_buffer_offset_alignment`
+    stride: u32,
+    capacity: u32,
+    /// Pushed values, `stride` bytes each
+    data: Vec<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: UniformBlock + bytemuck::Pod> UniformRing<T> {
+    /// * `capacity`: maximum number of values per frame
+    pub fn new(
+        gpu: &Gpu,
+        capacity: u32,
+        visibility: wgpu::ShaderStages,
+        label: &str,
+    ) -> Result<Self> {
+        ensure!(capacity > 0, "zero-capacity uniform ring `{}`", label);
+        let block = UniformLayout::of::<T>()?;
+
+        let align = gpu.device.limits().min_uniform_buffer_offset_alignment;
+        let stride = (block.size() as u32).div_ceil(align) * align;
+
+        let layout = self::bind_group_layout(gpu, &block, visibility, true, label);
+        let buf = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (stride * capacity) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                // one element is visible at a time
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buf,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(block.size() as u64),
+                }),
+            }],
+            label: Some(label),
+        });
+
+        Ok(Self {
+            buf,
+            layout,
+            group,
+            stride,
+            capacity,
+            data: Vec::with_capacity((stride * capacity) as usize),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Appends a value and returns its dynamic offset
+    pub fn push(&mut self, value: &T) -> Result<u32> {
+        let len = self.len();
+        ensure!(
+            len < self.capacity,
+            "uniform ring is full ({} values)",
+            self.capacity
+        );
+
+        let offset = len * self.stride;
+        self.data.extend_from_slice(bytemuck::bytes_of(value));
+        self.data.resize(((len + 1) * self.stride) as usize, 0);
+        Ok(offset)
+    }
+
+    /// Uploads the pushed values
+    pub fn flush(&self, gpu: &Gpu) {
+        if !self.data.is_empty() {
+            gpu.queue.write_buffer(&self.buf, 0, &self.data);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Number of pushed values
+    pub fn len(&self) -> u32 {
+        self.data.len() as u32 / self.stride
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Binds the value at a dynamic offset returned by [`push`](Self::push)
+    pub fn bind<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, index: u32, offset: u32) {
+        rpass.set_bind_group(index, &self.group, &[offset]);
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+}
+
+fn bind_group_layout(
+    gpu: &Gpu,
+    block: &UniformLayout,
+    visibility: wgpu::ShaderStages,
+    has_dynamic_offset: bool,
+    label: &str,
+) -> wgpu::BindGroupLayout {
+    gpu.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset,
+                    // rejects shaders declaring a bigger block
+                    min_binding_size: wgpu::BufferSize::new(block.size() as u64),
+                },
+                count: None,
+            }],
+            label: Some(label),
+        })
+}
@@ -1,2 +1,5 @@
+// lets `#[derive(Vertex)]` refer to `::in_wgpu` from inside this crate
+extern crate self as in_wgpu;
+
 pub mod app;
 pub mod gfx;
@@ -1,9 +1,12 @@
 //! Draw triangle with [`wgpu`]
 
+use std::time::Instant;
+
 use anyhow::*;
 
 use sdl2::event::{Event, WindowEvent};
 
+use in_common::input::{ActionMap, Controllers, Input};
 use in_wgpu::gfx::WindowWrapper;
 
 fn main() -> Result<()> {
@@ -29,13 +32,25 @@ fn main() -> Result<()> {
 
     let mut app = pollster::block_on(in_wgpu::app::App::new(&window)).map_err(Error::msg)?;
 
+    let mut controllers = Controllers::new(&window.sdl)?;
+    let actions = ActionMap::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/actions.txt"))?;
+    let mut input = Input::new(actions);
+    input.set_dpi_scale(window.dpi_scale());
+
+    let mut last_frame = Instant::now();
     'running: loop {
         for event in pump.poll_iter() {
+            controllers.event(&event);
+            input.event(&event);
+
             match event {
                 Event::Quit { .. } => break 'running,
                 Event::Window { win_event, .. } => match win_event {
                     WindowEvent::Resized(_w, _h) => {
                         app.gpu.on_resize(&window);
+                        app.clip
+                            .set_viewport(window.fb_size_u(), window.dpi_scale());
+                        input.set_dpi_scale(window.dpi_scale());
                     }
                     _ => {}
                 },
@@ -43,7 +58,16 @@ fn main() -> Result<()> {
             }
         }
 
+        if input.action_pressed("quit") {
+            break 'running;
+        }
+
+        let now = Instant::now();
+        app.update(&input, now - last_frame);
+        last_frame = now;
+
         app.render().map_err(Error::msg)?;
+        input.end_frame();
 
         // super-dirty around 60 FPS
         std::thread::sleep(std::time::Duration::from_micros(1000 / 60));
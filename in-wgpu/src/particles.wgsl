@@ -0,0 +1,62 @@
+// Particles as instanced quads
+
+struct InstanceInput {
+    [[location(0)]] pos: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+    [[location(2)]] size: f32;
+    [[location(3)]] age: f32;
+    [[location(4)]] life: f32;
+};
+
+struct VertexOutput {
+    // clip position
+    [[builtin(position)]] pos: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+    // [-1, 1]
+    [[location(1)]] corner: vec2<f32>;
+};
+
+struct Camera {
+    // world (pixels) to clip
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex: u32,
+    p: InstanceInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex];
+
+    var out: VertexOutput;
+    out.color = p.color;
+    out.corner = corner;
+    if (p.age >= p.life) {
+        // dead: degenerate triangle out of the screen
+        out.pos = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    let world = p.pos + corner * p.size * 0.5;
+    out.pos = camera.view_proj * vec4<f32>(world, 0.0, 1.0);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // soft disc
+    let alpha = in.color.a * clamp(2.0 - 2.0 * length(in.corner), 0.0, 1.0);
+    // premultiplied
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
@@ -0,0 +1,132 @@
+// Particle simulation: one invocation per particle slot
+
+struct Particle {
+    pos: vec2<f32>;
+    vel: vec2<f32>;
+    color: vec4<f32>;
+    size: f32;
+    age: f32;
+    // dead if `age >= life`
+    life: f32;
+    emitter: u32;
+};
+
+struct Particles {
+    data: array<Particle>;
+};
+
+struct Emitter {
+    pos: vec2<f32>;
+    // radians
+    angle: f32;
+    spread: f32;
+    // (min, max)
+    speed: vec2<f32>;
+    life: vec2<f32>;
+    color_start: vec4<f32>;
+    color_end: vec4<f32>;
+    // (start, end)
+    size: vec2<f32>;
+    // particles to spawn this frame
+    count: u32;
+    pad: u32;
+};
+
+struct Emitters {
+    data: array<Emitter>;
+};
+
+struct SimParams {
+    gravity: vec2<f32>;
+    dt: f32;
+    drag: f32;
+    seed: u32;
+    n_emitters: u32;
+    // sum of `Emitter::count`
+    n_spawn: u32;
+    pad: u32;
+};
+
+struct Counter {
+    spawned: atomic<u32>;
+};
+
+[[group(0), binding(0)]]
+var<storage, read_write> particles: Particles;
+[[group(0), binding(1)]]
+var<storage, read> emitters: Emitters;
+[[group(0), binding(2)]]
+var<uniform> params: SimParams;
+[[group(0), binding(3)]]
+var<storage, read_write> counter: Counter;
+
+// PCG hash
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// [0, 1)
+fn rand(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
+
+fn spawn(i: u32, k: u32) {
+    // find the emitter of the `k`-th spawn
+    var e = 0u;
+    var rest = k;
+    loop {
+        if (e + 1u >= params.n_emitters || rest < emitters.data[e].count) {
+            break;
+        }
+        rest = rest - emitters.data[e].count;
+        e = e + 1u;
+    }
+    let em = emitters.data[e];
+
+    var state = hash(i ^ hash(params.seed));
+    let angle = em.angle + (rand(&state) - 0.5) * em.spread;
+    let speed = mix(em.speed.x, em.speed.y, rand(&state));
+
+    var p: Particle;
+    p.pos = em.pos;
+    p.vel = vec2<f32>(cos(angle), sin(angle)) * speed;
+    p.color = em.color_start;
+    p.size = em.size.x;
+    p.age = 0.0;
+    p.life = mix(em.life.x, em.life.y, rand(&state));
+    p.emitter = e;
+    particles.data[i] = p;
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let i = id.x;
+    if (i >= arrayLength(&particles.data)) {
+        return;
+    }
+
+    var p = particles.data[i];
+    if (p.age >= p.life) {
+        if (params.n_spawn > 0u) {
+            let k = atomicAdd(&counter.spawned, 1u);
+            if (k < params.n_spawn) {
+                spawn(i, k);
+            }
+        }
+        return;
+    }
+
+    p.vel = (p.vel + params.gravity * params.dt) * max(1.0 - params.drag * params.dt, 0.0);
+    p.pos = p.pos + p.vel * params.dt;
+    p.age = p.age + params.dt;
+
+    let em = emitters.data[p.emitter];
+    let t = clamp(p.age / max(p.life, 0.0001), 0.0, 1.0);
+    p.color = mix(em.color_start, em.color_end, t);
+    p.size = mix(em.size.x, em.size.y, t);
+
+    particles.data[i] = p;
+}
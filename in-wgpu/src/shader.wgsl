@@ -11,6 +11,15 @@ struct VertexOutput {
     [[location(1)]] uv: vec2<f32>;
 };
 
+struct MaterialParams {
+    tint: vec4<f32>;
+    uv_offset: vec2<f32>;
+    uv_scale: vec2<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: MaterialParams;
+
 [[stage(vertex)]]
 fn vs_main(
     model: VertexInput,
@@ -18,7 +27,7 @@ fn vs_main(
     var out: VertexOutput;
     out.pos = vec4<f32>(model.pos, 0.0, 1.0);
     out.color = model.color;
-    out.uv = model.uv;
+    out.uv = model.uv * material.uv_scale + material.uv_offset;
     return out;
 }
 
@@ -30,6 +39,5 @@ var s_diffuse: sampler;
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let color = textureSample(t_diffuse, s_diffuse, in.uv);
-    // return color * in.color;
-    return color;
+    return color * in.color * material.tint;
 }